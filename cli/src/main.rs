@@ -0,0 +1,227 @@
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use sdfer::export::{write_3mf, write_mesh, Format};
+use sdfer::mesh::{mesh_parts, mesh_primitive, MeshSettings, Mesher, Resolution};
+use sdfer::primitive::{from_json, from_ron, to_json, to_ron, BoundingBox, Primitive};
+use sdfer::script_engine::{RhaiScriptEngine, ScriptEngine};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+extern crate nalgebra_glm as glm;
+
+#[derive(Parser)]
+#[command(version, about = "Script based CAD using signed distance functions.")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Evaluate a Rhai script and write the resulting object as a mesh.
+    Mesh {
+        /// The Rhai script to evaluate, or an object saved as JSON or RON.
+        script: PathBuf,
+        /// Where to write the mesh to. The extension (stl, obj, ply or 3mf) selects the format.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Write ASCII instead of binary STL.
+        #[arg(long)]
+        ascii: bool,
+        /// Override a parameter the script declares with param(), e.g. --set wall=3.
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_assignment)]
+        parameters: Vec<(String, f32)>,
+        #[command(flatten)]
+        settings: MeshOptions,
+    },
+    /// Evaluate a Rhai script and save the resulting object, so it can be meshed without the script.
+    Save {
+        /// The Rhai script to evaluate.
+        script: PathBuf,
+        /// Where to write the object to. The extension (json or ron) selects the format.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Override a parameter the script declares with param(), e.g. --set wall=3.
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_assignment)]
+        parameters: Vec<(String, f32)>,
+    },
+}
+
+/// Overrides for the mesh settings of the script.
+#[derive(Args)]
+struct MeshOptions {
+    /// Edge length of the meshing cells.
+    #[arg(short, long, conflicts_with = "depth")]
+    resolution: Option<f32>,
+    /// Octree depth, as an alternative to --resolution.
+    #[arg(long)]
+    depth: Option<usize>,
+    /// Region to mesh as min_x,min_y,min_z,max_x,max_y,max_z.
+    #[arg(long, value_parser = parse_bounds, allow_hyphen_values = true)]
+    bounds: Option<BoundingBox>,
+    /// Meshing algorithm, dual_contouring (adaptive, keeps sharp edges) or marching_cubes.
+    #[arg(long, value_parser = Mesher::from_name)]
+    mesher: Option<Mesher>,
+    /// Step used for estimating normals where they are not computed analytically.
+    #[arg(long)]
+    normal_epsilon: Option<f32>,
+}
+
+impl MeshOptions {
+    fn apply(&self, settings: &mut MeshSettings) {
+        if let Some(resolution) = self.resolution {
+            settings.resolution = Resolution::CellSize(resolution);
+        }
+        if let Some(depth) = self.depth {
+            settings.resolution = Resolution::Depth(depth);
+        }
+        if self.bounds.is_some() {
+            settings.bounds = self.bounds;
+        }
+        if let Some(mesher) = self.mesher {
+            settings.mesher = mesher;
+        }
+        if let Some(normal_epsilon) = self.normal_epsilon {
+            settings.normal_epsilon = normal_epsilon;
+        }
+    }
+}
+
+fn parse_bounds(s: &str) -> Result<BoundingBox> {
+    let b = s
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    if b.len() != 6 {
+        bail!("expected 6 comma separated numbers, got {}.", b.len());
+    }
+    Ok(BoundingBox::new(
+        glm::vec3(b[0], b[1], b[2]),
+        glm::vec3(b[3], b[4], b[5]),
+    ))
+}
+
+fn parse_assignment(s: &str) -> Result<(String, f32)> {
+    let (name, value) = s
+        .split_once('=')
+        .with_context(|| format!("expected NAME=VALUE, got {}.", s))?;
+    Ok((name.trim().to_string(), value.trim().parse()?))
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Mesh {
+            script,
+            output,
+            ascii,
+            parameters,
+            settings,
+        } => mesh(&script, &output, ascii, &parameters, &settings),
+        Command::Save {
+            script,
+            output,
+            parameters,
+        } => save(&script, &output, &parameters),
+    }
+}
+
+/// Returns the object in `input` and how the script wants it meshed.
+fn load(input: &Path, parameters: &[(String, f32)]) -> Result<(Box<dyn Primitive>, MeshSettings)> {
+    let source = std::fs::read_to_string(input)
+        .with_context(|| format!("Cannot read {}.", input.display()))?;
+    let saved = match extension(input) {
+        Some("json") => Some(from_json(&source)),
+        Some("ron") => Some(from_ron(&source)),
+        _ => None,
+    };
+    if let Some(primitive) = saved {
+        if !parameters.is_empty() {
+            bail!("--set only applies to scripts.");
+        }
+        let primitive = primitive.with_context(|| format!("Cannot load {}.", input.display()))?;
+        return Ok((primitive, MeshSettings::default()));
+    }
+    let mut engine = RhaiScriptEngine::new();
+    engine.on_print(|s| println!("{}", s));
+    for (name, value) in parameters {
+        engine.set_parameter(name, *value);
+    }
+    let primitive = engine.eval(&source)?;
+    let declared = engine.parameters();
+    for (name, _) in parameters {
+        if !declared.iter().any(|p| p.name == *name) {
+            let names = declared.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+            bail!(
+                "The script has no parameter {}. It declares: {}.",
+                name,
+                names.join(", ")
+            );
+        }
+    }
+    Ok((primitive, engine.mesh_settings()))
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+fn mesh(
+    script: &Path,
+    output: &Path,
+    ascii: bool,
+    parameters: &[(String, f32)],
+    options: &MeshOptions,
+) -> Result<()> {
+    let (primitive, mut settings) = load(script, parameters)?;
+    options.apply(&mut settings);
+    let format = output_format(output, ascii)?;
+    // Mesh before creating the file, so failures do not leave an empty file behind.
+    let (mesh, parts) = if format == Format::ThreeMf {
+        // 3MF keeps the named parts of the object apart.
+        (None, mesh_parts(&*primitive, &settings)?)
+    } else {
+        (Some(mesh_primitive(&*primitive, &settings)?), Vec::new())
+    };
+    let file =
+        File::create(output).with_context(|| format!("Cannot create {}.", output.display()))?;
+    let mut writer = BufWriter::new(file);
+    let triangles = match mesh {
+        Some(mesh) => {
+            write_mesh(&mesh, format, &mut writer)?;
+            mesh.triangles.len()
+        }
+        None => {
+            write_3mf(&parts, &mut writer)?;
+            parts.iter().map(|p| p.mesh.triangles.len()).sum()
+        }
+    };
+    writer.flush()?;
+    eprintln!("Wrote {} triangles to {}.", triangles, output.display());
+    Ok(())
+}
+
+fn save(script: &Path, output: &Path, parameters: &[(String, f32)]) -> Result<()> {
+    let (primitive, _) = load(script, parameters)?;
+    let text = match extension(output) {
+        Some("json") => to_json(&*primitive)?,
+        Some("ron") => to_ron(&*primitive)?,
+        _ => bail!(
+            "Cannot tell the format of {}, use json or ron.",
+            output.display()
+        ),
+    };
+    std::fs::write(output, text).with_context(|| format!("Cannot write {}.", output.display()))?;
+    eprintln!("Wrote {}.", output.display());
+    Ok(())
+}
+
+fn output_format(output: &Path, ascii: bool) -> Result<Format> {
+    let extension = extension(output)
+        .with_context(|| format!("Cannot tell the format of {}.", output.display()))?;
+    match (Format::from_extension(extension)?, ascii) {
+        (Format::BinaryStl, true) => Ok(Format::AsciiStl),
+        (format, false) => Ok(format),
+        (_, true) => bail!("--ascii is only supported for STL."),
+    }
+}
@@ -0,0 +1,49 @@
+use crate::mesh::TriangleMesh;
+use anyhow::{bail, Result};
+use std::io::Write;
+
+mod obj;
+pub use obj::write_obj;
+
+mod ply;
+pub use ply::write_ply;
+
+mod stl;
+pub use stl::{write_ascii_stl, write_binary_stl};
+
+mod threemf;
+pub use threemf::{write_3mf, write_3mf_mesh};
+
+/// The mesh file formats sdfer can write.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    BinaryStl,
+    AsciiStl,
+    Obj,
+    Ply,
+    ThreeMf,
+}
+
+impl Format {
+    /// Picks the format from a file extension, preferring binary STL for `.stl`.
+    pub fn from_extension(extension: &str) -> Result<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "stl" => Ok(Format::BinaryStl),
+            "obj" => Ok(Format::Obj),
+            "ply" => Ok(Format::Ply),
+            "3mf" => Ok(Format::ThreeMf),
+            _ => bail!("unsupported mesh format: {}", extension),
+        }
+    }
+}
+
+/// Writes `mesh` in the given `format`.
+pub fn write_mesh(mesh: &TriangleMesh, format: Format, w: &mut impl Write) -> Result<()> {
+    match format {
+        Format::BinaryStl => write_binary_stl(mesh, w),
+        Format::AsciiStl => write_ascii_stl(mesh, w),
+        Format::Obj => write_obj(mesh, w),
+        Format::Ply => write_ply(mesh, w),
+        Format::ThreeMf => write_3mf_mesh(mesh, w),
+    }
+}
@@ -0,0 +1,20 @@
+use crate::mesh::TriangleMesh;
+use anyhow::Result;
+use std::io::Write;
+
+/// Writes `mesh` as Wavefront OBJ, keeping shared vertices and their normals.
+pub fn write_obj(mesh: &TriangleMesh, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "# sdfer")?;
+    for v in &mesh.vertices {
+        writeln!(w, "v {} {} {}", v[0], v[1], v[2])?;
+    }
+    for n in &mesh.normals {
+        writeln!(w, "vn {} {} {}", n[0], n[1], n[2])?;
+    }
+    for triangle in &mesh.triangles {
+        // OBJ indices are 1-based.
+        let [a, b, c] = triangle.map(|i| i + 1);
+        writeln!(w, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use crate::mesh::TriangleMesh;
+use anyhow::Result;
+use std::io::Write;
+
+/// Writes `mesh` as binary little endian PLY with per-vertex normals.
+pub fn write_ply(mesh: &TriangleMesh, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "ply")?;
+    writeln!(w, "format binary_little_endian 1.0")?;
+    writeln!(w, "comment sdfer")?;
+    writeln!(w, "element vertex {}", mesh.vertices.len())?;
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(w, "property float {}", property)?;
+    }
+    writeln!(w, "element face {}", mesh.triangles.len())?;
+    writeln!(w, "property list uchar uint vertex_indices")?;
+    writeln!(w, "end_header")?;
+    for (v, n) in mesh.vertices.iter().zip(&mesh.normals) {
+        for c in v.iter().chain(n.iter()) {
+            w.write_all(&c.to_le_bytes())?;
+        }
+    }
+    for triangle in &mesh.triangles {
+        w.write_all(&[3])?;
+        for i in triangle {
+            w.write_all(&i.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
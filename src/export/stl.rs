@@ -0,0 +1,49 @@
+use crate::mesh::TriangleMesh;
+use anyhow::Result;
+use std::io::Write;
+
+/// Name written into the STL header and `solid` line.
+const SOLID_NAME: &str = "sdfer";
+
+/// Writes `mesh` as binary STL.
+pub fn write_binary_stl(mesh: &TriangleMesh, w: &mut impl Write) -> Result<()> {
+    let mut header = [0_u8; 80];
+    header[..SOLID_NAME.len()].copy_from_slice(SOLID_NAME.as_bytes());
+    w.write_all(&header)?;
+    w.write_all(&(mesh.triangles.len() as u32).to_le_bytes())?;
+    for triangle in &mesh.triangles {
+        let normal = mesh.face_normal(triangle);
+        write_vec3(w, &normal)?;
+        for i in triangle {
+            write_vec3(w, &mesh.vertices[*i as usize])?;
+        }
+        // Attribute byte count.
+        w.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+/// Writes `mesh` as ASCII STL.
+pub fn write_ascii_stl(mesh: &TriangleMesh, w: &mut impl Write) -> Result<()> {
+    writeln!(w, "solid {}", SOLID_NAME)?;
+    for triangle in &mesh.triangles {
+        let n = mesh.face_normal(triangle);
+        writeln!(w, "  facet normal {:e} {:e} {:e}", n[0], n[1], n[2])?;
+        writeln!(w, "    outer loop")?;
+        for i in triangle {
+            let v = mesh.vertices[*i as usize];
+            writeln!(w, "      vertex {:e} {:e} {:e}", v[0], v[1], v[2])?;
+        }
+        writeln!(w, "    endloop")?;
+        writeln!(w, "  endfacet")?;
+    }
+    writeln!(w, "endsolid {}", SOLID_NAME)?;
+    Ok(())
+}
+
+fn write_vec3(w: &mut impl Write, v: &glm::Vec3) -> Result<()> {
+    for c in v.iter() {
+        w.write_all(&c.to_le_bytes())?;
+    }
+    Ok(())
+}
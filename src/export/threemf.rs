@@ -0,0 +1,152 @@
+use crate::mesh::{PartMesh, TriangleMesh};
+use anyhow::Result;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+  <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+  <Default Extension="model" ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>
+</Types>
+"#;
+
+const RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Target="/3D/3dmodel.model" Id="rel0" Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>
+</Relationships>
+"#;
+
+/// Writes all `parts` as separate objects of a single 3MF package.
+pub fn write_3mf(parts: &[PartMesh], w: &mut impl Write) -> Result<()> {
+    let objects = parts
+        .iter()
+        .map(|part| Object {
+            name: &part.name,
+            color: part.color,
+            mesh: &part.mesh,
+        })
+        .collect::<Vec<_>>();
+    write_package(&objects, w)
+}
+
+/// Writes `mesh` as the only object of a 3MF package.
+pub fn write_3mf_mesh(mesh: &TriangleMesh, w: &mut impl Write) -> Result<()> {
+    let object = Object {
+        name: "sdfer",
+        color: None,
+        mesh,
+    };
+    write_package(&[object], w)
+}
+
+/// A borrowed view of what 3MF stores about an object.
+struct Object<'a> {
+    name: &'a str,
+    color: Option<glm::Vec3>,
+    mesh: &'a TriangleMesh,
+}
+
+fn write_package(parts: &[Object], w: &mut impl Write) -> Result<()> {
+    // Zip needs to seek, so assemble the package in memory.
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    zip.start_file("[Content_Types].xml", options)?;
+    zip.write_all(CONTENT_TYPES.as_bytes())?;
+    zip.start_file("_rels/.rels", options)?;
+    zip.write_all(RELATIONSHIPS.as_bytes())?;
+    zip.start_file("3D/3dmodel.model", options)?;
+    write_model(parts, &mut zip)?;
+    w.write_all(&zip.finish()?.into_inner())?;
+    Ok(())
+}
+
+fn write_model(parts: &[Object], w: &mut impl Write) -> Result<()> {
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<model unit="millimeter" xml:lang="en-US" xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">"#
+    )?;
+    writeln!(w, "  <resources>")?;
+    // Object ids start after the base materials, which always get id 1.
+    let materials_id = 1;
+    if parts.iter().any(|p| p.color.is_some()) {
+        writeln!(w, r#"    <basematerials id="{}">"#, materials_id)?;
+        for part in parts {
+            if let Some(color) = part.color {
+                writeln!(
+                    w,
+                    r#"      <base name="{}" displaycolor="{}"/>"#,
+                    escape(part.name),
+                    display_color(&color)
+                )?;
+            }
+        }
+        writeln!(w, "    </basematerials>")?;
+    }
+    // Colored parts refer to their base material by its position in the list above.
+    let mut next_material = 0..;
+    for (i, part) in parts.iter().enumerate() {
+        let material = match part.color {
+            Some(_) => format!(
+                r#" pid="{}" pindex="{}""#,
+                materials_id,
+                next_material.next().unwrap()
+            ),
+            None => String::new(),
+        };
+        writeln!(
+            w,
+            r#"    <object id="{}" type="model" name="{}"{}>"#,
+            object_id(i),
+            escape(part.name),
+            material
+        )?;
+        writeln!(w, "      <mesh>")?;
+        writeln!(w, "        <vertices>")?;
+        for v in &part.mesh.vertices {
+            writeln!(
+                w,
+                r#"          <vertex x="{}" y="{}" z="{}"/>"#,
+                v[0], v[1], v[2]
+            )?;
+        }
+        writeln!(w, "        </vertices>")?;
+        writeln!(w, "        <triangles>")?;
+        for t in &part.mesh.triangles {
+            writeln!(
+                w,
+                r#"          <triangle v1="{}" v2="{}" v3="{}"/>"#,
+                t[0], t[1], t[2]
+            )?;
+        }
+        writeln!(w, "        </triangles>")?;
+        writeln!(w, "      </mesh>")?;
+        writeln!(w, "    </object>")?;
+    }
+    writeln!(w, "  </resources>")?;
+    writeln!(w, "  <build>")?;
+    for i in 0..parts.len() {
+        writeln!(w, r#"    <item objectid="{}"/>"#, object_id(i))?;
+    }
+    writeln!(w, "  </build>")?;
+    writeln!(w, "</model>")?;
+    Ok(())
+}
+
+fn object_id(part_index: usize) -> usize {
+    part_index + 2
+}
+
+fn display_color(color: &glm::Vec3) -> String {
+    let [r, g, b] = [color[0], color[1], color[2]].map(|c| (c * 255.).round() as u8);
+    format!("#{:02X}{:02X}{:02X}FF", r, g, b)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,544 @@
+//! Evaluates expressions on the CPU, following GLSL semantics.
+//!
+//! Building expressions from constants uses the same functions, so evaluating a distance at a
+//! known point never builds a tree in the first place.
+
+use super::{BinaryOp, Builtin, Expr, Kind, Node, Type, Value, Var};
+use std::cell::RefCell;
+
+/// Evaluates an expression without free variables.
+pub fn eval(expr: &Expr) -> Value {
+    Evaluator {
+        variables: Vec::new(),
+    }
+    .eval(expr)
+}
+
+struct Evaluator {
+    /// Bound variables by id, innermost last.
+    variables: Vec<(u32, Value)>,
+}
+
+impl Evaluator {
+    fn eval(&mut self, expr: &Expr) -> Value {
+        let node = match &expr.kind {
+            Kind::Constant(value) => return *value,
+            Kind::Node(node) => node,
+        };
+        match &**node {
+            Node::Variable(var) => {
+                self.variables
+                    .iter()
+                    .rev()
+                    .find(|(id, _)| *id == var.id)
+                    .unwrap_or_else(|| panic!("unbound variable {}", var.name))
+                    .1
+            }
+            Node::Negate(a) => negate(self.eval(a)),
+            Node::Binary(op, a, b) => binary(*op, self.eval(a), self.eval(b)),
+            Node::Builtin(f, args) => {
+                let mut values = [Value::Float(0.); 4];
+                for (value, a) in values.iter_mut().zip(args) {
+                    *value = self.eval(a);
+                }
+                builtin(*f, &values[..args.len()])
+            }
+            Node::Swizzle(a, components) => swizzle(&self.eval(a), components),
+            Node::Select(condition, a, b) => match self.eval(condition) {
+                Value::Bool(true) => self.eval(a),
+                Value::Bool(false) => self.eval(b),
+                v => panic!("expected bool, got {:?}", v),
+            },
+            Node::Shortcut(_, value, _) => self.eval(value),
+            Node::Let(var, value, body) => {
+                let value = self.eval(value);
+                self.variables.push((var.id, value));
+                let result = self.eval(body);
+                self.variables.pop();
+                result
+            }
+            Node::Fold(accumulator, initial, elements, body) => {
+                let mut value = self.eval(initial);
+                for i in 0..elements[0].1.len() {
+                    self.variables.push((accumulator.id, value));
+                    for (var, array) in elements {
+                        self.variables.push((var.id, array[i]));
+                    }
+                    value = self.eval(body);
+                    self.variables
+                        .truncate(self.variables.len() - elements.len() - 1);
+                }
+                value
+            }
+            Node::Call(f, args) => {
+                let arguments = args
+                    .iter()
+                    .map(|a| Expr::from(self.eval(a)))
+                    .collect::<Vec<_>>();
+                eval(&(f.apply)(&arguments))
+            }
+        }
+    }
+}
+
+/// An expression compiled into instructions on single floats, for evaluating it for many values
+/// of its variables. This skips building the expression again for every value, costs nothing
+/// for swizzles and constructors, and selects jump over the branch they do not take.
+pub struct Program {
+    instructions: Vec<Instruction>,
+    /// The components of the variables, followed by constants and intermediate results.
+    registers: RefCell<Vec<f32>>,
+    result: Slot,
+}
+
+/// The registers holding the components of a value, with booleans stored as 0 or 1. Matrices
+/// are stored column by column.
+#[derive(Clone)]
+struct Slot {
+    ty: Type,
+    registers: Vec<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Unary {
+    Negate,
+    Abs,
+    Sign,
+    Sqrt,
+    Round,
+    Sin,
+    Cos,
+    Exp2,
+    Log2,
+}
+
+#[derive(Clone, Copy)]
+enum Binary {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Less,
+    Greater,
+    Min,
+    Max,
+    Mod,
+    Atan2,
+}
+
+/// An operation writing to the register given first.
+enum Instruction {
+    Unary(Unary, usize, usize),
+    Binary(Binary, usize, usize, usize),
+    /// The dot product of the vectors starting at the given registers, of the given length.
+    Dot(usize, [usize; 3], [usize; 3], usize),
+    Copy(usize, usize),
+    /// Continues at the given instruction unless the condition is true.
+    JumpUnless(usize, usize),
+    Jump(usize),
+}
+
+impl Program {
+    pub fn new(expr: &Expr, variables: &[Var]) -> Program {
+        let mut compiler = Compiler {
+            instructions: Vec::new(),
+            registers: Vec::new(),
+            variables: Vec::new(),
+        };
+        for var in variables {
+            let slot = compiler.slot(var.ty);
+            compiler.variables.push((var.id, slot));
+        }
+        let result = compiler.compile(expr);
+        Program {
+            instructions: compiler.instructions,
+            registers: RefCell::new(compiler.registers),
+            result,
+        }
+    }
+    /// Evaluates the expression with the variables set to `values`, in the order given to
+    /// [`Program::new`].
+    pub fn eval(&self, values: &[Value]) -> Value {
+        let mut r = self.registers.borrow_mut();
+        let mut start = 0;
+        for value in values {
+            let n = value.ty().components();
+            for i in 0..n {
+                r[start + i] = component(value, i);
+            }
+            start += n;
+        }
+        let mut next = 0;
+        while let Some(instruction) = self.instructions.get(next) {
+            next += 1;
+            match *instruction {
+                Instruction::Unary(op, d, a) => r[d] = unary_scalar(op, r[a]),
+                Instruction::Binary(op, d, a, b) => r[d] = binary_scalar(op, r[a], r[b]),
+                Instruction::Dot(d, a, b, n) => {
+                    r[d] = (0..n).map(|i| r[a[i]] * r[b[i]]).sum();
+                }
+                Instruction::Copy(d, a) => r[d] = r[a],
+                Instruction::JumpUnless(condition, target) => {
+                    if r[condition] == 0. {
+                        next = target;
+                    }
+                }
+                Instruction::Jump(target) => next = target,
+            }
+        }
+        let c = |i: usize| r[self.result.registers[i]];
+        match self.result.ty {
+            Type::Bool => Value::Bool(c(0) != 0.),
+            Type::Float => Value::Float(c(0)),
+            Type::Vec2 => Value::Vec2(glm::vec2(c(0), c(1))),
+            Type::Vec3 => Value::Vec3(glm::vec3(c(0), c(1), c(2))),
+            Type::Mat2 => Value::Mat2(glm::Mat2::from_fn(|i, j| c(j * 2 + i))),
+            Type::Mat3 => Value::Mat3(glm::Mat3::from_fn(|i, j| c(j * 3 + i))),
+        }
+    }
+}
+
+fn unary_scalar(op: Unary, a: f32) -> f32 {
+    match op {
+        Unary::Negate => -a,
+        Unary::Abs => a.abs(),
+        Unary::Sign => {
+            if a == 0. {
+                0.
+            } else {
+                a.signum()
+            }
+        }
+        Unary::Sqrt => a.sqrt(),
+        Unary::Round => a.round(),
+        Unary::Sin => a.sin(),
+        Unary::Cos => a.cos(),
+        Unary::Exp2 => a.exp2(),
+        Unary::Log2 => a.log2(),
+    }
+}
+
+fn binary_scalar(op: Binary, a: f32, b: f32) -> f32 {
+    match op {
+        Binary::Add => a + b,
+        Binary::Sub => a - b,
+        Binary::Mul => a * b,
+        Binary::Div => a / b,
+        Binary::Less => (a < b) as u8 as f32,
+        Binary::Greater => (a > b) as u8 as f32,
+        Binary::Min => a.min(b),
+        Binary::Max => a.max(b),
+        Binary::Mod => a - b * (a / b).floor(),
+        Binary::Atan2 => a.atan2(b),
+    }
+}
+
+struct Compiler {
+    instructions: Vec<Instruction>,
+    registers: Vec<f32>,
+    /// Slots of the bound variables by id, innermost last.
+    variables: Vec<(u32, Slot)>,
+}
+
+impl Compiler {
+    /// Returns a slot of new registers.
+    fn slot(&mut self, ty: Type) -> Slot {
+        let start = self.registers.len();
+        self.registers.resize(start + ty.components(), 0.);
+        Slot {
+            ty,
+            registers: (start..self.registers.len()).collect(),
+        }
+    }
+    fn constant(&mut self, value: &Value) -> Slot {
+        let slot = self.slot(value.ty());
+        for (i, r) in slot.registers.iter().enumerate() {
+            self.registers[*r] = match value {
+                Value::Bool(b) => *b as u8 as f32,
+                _ => component(value, i),
+            };
+        }
+        slot
+    }
+    /// Applies `op` component wise, repeating floats for every component of the other operand.
+    fn zip(&mut self, op: Binary, a: &Slot, b: &Slot, ty: Type) -> Slot {
+        let result = self.slot(ty);
+        for (i, d) in result.registers.iter().enumerate() {
+            let a = a.registers[i.min(a.registers.len() - 1)];
+            let b = b.registers[i.min(b.registers.len() - 1)];
+            self.instructions.push(Instruction::Binary(op, *d, a, b));
+        }
+        result
+    }
+    fn map(&mut self, op: Unary, a: &Slot) -> Slot {
+        let result = self.slot(a.ty);
+        for (d, a) in result.registers.iter().zip(&a.registers) {
+            self.instructions.push(Instruction::Unary(op, *d, *a));
+        }
+        result
+    }
+    fn dot(&mut self, a: &[usize], b: &[usize]) -> usize {
+        let d = self.slot(Type::Float).registers[0];
+        let vector = |v: &[usize]| std::array::from_fn(|i| v.get(i).copied().unwrap_or(0));
+        self.instructions
+            .push(Instruction::Dot(d, vector(a), vector(b), a.len()));
+        d
+    }
+    /// Adds the instructions computing `expr` and returns the slot holding its value.
+    fn compile(&mut self, expr: &Expr) -> Slot {
+        let node = match &expr.kind {
+            Kind::Constant(value) => return self.constant(value),
+            Kind::Node(node) => node,
+        };
+        let ty = expr.ty;
+        match &**node {
+            Node::Variable(var) => self
+                .variables
+                .iter()
+                .rev()
+                .find(|(id, _)| *id == var.id)
+                .unwrap_or_else(|| panic!("unbound variable {}", var.name))
+                .1
+                .clone(),
+            Node::Negate(a) => {
+                let a = self.compile(a);
+                self.map(Unary::Negate, &a)
+            }
+            Node::Binary(op, a, b) => {
+                let (a, b) = (self.compile(a), self.compile(b));
+                if let (BinaryOp::Mul, Type::Mat2 | Type::Mat3) = (op, a.ty) {
+                    // Row i of the matrix times the vector.
+                    let n = b.registers.len();
+                    let registers = (0..n)
+                        .map(|i| {
+                            let row = (0..n).map(|j| a.registers[j * n + i]).collect::<Vec<_>>();
+                            self.dot(&row, &b.registers)
+                        })
+                        .collect();
+                    return Slot { ty, registers };
+                }
+                let op = match op {
+                    BinaryOp::Add => Binary::Add,
+                    BinaryOp::Sub => Binary::Sub,
+                    BinaryOp::Mul => Binary::Mul,
+                    BinaryOp::Div => Binary::Div,
+                    BinaryOp::Less => Binary::Less,
+                    BinaryOp::Greater => Binary::Greater,
+                };
+                self.zip(op, &a, &b, ty)
+            }
+            Node::Builtin(f, args) => {
+                let args = args.iter().map(|a| self.compile(a)).collect::<Vec<_>>();
+                let unary = |op| (op, &args[0]);
+                let (op, a) = match f {
+                    Builtin::Abs => unary(Unary::Abs),
+                    Builtin::Sign => unary(Unary::Sign),
+                    Builtin::Sqrt => unary(Unary::Sqrt),
+                    Builtin::Round => unary(Unary::Round),
+                    Builtin::Sin => unary(Unary::Sin),
+                    Builtin::Cos => unary(Unary::Cos),
+                    Builtin::Exp2 => unary(Unary::Exp2),
+                    Builtin::Log2 => unary(Unary::Log2),
+                    Builtin::Atan2 => return self.zip(Binary::Atan2, &args[0], &args[1], ty),
+                    Builtin::Min => return self.zip(Binary::Min, &args[0], &args[1], ty),
+                    Builtin::Max => return self.zip(Binary::Max, &args[0], &args[1], ty),
+                    Builtin::Mod => return self.zip(Binary::Mod, &args[0], &args[1], ty),
+                    Builtin::Clamp => {
+                        let lower = self.zip(Binary::Max, &args[0], &args[1], ty);
+                        return self.zip(Binary::Min, &lower, &args[2], ty);
+                    }
+                    Builtin::Length => {
+                        let a = &args[0].registers;
+                        let squared = self.dot(a, a);
+                        let length = self.slot(ty);
+                        self.instructions.push(Instruction::Unary(
+                            Unary::Sqrt,
+                            length.registers[0],
+                            squared,
+                        ));
+                        return length;
+                    }
+                    Builtin::Dot => {
+                        let d = self.dot(&args[0].registers, &args[1].registers);
+                        return Slot {
+                            ty,
+                            registers: vec![d],
+                        };
+                    }
+                    Builtin::Vec2 | Builtin::Vec3 | Builtin::Mat2 => {
+                        let registers = args.iter().flat_map(|a| a.registers.clone()).collect();
+                        return Slot { ty, registers };
+                    }
+                };
+                self.map(op, a)
+            }
+            Node::Swizzle(a, components) => {
+                let a = self.compile(a);
+                Slot {
+                    ty,
+                    registers: components.iter().map(|c| a.registers[*c]).collect(),
+                }
+            }
+            Node::Select(condition, a, b) => {
+                let condition = self.compile(condition).registers[0];
+                let result = self.slot(ty);
+                let jump_to_b = self.instructions.len();
+                self.instructions.push(Instruction::Jump(0));
+                let a = self.compile(a);
+                self.copy(&result, &a);
+                let jump_to_end = self.instructions.len();
+                self.instructions.push(Instruction::Jump(0));
+                self.instructions[jump_to_b] =
+                    Instruction::JumpUnless(condition, self.instructions.len());
+                let b = self.compile(b);
+                self.copy(&result, &b);
+                self.instructions[jump_to_end] = Instruction::Jump(self.instructions.len());
+                result
+            }
+            Node::Shortcut(_, value, _) => self.compile(value),
+            Node::Let(var, value, body) => {
+                let value = self.compile(value);
+                self.variables.push((var.id, value));
+                let result = self.compile(body);
+                self.variables.pop();
+                result
+            }
+            Node::Fold(accumulator, initial, elements, body) => {
+                // The arrays are constant, so the loop gets unrolled.
+                let mut value = self.compile(initial);
+                for i in 0..elements[0].1.len() {
+                    self.variables.push((accumulator.id, value));
+                    for (var, array) in elements {
+                        let element = self.constant(&array[i]);
+                        self.variables.push((var.id, element));
+                    }
+                    value = self.compile(body);
+                    self.variables
+                        .truncate(self.variables.len() - elements.len() - 1);
+                }
+                value
+            }
+            Node::Call(f, args) => {
+                // Functions get inlined, with their parameters bound to the arguments.
+                let args = args.iter().map(|a| self.compile(a)).collect::<Vec<_>>();
+                let depth = self.variables.len();
+                for (parameter, a) in f.parameters.iter().zip(args) {
+                    self.variables.push((parameter.id, a));
+                }
+                let result = self.compile(&f.body);
+                self.variables.truncate(depth);
+                result
+            }
+        }
+    }
+    fn copy(&mut self, to: &Slot, from: &Slot) {
+        for (d, a) in to.registers.iter().zip(&from.registers) {
+            self.instructions.push(Instruction::Copy(*d, *a));
+        }
+    }
+}
+
+fn component(v: &Value, i: usize) -> f32 {
+    match v {
+        Value::Float(f) => *f,
+        Value::Vec2(v) => v[i],
+        Value::Vec3(v) => v[i],
+        Value::Mat2(m) => m[i],
+        Value::Mat3(m) => m[i],
+        Value::Bool(_) => panic!("expected number, got {:?}", v),
+    }
+}
+
+fn map(v: Value, f: impl Fn(f32) -> f32) -> Value {
+    match v {
+        Value::Float(a) => Value::Float(f(a)),
+        Value::Vec2(a) => Value::Vec2(a.map(f)),
+        Value::Vec3(a) => Value::Vec3(a.map(f)),
+        v => panic!("expected scalar or vector, got {:?}", v),
+    }
+}
+
+/// Applies `f` component wise, repeating floats for every component of the other operand.
+fn zip(a: Value, b: Value, f: impl Fn(f32, f32) -> f32) -> Value {
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => Value::Float(f(a, b)),
+        (Value::Vec2(a), Value::Vec2(b)) => Value::Vec2(a.zip_map(&b, f)),
+        (Value::Vec3(a), Value::Vec3(b)) => Value::Vec3(a.zip_map(&b, f)),
+        (Value::Vec2(a), Value::Float(b)) => Value::Vec2(a.map(|a| f(a, b))),
+        (Value::Vec3(a), Value::Float(b)) => Value::Vec3(a.map(|a| f(a, b))),
+        (Value::Float(a), Value::Vec2(b)) => Value::Vec2(b.map(|b| f(a, b))),
+        (Value::Float(a), Value::Vec3(b)) => Value::Vec3(b.map(|b| f(a, b))),
+        (a, b) => panic!("cannot combine {:?} and {:?}", a, b),
+    }
+}
+
+pub(super) fn negate(a: Value) -> Value {
+    map(a, |a| -a)
+}
+
+pub(super) fn binary(op: BinaryOp, a: Value, b: Value) -> Value {
+    match (op, a, b) {
+        (BinaryOp::Add, a, b) => zip(a, b, |a, b| a + b),
+        (BinaryOp::Sub, a, b) => zip(a, b, |a, b| a - b),
+        (BinaryOp::Mul, Value::Mat2(m), Value::Vec2(v)) => Value::Vec2(m * v),
+        (BinaryOp::Mul, Value::Mat3(m), Value::Vec3(v)) => Value::Vec3(m * v),
+        (BinaryOp::Mul, a, b) => zip(a, b, |a, b| a * b),
+        (BinaryOp::Div, a, b) => zip(a, b, |a, b| a / b),
+        (BinaryOp::Less, Value::Float(a), Value::Float(b)) => Value::Bool(a < b),
+        (BinaryOp::Greater, Value::Float(a), Value::Float(b)) => Value::Bool(a > b),
+        (op, a, b) => panic!("cannot apply {:?} to {:?} and {:?}", op, a, b),
+    }
+}
+
+pub(super) fn swizzle(v: &Value, components: &[usize]) -> Value {
+    let c = |i: usize| component(v, components[i]);
+    match components.len() {
+        1 => Value::Float(c(0)),
+        2 => Value::Vec2(glm::vec2(c(0), c(1))),
+        _ => Value::Vec3(glm::vec3(c(0), c(1), c(2))),
+    }
+}
+
+pub(super) fn builtin(f: Builtin, args: &[Value]) -> Value {
+    match f {
+        Builtin::Abs => map(args[0], f32::abs),
+        // Unlike f32::signum, GLSL's sign is zero at zero.
+        Builtin::Sign => map(args[0], |a| if a == 0. { 0. } else { a.signum() }),
+        Builtin::Sqrt => map(args[0], f32::sqrt),
+        Builtin::Round => map(args[0], f32::round),
+        Builtin::Sin => map(args[0], f32::sin),
+        Builtin::Cos => map(args[0], f32::cos),
+        Builtin::Exp2 => map(args[0], f32::exp2),
+        Builtin::Log2 => map(args[0], f32::log2),
+        Builtin::Atan2 => Value::Float(args[0].float().atan2(args[1].float())),
+        Builtin::Min => zip(args[0], args[1], f32::min),
+        Builtin::Max => zip(args[0], args[1], f32::max),
+        Builtin::Clamp => zip(zip(args[0], args[1], f32::max), args[2], f32::min),
+        Builtin::Mod => zip(args[0], args[1], |x, y| x - y * (x / y).floor()),
+        Builtin::Length => match args[0] {
+            Value::Vec2(v) => Value::Float(v.norm()),
+            Value::Vec3(v) => Value::Float(v.norm()),
+            v => panic!("expected vector, got {:?}", v),
+        },
+        Builtin::Dot => match (args[0], args[1]) {
+            (Value::Vec2(a), Value::Vec2(b)) => Value::Float(a.dot(&b)),
+            (Value::Vec3(a), Value::Vec3(b)) => Value::Float(a.dot(&b)),
+            (a, b) => panic!("cannot dot {:?} and {:?}", a, b),
+        },
+        Builtin::Vec2 | Builtin::Vec3 | Builtin::Mat2 => {
+            let mut c = [0_f32; 4];
+            let mut n = 0;
+            for a in args {
+                for i in 0..a.ty().components() {
+                    c[n] = component(a, i);
+                    n += 1;
+                }
+            }
+            match f {
+                Builtin::Vec2 => Value::Vec2(glm::vec2(c[0], c[1])),
+                Builtin::Vec3 => Value::Vec3(glm::vec3(c[0], c[1], c[2])),
+                _ => Value::Mat2(glm::Mat2::from_column_slice(&c)),
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Emits expressions as GLSL.
+
+use super::shader::{self, float, floats, Dialect, Uniforms};
+use super::{Builtin, Expr, Type, Value, Var};
+
+/// Returns GLSL code computing `expr`, where the `inputs` are given as GLSL expressions.
+/// Helper functions get added to `shared_code`.
+pub fn expression(expr: &Expr, inputs: &[(Var, &str)], shared_code: &mut Vec<String>) -> String {
+    shader::expression::<Glsl>(expr, inputs, shared_code, None)
+}
+
+/// Like [`expression`], but reads constants from `uniforms` as long as they fit.
+pub fn expression_with_uniforms(
+    expr: &Expr,
+    inputs: &[(Var, &str)],
+    shared_code: &mut Vec<String>,
+    uniforms: &mut Uniforms,
+) -> String {
+    shader::expression::<Glsl>(expr, inputs, shared_code, Some(uniforms))
+}
+
+/// Returns the declaration of the uniform array.
+pub fn declare_uniforms(uniforms: &Uniforms) -> String {
+    format!("uniform mat4 {}[{}];", uniforms.name(), uniforms.matrices())
+}
+
+pub fn type_name(ty: Type) -> &'static str {
+    Glsl::type_name(ty)
+}
+
+struct Glsl;
+
+impl Dialect for Glsl {
+    const LAZY_SELECT: bool = true;
+
+    fn type_name(ty: Type) -> &'static str {
+        match ty {
+            Type::Bool => "bool",
+            Type::Float => "float",
+            Type::Vec2 => "vec2",
+            Type::Vec3 => "vec3",
+            Type::Mat2 => "mat2",
+            Type::Mat3 => "mat3",
+        }
+    }
+
+    fn constant(value: &Value) -> String {
+        match value {
+            Value::Bool(b) => b.to_string(),
+            Value::Float(f) => float(*f),
+            // Matrices are given column by column in both glm and GLSL.
+            Value::Vec2(v) => format!("vec2({})", floats(v.as_slice())),
+            Value::Vec3(v) => format!("vec3({})", floats(v.as_slice())),
+            Value::Mat2(m) => format!("mat2({})", floats(m.as_slice())),
+            Value::Mat3(m) => format!("mat3({})", floats(m.as_slice())),
+        }
+    }
+
+    fn builtin(f: Builtin, args: &[(String, Type)], _shared_code: &mut Vec<String>) -> String {
+        let name = match f {
+            Builtin::Abs => "abs",
+            Builtin::Sign => "sign",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Round => "round",
+            Builtin::Sin => "sin",
+            Builtin::Cos => "cos",
+            Builtin::Exp2 => "exp2",
+            Builtin::Log2 => "log2",
+            Builtin::Atan2 => "atan",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Clamp => "clamp",
+            Builtin::Mod => "mod",
+            Builtin::Length => "length",
+            Builtin::Dot => "dot",
+            Builtin::Vec2 => "vec2",
+            Builtin::Vec3 => "vec3",
+            Builtin::Mat2 => "mat2",
+        };
+        let args = args.iter().map(|(a, _)| a.as_str()).collect::<Vec<_>>();
+        format!("{}({})", name, args.join(", "))
+    }
+
+    fn select(condition: &str, a: &str, b: &str) -> String {
+        format!("({} ? {} : {})", condition, a, b)
+    }
+
+    fn declare(name: &str, ty: Type, value: Option<&str>) -> String {
+        match value {
+            Some(value) => format!("{} {} = {};", Glsl::type_name(ty), name, value),
+            None => format!("{} {};", Glsl::type_name(ty), name),
+        }
+    }
+
+    fn signature(name: &str, parameters: &[(&str, Type)], result: Type) -> String {
+        let parameters = parameters
+            .iter()
+            .map(|(p, ty)| format!("{} {}", Glsl::type_name(*ty), p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{} {}({})", Glsl::type_name(result), name, parameters)
+    }
+
+    fn declare_array(name: &str, ty: Type, elements: &[String]) -> String {
+        let ty = Glsl::type_name(ty);
+        let n = elements.len();
+        format!(
+            "{} {}[{}] = {}[{}]({});",
+            ty,
+            name,
+            n,
+            ty,
+            n,
+            elements.join(", ")
+        )
+    }
+
+    fn for_loop(index: &str, count: usize) -> String {
+        format!("for (int {i} = 0; {i} < {}; {i}++) {{", count, i = index)
+    }
+}
@@ -0,0 +1,602 @@
+//! An intermediate representation of distance functions.
+//!
+//! Primitives describe their distance once as an expression tree. Backends turn the tree into
+//! shader code or evaluate it on the CPU, so the rendered and the meshed object cannot disagree.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub mod cpu;
+pub mod glsl;
+mod shader;
+pub mod wgsl;
+
+pub use shader::Uniforms;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Type {
+    Bool,
+    Float,
+    Vec2,
+    Vec3,
+    Mat2,
+    Mat3,
+}
+
+impl Type {
+    /// Number of floats in a value of this type.
+    fn components(&self) -> usize {
+        match self {
+            Type::Bool | Type::Float => 1,
+            Type::Vec2 => 2,
+            Type::Vec3 => 3,
+            Type::Mat2 => 4,
+            Type::Mat3 => 9,
+        }
+    }
+    fn is_vector(&self) -> bool {
+        matches!(self, Type::Vec2 | Type::Vec3)
+    }
+    fn is_numeric(&self) -> bool {
+        matches!(self, Type::Float | Type::Vec2 | Type::Vec3)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Float(f32),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Mat2(glm::Mat2),
+    Mat3(glm::Mat3),
+}
+
+impl Value {
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Bool(_) => Type::Bool,
+            Value::Float(_) => Type::Float,
+            Value::Vec2(_) => Type::Vec2,
+            Value::Vec3(_) => Type::Vec3,
+            Value::Mat2(_) => Type::Mat2,
+            Value::Mat3(_) => Type::Mat3,
+        }
+    }
+    pub fn float(&self) -> f32 {
+        match self {
+            Value::Float(f) => *f,
+            v => panic!("expected float, got {:?}", v),
+        }
+    }
+}
+
+static NEXT_VARIABLE: AtomicU32 = AtomicU32::new(0);
+
+/// A variable, bound either by a let expression or as a function parameter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Var {
+    id: u32,
+    name: &'static str,
+    ty: Type,
+}
+
+impl Var {
+    pub fn new(name: &'static str, ty: Type) -> Var {
+        Var {
+            id: NEXT_VARIABLE.fetch_add(1, Ordering::Relaxed),
+            name,
+            ty,
+        }
+    }
+    pub fn expr(&self) -> Expr {
+        Expr::new(Node::Variable(*self), self.ty)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Less,
+    Greater,
+}
+
+/// Functions every backend provides. Component wise unless noted otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Builtin {
+    Abs,
+    Sign,
+    Sqrt,
+    Round,
+    Sin,
+    Cos,
+    Exp2,
+    Log2,
+    /// The angle of the point (x, y) given as (y, x).
+    Atan2,
+    Min,
+    Max,
+    Clamp,
+    /// `x - y * floor(x / y)`, i.e. the result has the sign of `y`.
+    Mod,
+    Length,
+    Dot,
+    Vec2,
+    Vec3,
+    /// A 2x2 matrix given column by column.
+    Mat2,
+}
+
+/// Builds the body of a function for the given arguments.
+type Apply = dyn Fn(&[Expr]) -> Expr;
+
+/// A named function of its parameters, which shader backends emit only once.
+/// Functions of the same name must compute the same.
+pub struct Function {
+    name: &'static str,
+    parameters: Vec<Var>,
+    body: Expr,
+    /// Builds the body for the given arguments, which computes it right away for constants.
+    apply: Box<Apply>,
+}
+
+impl Function {
+    pub fn new<const N: usize>(
+        name: &'static str,
+        parameters: [(&'static str, Type); N],
+        body: impl Fn([Expr; N]) -> Expr + 'static,
+    ) -> Rc<Function> {
+        let apply: Box<Apply> =
+            Box::new(move |args| body(std::array::from_fn(|i| args[i].clone())));
+        let parameters = parameters.map(|(name, ty)| Var::new(name, ty)).to_vec();
+        let body = apply(&parameters.iter().map(Var::expr).collect::<Vec<_>>());
+        Rc::new(Function {
+            name,
+            parameters,
+            body,
+            apply,
+        })
+    }
+    pub fn call<const N: usize>(self: &Rc<Function>, arguments: [Expr; N]) -> Expr {
+        assert!(
+            arguments
+                .iter()
+                .map(Expr::ty)
+                .eq(self.parameters.iter().map(|p| p.ty)),
+            "wrong arguments for {}",
+            self.name
+        );
+        if arguments.iter().all(|a| a.constant().is_some()) {
+            return (self.apply)(&arguments);
+        }
+        Expr::new(Node::Call(self.clone(), arguments.to_vec()), self.body.ty)
+    }
+}
+
+enum Node {
+    Variable(Var),
+    Negate(Expr),
+    Binary(BinaryOp, Expr, Expr),
+    Builtin(Builtin, Vec<Expr>),
+    /// Selects components of a vector by index.
+    Swizzle(Expr, Vec<usize>),
+    /// Evaluates only one of the branches, depending on the condition.
+    Select(Expr, Expr, Expr),
+    /// A value and a cheaper one giving the same where the condition does not hold, see
+    /// [`shortcut`].
+    Shortcut(Expr, Expr, Expr),
+    /// Binds a value to a variable within the body.
+    Let(Var, Expr, Expr),
+    Call(Rc<Function>, Vec<Expr>),
+    /// Starts the accumulator at the initial value and replaces it by the body for every index
+    /// of the arrays, with each element variable bound to the element of its array.
+    Fold(Var, Expr, Vec<(Var, Vec<Value>)>, Expr),
+}
+
+#[derive(Clone)]
+enum Kind {
+    Constant(Value),
+    Node(Rc<Node>),
+}
+
+/// A typed expression. Cloning is cheap, but clones get evaluated again wherever they are used,
+/// so values needed more than once should be bound with [`Expr::bind`].
+///
+/// Operations on constants are computed right away, by the same rules the CPU backend uses.
+/// So building the expression for a known point evaluates it, without building a tree.
+#[derive(Clone)]
+pub struct Expr {
+    kind: Kind,
+    ty: Type,
+    /// Whether this contains let expressions, which shader backends emit as statements.
+    has_let: bool,
+}
+
+impl Expr {
+    fn new(node: Node, ty: Type) -> Expr {
+        let has_let = match &node {
+            Node::Variable(_) => false,
+            Node::Let(..) | Node::Fold(..) => true,
+            Node::Negate(a) | Node::Swizzle(a, _) => a.has_let,
+            Node::Binary(_, a, b) => a.has_let || b.has_let,
+            Node::Select(a, b, c) | Node::Shortcut(a, b, c) => a.has_let || b.has_let || c.has_let,
+            Node::Builtin(_, args) | Node::Call(_, args) => args.iter().any(|a| a.has_let),
+        };
+        Expr {
+            kind: Kind::Node(Rc::new(node)),
+            ty,
+            has_let,
+        }
+    }
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+    /// Returns the value, if this is a constant.
+    pub fn constant(&self) -> Option<Value> {
+        match self.kind {
+            Kind::Constant(value) => Some(value),
+            Kind::Node(_) => None,
+        }
+    }
+
+    /// Makes the value available to `body` without evaluating it more than once.
+    pub fn bind(&self, name: &'static str, body: impl FnOnce(&Expr) -> Expr) -> Expr {
+        match &self.kind {
+            Kind::Constant(_) => return body(self),
+            Kind::Node(node) if matches!(**node, Node::Variable(_)) => return body(self),
+            Kind::Node(_) => {}
+        }
+        let var = Var::new(name, self.ty);
+        let body = body(&var.expr());
+        let ty = body.ty;
+        Expr::new(Node::Let(var, self.clone(), body), ty)
+    }
+
+    fn binary(op: BinaryOp, a: Expr, b: Expr) -> Expr {
+        if let (Kind::Constant(a), Kind::Constant(b)) = (&a.kind, &b.kind) {
+            return cpu::binary(op, *a, *b).into();
+        }
+        let ty = match (op, a.ty, b.ty) {
+            (BinaryOp::Less | BinaryOp::Greater, Type::Float, Type::Float) => Type::Bool,
+            (BinaryOp::Mul, Type::Mat2, Type::Vec2) => Type::Vec2,
+            (BinaryOp::Mul, Type::Mat3, Type::Vec3) => Type::Vec3,
+            (BinaryOp::Less | BinaryOp::Greater, _, _) => {
+                panic!("cannot compare {:?} and {:?}", a.ty, b.ty)
+            }
+            (_, x, y) if x == y && x.is_numeric() => x,
+            (_, Type::Float, y) if y.is_vector() => y,
+            (_, x, Type::Float) if x.is_vector() => x,
+            (op, x, y) => panic!("cannot apply {:?} to {:?} and {:?}", op, x, y),
+        };
+        Expr::new(Node::Binary(op, a, b), ty)
+    }
+
+    fn builtin(f: Builtin, args: &[Expr]) -> Expr {
+        if args.iter().all(|a| matches!(a.kind, Kind::Constant(_))) {
+            let mut values = [Value::Float(0.); 4];
+            for (value, a) in values.iter_mut().zip(args) {
+                *value = a.constant().unwrap();
+            }
+            return cpu::builtin(f, &values[..args.len()]).into();
+        }
+        let ty = |i: usize| args[i].ty;
+        let same_or_float = |i: usize| ty(i) == ty(0) || ty(i) == Type::Float;
+        let ty = match f {
+            Builtin::Abs
+            | Builtin::Sign
+            | Builtin::Sqrt
+            | Builtin::Round
+            | Builtin::Sin
+            | Builtin::Cos
+            | Builtin::Exp2
+            | Builtin::Log2
+                if args.len() == 1 && ty(0).is_numeric() =>
+            {
+                ty(0)
+            }
+            Builtin::Atan2 if args.len() == 2 && ty(0) == Type::Float && ty(1) == Type::Float => {
+                Type::Float
+            }
+            Builtin::Min | Builtin::Max | Builtin::Mod
+                if args.len() == 2 && ty(0).is_numeric() && same_or_float(1) =>
+            {
+                ty(0)
+            }
+            Builtin::Clamp
+                if args.len() == 3
+                    && ty(0).is_numeric()
+                    && same_or_float(1)
+                    && same_or_float(2) =>
+            {
+                ty(0)
+            }
+            Builtin::Length if args.len() == 1 && ty(0).is_vector() => Type::Float,
+            Builtin::Dot if args.len() == 2 && ty(0).is_vector() && ty(0) == ty(1) => Type::Float,
+            Builtin::Vec2 | Builtin::Vec3 | Builtin::Mat2
+                if args.iter().all(|a| a.ty.is_numeric()) =>
+            {
+                let result = match f {
+                    Builtin::Vec2 => Type::Vec2,
+                    Builtin::Vec3 => Type::Vec3,
+                    _ => Type::Mat2,
+                };
+                let components = args.iter().map(|a| a.ty.components()).sum::<usize>();
+                assert_eq!(components, result.components(), "wrong size for {:?}", f);
+                result
+            }
+            _ => panic!(
+                "cannot apply {:?} to {:?}",
+                f,
+                args.iter().map(Expr::ty).collect::<Vec<_>>()
+            ),
+        };
+        Expr::new(Node::Builtin(f, args.to_vec()), ty)
+    }
+
+    fn swizzle(&self, components: &[usize]) -> Expr {
+        assert!(
+            self.ty.is_vector() && components.iter().all(|c| *c < self.ty.components()),
+            "cannot swizzle {:?}",
+            self.ty
+        );
+        let ty = match components.len() {
+            1 => Type::Float,
+            2 => Type::Vec2,
+            3 => Type::Vec3,
+            n => panic!("cannot swizzle {} components", n),
+        };
+        if let Some(value) = self.constant() {
+            return cpu::swizzle(&value, components).into();
+        }
+        Expr::new(Node::Swizzle(self.clone(), components.to_vec()), ty)
+    }
+    pub fn x(&self) -> Expr {
+        self.swizzle(&[0])
+    }
+    pub fn y(&self) -> Expr {
+        self.swizzle(&[1])
+    }
+    pub fn z(&self) -> Expr {
+        self.swizzle(&[2])
+    }
+    pub fn xy(&self) -> Expr {
+        self.swizzle(&[0, 1])
+    }
+
+    pub fn abs(&self) -> Expr {
+        Expr::builtin(Builtin::Abs, std::slice::from_ref(self))
+    }
+    pub fn sign(&self) -> Expr {
+        Expr::builtin(Builtin::Sign, std::slice::from_ref(self))
+    }
+    pub fn sqrt(&self) -> Expr {
+        Expr::builtin(Builtin::Sqrt, std::slice::from_ref(self))
+    }
+    pub fn round(&self) -> Expr {
+        Expr::builtin(Builtin::Round, std::slice::from_ref(self))
+    }
+    pub fn sin(&self) -> Expr {
+        Expr::builtin(Builtin::Sin, std::slice::from_ref(self))
+    }
+    pub fn cos(&self) -> Expr {
+        Expr::builtin(Builtin::Cos, std::slice::from_ref(self))
+    }
+    pub fn exp2(&self) -> Expr {
+        Expr::builtin(Builtin::Exp2, std::slice::from_ref(self))
+    }
+    pub fn log2(&self) -> Expr {
+        Expr::builtin(Builtin::Log2, std::slice::from_ref(self))
+    }
+    /// The angle of the point (x, y), where `self` is y.
+    pub fn atan2(&self, x: impl Into<Expr>) -> Expr {
+        Expr::builtin(Builtin::Atan2, &[self.clone(), x.into()])
+    }
+    pub fn min(&self, other: impl Into<Expr>) -> Expr {
+        Expr::builtin(Builtin::Min, &[self.clone(), other.into()])
+    }
+    pub fn max(&self, other: impl Into<Expr>) -> Expr {
+        Expr::builtin(Builtin::Max, &[self.clone(), other.into()])
+    }
+    pub fn clamp(&self, min: impl Into<Expr>, max: impl Into<Expr>) -> Expr {
+        Expr::builtin(Builtin::Clamp, &[self.clone(), min.into(), max.into()])
+    }
+    /// The remainder with the sign of `y`.
+    pub fn modulo(&self, y: impl Into<Expr>) -> Expr {
+        Expr::builtin(Builtin::Mod, &[self.clone(), y.into()])
+    }
+    pub fn length(&self) -> Expr {
+        Expr::builtin(Builtin::Length, std::slice::from_ref(self))
+    }
+    pub fn dot(&self, other: impl Into<Expr>) -> Expr {
+        Expr::builtin(Builtin::Dot, &[self.clone(), other.into()])
+    }
+    pub fn lt(&self, other: impl Into<Expr>) -> Expr {
+        Expr::binary(BinaryOp::Less, self.clone(), other.into())
+    }
+    pub fn gt(&self, other: impl Into<Expr>) -> Expr {
+        Expr::binary(BinaryOp::Greater, self.clone(), other.into())
+    }
+}
+
+pub fn vec2(x: impl Into<Expr>, y: impl Into<Expr>) -> Expr {
+    Expr::builtin(Builtin::Vec2, &[x.into(), y.into()])
+}
+
+pub fn vec3(x: impl Into<Expr>, y: impl Into<Expr>, z: impl Into<Expr>) -> Expr {
+    Expr::builtin(Builtin::Vec3, &[x.into(), y.into(), z.into()])
+}
+
+/// A 2x2 matrix from its columns.
+pub fn mat2(
+    c0x: impl Into<Expr>,
+    c0y: impl Into<Expr>,
+    c1x: impl Into<Expr>,
+    c1y: impl Into<Expr>,
+) -> Expr {
+    Expr::builtin(
+        Builtin::Mat2,
+        &[c0x.into(), c0y.into(), c1x.into(), c1y.into()],
+    )
+}
+
+/// Evaluates to `a` if `condition` holds and to `b` otherwise.
+pub fn select(condition: Expr, a: impl Into<Expr>, b: impl Into<Expr>) -> Expr {
+    let (a, b) = (a.into(), b.into());
+    assert_eq!(condition.ty, Type::Bool, "condition must be a bool");
+    assert_eq!(a.ty, b.ty, "branches must have the same type");
+    match condition.constant() {
+        Some(Value::Bool(true)) => a,
+        Some(_) => b,
+        None => {
+            let ty = a.ty;
+            Expr::new(Node::Select(condition, a, b), ty)
+        }
+    }
+}
+
+/// Evaluates to `value`, which must equal `shortcut` wherever `condition` does not hold.
+/// Shaders select between them to skip computing `value` there, while the CPU computes `value`
+/// right away, as checking the condition rarely saves it more than it costs.
+pub fn shortcut(condition: Expr, value: impl Into<Expr>, shortcut: impl Into<Expr>) -> Expr {
+    let (value, shortcut) = (value.into(), shortcut.into());
+    assert_eq!(condition.ty, Type::Bool, "condition must be a bool");
+    assert_eq!(value.ty, shortcut.ty, "branches must have the same type");
+    if condition.constant().is_some() {
+        return value;
+    }
+    let ty = value.ty;
+    Expr::new(Node::Shortcut(condition, value, shortcut), ty)
+}
+
+/// Folds `body` over arrays of constants of the same length, starting with `initial`.
+/// Backends emit a loop, so the code stays small however long the arrays are.
+///
+/// The body gets the accumulated value and the elements with the same index in each array.
+pub fn fold<const N: usize>(
+    initial: impl Into<Expr>,
+    arrays: [Vec<Value>; N],
+    body: impl Fn(&Expr, [Expr; N]) -> Expr,
+) -> Expr {
+    let initial = initial.into();
+    let length = arrays[0].len();
+    for array in &arrays {
+        assert_eq!(array.len(), length, "arrays must have the same length");
+        assert!(
+            array.iter().all(|v| v.ty() == array[0].ty()),
+            "array elements must have the same type"
+        );
+    }
+    // Constants get folded right away, as long as the body computes constants.
+    let mut accumulator = initial.clone();
+    let mut i = 0;
+    while i < length && accumulator.constant().is_some() {
+        accumulator = body(&accumulator, std::array::from_fn(|j| arrays[j][i].into()));
+        i += 1;
+    }
+    if accumulator.constant().is_some() || length == 0 {
+        return accumulator;
+    }
+    let var = Var::new("accumulator", initial.ty);
+    let elements = arrays.map(|array| (Var::new("element", array[0].ty()), array));
+    let body = body(&var.expr(), std::array::from_fn(|j| elements[j].0.expr()));
+    assert_eq!(body.ty, initial.ty, "the body must keep the type");
+    let ty = initial.ty;
+    Expr::new(Node::Fold(var, initial, elements.to_vec(), body), ty)
+}
+
+impl From<Value> for Expr {
+    fn from(value: Value) -> Expr {
+        Expr {
+            kind: Kind::Constant(value),
+            ty: value.ty(),
+            has_let: false,
+        }
+    }
+}
+
+impl From<f32> for Expr {
+    fn from(f: f32) -> Expr {
+        Value::Float(f).into()
+    }
+}
+
+impl From<glm::Vec2> for Expr {
+    fn from(v: glm::Vec2) -> Expr {
+        Value::Vec2(v).into()
+    }
+}
+
+impl From<glm::Vec3> for Expr {
+    fn from(v: glm::Vec3) -> Expr {
+        Value::Vec3(v).into()
+    }
+}
+
+impl From<glm::Mat3> for Expr {
+    fn from(m: glm::Mat3) -> Expr {
+        Value::Mat3(m).into()
+    }
+}
+
+impl From<&Expr> for Expr {
+    fn from(e: &Expr) -> Expr {
+        e.clone()
+    }
+}
+
+macro_rules! binary_operator {
+    ($trait:ident, $method:ident, $op:expr) => {
+        impl<T: Into<Expr>> $trait<T> for Expr {
+            type Output = Expr;
+            fn $method(self, other: T) -> Expr {
+                Expr::binary($op, self, other.into())
+            }
+        }
+        impl<T: Into<Expr>> $trait<T> for &Expr {
+            type Output = Expr;
+            fn $method(self, other: T) -> Expr {
+                Expr::binary($op, self.clone(), other.into())
+            }
+        }
+        impl $trait<Expr> for f32 {
+            type Output = Expr;
+            fn $method(self, other: Expr) -> Expr {
+                Expr::binary($op, self.into(), other)
+            }
+        }
+        impl $trait<&Expr> for f32 {
+            type Output = Expr;
+            fn $method(self, other: &Expr) -> Expr {
+                Expr::binary($op, self.into(), other.clone())
+            }
+        }
+    };
+}
+
+binary_operator!(Add, add, BinaryOp::Add);
+binary_operator!(Sub, sub, BinaryOp::Sub);
+binary_operator!(Mul, mul, BinaryOp::Mul);
+binary_operator!(Div, div, BinaryOp::Div);
+
+impl Neg for Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        let ty = self.ty;
+        assert!(ty.is_numeric(), "cannot negate {:?}", ty);
+        match self.constant() {
+            Some(value) => cpu::negate(value).into(),
+            None => Expr::new(Node::Negate(self), ty),
+        }
+    }
+}
+
+impl Neg for &Expr {
+    type Output = Expr;
+    fn neg(self) -> Expr {
+        -self.clone()
+    }
+}
@@ -0,0 +1,325 @@
+//! Emits expressions as shader code, leaving the syntax to a [`Dialect`].
+
+use super::{BinaryOp, Builtin, Expr, Function, Kind, Node, Type, Value, Var};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// The syntax of a shading language.
+pub(super) trait Dialect {
+    /// Whether the conditional expression evaluates only the chosen branch. Otherwise selects
+    /// become if statements, unless both branches are free to compute.
+    const LAZY_SELECT: bool;
+    fn type_name(ty: Type) -> &'static str;
+    fn constant(value: &Value) -> String;
+    /// Returns code applying `f` to the emitted arguments. Helper functions get added to
+    /// `shared_code`.
+    fn builtin(f: Builtin, args: &[(String, Type)], shared_code: &mut Vec<String>) -> String;
+    /// Returns code evaluating to `a` if `condition` holds and to `b` otherwise.
+    fn select(condition: &str, a: &str, b: &str) -> String;
+    /// Returns the statement declaring a local variable, initialized to `value` if given.
+    fn declare(name: &str, ty: Type, value: Option<&str>) -> String;
+    /// Returns the signature of a function, without the opening brace.
+    fn signature(name: &str, parameters: &[(&str, Type)], result: Type) -> String;
+    /// Returns the statement declaring a local array holding the given elements.
+    fn declare_array(name: &str, ty: Type, elements: &[String]) -> String;
+    /// Returns the head of a loop counting `index` from zero to below `count`, including the
+    /// opening brace.
+    fn for_loop(index: &str, count: usize) -> String;
+}
+
+/// Constants that shader code reads from a uniform array of 4x4 matrices instead of having them
+/// written out, so that the code only depends on the structure of an expression.
+pub struct Uniforms {
+    name: &'static str,
+    capacity: usize,
+    values: Vec<f32>,
+}
+
+impl Uniforms {
+    /// Holds up to `capacity` floats. Constants that do not fit get written out.
+    pub fn new(name: &'static str, capacity: usize) -> Uniforms {
+        Uniforms {
+            name,
+            capacity,
+            values: Vec::new(),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    /// Number of matrices the uniform array needs, which is at least one.
+    pub fn matrices(&self) -> usize {
+        self.values.len().div_ceil(16).max(1)
+    }
+    /// The values, padded to whole matrices and given column by column.
+    pub fn values(&self) -> Vec<f32> {
+        let mut values = self.values.clone();
+        values.resize(self.matrices() * 16, 0.);
+        values
+    }
+    /// Stores `f` and returns the code reading it.
+    fn push(&mut self, f: f32) -> String {
+        let i = self.values.len();
+        self.values.push(f);
+        format!("{}[{}][{}][{}]", self.name, i / 16, i % 16 / 4, i % 4)
+    }
+}
+
+/// Formats a float such that every backend reads it as a float.
+pub(super) fn float(f: f32) -> String {
+    format!("{:.8}", f)
+}
+
+/// Formats the floats as arguments of a constructor.
+pub(super) fn floats(f: &[f32]) -> String {
+    f.iter().map(|f| float(*f)).collect::<Vec<_>>().join(", ")
+}
+
+pub(super) fn expression<D: Dialect>(
+    expr: &Expr,
+    inputs: &[(Var, &str)],
+    shared_code: &mut Vec<String>,
+    uniforms: Option<&mut Uniforms>,
+) -> String {
+    if !expr.has_let && D::LAZY_SELECT {
+        let mut emitter = Emitter::<D>::new(shared_code, uniforms);
+        for (var, code) in inputs {
+            let name = if is_identifier(code) {
+                code.to_string()
+            } else {
+                format!("({})", code)
+            };
+            emitter.names.insert(var.id, name);
+        }
+        return emitter.emit(expr);
+    }
+    // Let expressions and if statements need a function body, so wrap them into a function of
+    // the inputs.
+    let parameters = inputs.iter().map(|(var, _)| *var).collect::<Vec<_>>();
+    let name = format!("Sdf{}", shared_code.len());
+    let code = function::<D>(&name, &parameters, expr, shared_code, uniforms);
+    shared_code.push(code);
+    let arguments = inputs
+        .iter()
+        .map(|(_, code)| *code)
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{}({})", name, arguments)
+}
+
+fn is_identifier(code: &str) -> bool {
+    code.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Whether computing `expr` costs nothing, as it is a constant or a variable.
+fn is_trivial(expr: &Expr) -> bool {
+    match &expr.kind {
+        Kind::Constant(_) => true,
+        Kind::Node(node) => matches!(**node, Node::Variable(_)),
+    }
+}
+
+/// Returns the definition of a function computing `body` from `parameters`.
+fn function<D: Dialect>(
+    name: &str,
+    parameters: &[Var],
+    body: &Expr,
+    shared_code: &mut Vec<String>,
+    uniforms: Option<&mut Uniforms>,
+) -> String {
+    let mut emitter = Emitter::<D>::new(shared_code, uniforms);
+    emitter.depth = 1;
+    for p in parameters {
+        emitter.names.insert(p.id, p.name.to_string());
+    }
+    let result = emitter.emit(body);
+    emitter.line(format!("return {};", result));
+    let parameters = parameters
+        .iter()
+        .map(|p| (p.name, p.ty))
+        .collect::<Vec<_>>();
+    format!(
+        "
+{} {{
+{}
+}}
+",
+        D::signature(name, &parameters, body.ty),
+        emitter.lines.join("\n")
+    )
+}
+
+struct Emitter<'a, D> {
+    shared_code: &'a mut Vec<String>,
+    uniforms: Option<&'a mut Uniforms>,
+    names: HashMap<u32, String>,
+    lines: Vec<String>,
+    depth: usize,
+    dialect: PhantomData<D>,
+}
+
+impl<D: Dialect> Emitter<'_, D> {
+    fn new<'a>(
+        shared_code: &'a mut Vec<String>,
+        uniforms: Option<&'a mut Uniforms>,
+    ) -> Emitter<'a, D> {
+        Emitter {
+            shared_code,
+            uniforms,
+            names: HashMap::new(),
+            lines: Vec::new(),
+            depth: 0,
+            dialect: PhantomData,
+        }
+    }
+
+    fn line(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{}", "    ".repeat(self.depth), line));
+    }
+
+    /// Declares a local variable with a name unique within the function.
+    fn declare(&mut self, var: &Var) -> String {
+        let name = format!("{}_{}", var.name, self.names.len());
+        self.names.insert(var.id, name.clone());
+        name
+    }
+
+    /// Emits the statements `expr` needs and returns the expression computing it.
+    fn emit(&mut self, expr: &Expr) -> String {
+        let node = match &expr.kind {
+            Kind::Constant(value) => return self.constant(value),
+            Kind::Node(node) => node,
+        };
+        match &**node {
+            Node::Variable(var) => self.names[&var.id].clone(),
+            Node::Negate(a) => format!("-({})", self.emit(a)),
+            Node::Binary(op, a, b) => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    BinaryOp::Div => "/",
+                    BinaryOp::Less => "<",
+                    BinaryOp::Greater => ">",
+                };
+                format!("({} {} {})", self.emit(a), op, self.emit(b))
+            }
+            Node::Builtin(f, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| (self.emit(a), a.ty))
+                    .collect::<Vec<_>>();
+                D::builtin(*f, &args, self.shared_code)
+            }
+            Node::Swizzle(a, components) => {
+                let a = self.emit(a);
+                let components = components
+                    .iter()
+                    .map(|i| ['x', 'y', 'z'][*i])
+                    .collect::<String>();
+                if is_identifier(&a) {
+                    format!("{}.{}", a, components)
+                } else {
+                    format!("({}).{}", a, components)
+                }
+            }
+            Node::Select(condition, a, b) | Node::Shortcut(condition, a, b) => {
+                let condition = self.emit(condition);
+                let inline = if D::LAZY_SELECT {
+                    !a.has_let && !b.has_let
+                } else {
+                    is_trivial(a) && is_trivial(b)
+                };
+                if inline {
+                    return D::select(&condition, &self.emit(a), &self.emit(b));
+                }
+                let result = self.declare(&Var::new("select", expr.ty));
+                self.line(D::declare(&result, expr.ty, None));
+                self.line(format!("if ({}) {{", condition));
+                self.branch(&result, a);
+                self.line("} else {".to_string());
+                self.branch(&result, b);
+                self.line("}".to_string());
+                result
+            }
+            Node::Let(var, value, body) => {
+                let value = self.emit(value);
+                let name = self.declare(var);
+                self.line(D::declare(&name, var.ty, Some(&value)));
+                self.emit(body)
+            }
+            Node::Fold(accumulator, initial, elements, body) => {
+                let initial = self.emit(initial);
+                let result = self.declare(accumulator);
+                self.line(D::declare(&result, expr.ty, None));
+                self.line(format!("{} = {};", result, initial));
+                let arrays = elements
+                    .iter()
+                    .map(|(var, values)| {
+                        let values = values.iter().map(|v| self.constant(v)).collect::<Vec<_>>();
+                        let array = self.declare(&Var::new("array", var.ty));
+                        self.line(D::declare_array(&array, var.ty, &values));
+                        array
+                    })
+                    .collect::<Vec<_>>();
+                // The index is no variable of the tree, its declaration only reserves the name.
+                let index = self.declare(&Var::new("i", Type::Float));
+                self.line(D::for_loop(&index, elements[0].1.len()));
+                self.depth += 1;
+                for ((var, _), array) in elements.iter().zip(arrays) {
+                    let name = self.declare(var);
+                    let element = format!("{}[{}]", array, index);
+                    self.line(D::declare(&name, var.ty, Some(&element)));
+                }
+                let value = self.emit(body);
+                self.line(format!("{} = {};", result, value));
+                self.depth -= 1;
+                self.line("}".to_string());
+                result
+            }
+            Node::Call(f, args) => {
+                self.define(f);
+                let args = args.iter().map(|a| self.emit(a)).collect::<Vec<_>>();
+                format!("{}({})", f.name, args.join(", "))
+            }
+        }
+    }
+
+    /// Reads the value from the uniforms, if there are any and it fits.
+    fn constant(&mut self, value: &Value) -> String {
+        let floats = match value {
+            Value::Bool(_) => return D::constant(value),
+            Value::Float(f) => std::slice::from_ref(f),
+            Value::Vec2(v) => v.as_slice(),
+            Value::Vec3(v) => v.as_slice(),
+            Value::Mat2(m) => m.as_slice(),
+            Value::Mat3(m) => m.as_slice(),
+        };
+        let uniforms = match self.uniforms.as_deref_mut() {
+            Some(u) if u.values.len() + floats.len() <= u.capacity => u,
+            _ => return D::constant(value),
+        };
+        let reads = floats.iter().map(|f| uniforms.push(*f)).collect::<Vec<_>>();
+        match value {
+            Value::Float(_) => reads[0].clone(),
+            _ => format!("{}({})", D::type_name(value.ty()), reads.join(", ")),
+        }
+    }
+
+    /// Emits the statements assigning `expr` to `result` in a block.
+    fn branch(&mut self, result: &str, expr: &Expr) {
+        self.depth += 1;
+        let value = self.emit(expr);
+        self.line(format!("{} = {};", result, value));
+        self.depth -= 1;
+    }
+
+    fn define(&mut self, f: &Function) {
+        // Function bodies keep their constants, as every caller shares them.
+        let code = function::<D>(f.name, &f.parameters, &f.body, self.shared_code, None);
+        if !self.shared_code.contains(&code) {
+            self.shared_code.push(code);
+        }
+    }
+}
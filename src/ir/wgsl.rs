@@ -0,0 +1,143 @@
+//! Emits expressions as WGSL.
+
+use super::shader::{self, float, floats, Dialect};
+use super::{Builtin, Expr, Type, Value, Var};
+
+/// Returns WGSL code computing `expr`, where the `inputs` are given as WGSL expressions.
+/// Helper functions get added to `shared_code`.
+pub fn expression(expr: &Expr, inputs: &[(Var, &str)], shared_code: &mut Vec<String>) -> String {
+    shader::expression::<Wgsl>(expr, inputs, shared_code, None)
+}
+
+pub fn type_name(ty: Type) -> &'static str {
+    Wgsl::type_name(ty)
+}
+
+struct Wgsl;
+
+impl Dialect for Wgsl {
+    const LAZY_SELECT: bool = false;
+
+    fn type_name(ty: Type) -> &'static str {
+        match ty {
+            Type::Bool => "bool",
+            Type::Float => "f32",
+            Type::Vec2 => "vec2f",
+            Type::Vec3 => "vec3f",
+            Type::Mat2 => "mat2x2f",
+            Type::Mat3 => "mat3x3f",
+        }
+    }
+
+    fn constant(value: &Value) -> String {
+        match value {
+            Value::Bool(b) => b.to_string(),
+            Value::Float(f) => float(*f),
+            // Matrices are given column by column in both glm and WGSL.
+            Value::Vec2(v) => format!("vec2f({})", floats(v.as_slice())),
+            Value::Vec3(v) => format!("vec3f({})", floats(v.as_slice())),
+            Value::Mat2(m) => format!("mat2x2f({})", floats(m.as_slice())),
+            Value::Mat3(m) => format!("mat3x3f({})", floats(m.as_slice())),
+        }
+    }
+
+    fn builtin(f: Builtin, args: &[(String, Type)], shared_code: &mut Vec<String>) -> String {
+        let name = match f {
+            Builtin::Abs => "abs",
+            Builtin::Sign => "sign",
+            Builtin::Sqrt => "sqrt",
+            Builtin::Round => "round",
+            Builtin::Sin => "sin",
+            Builtin::Cos => "cos",
+            Builtin::Exp2 => "exp2",
+            Builtin::Log2 => "log2",
+            Builtin::Atan2 => "atan2",
+            Builtin::Min => "min",
+            Builtin::Max => "max",
+            Builtin::Clamp => "clamp",
+            Builtin::Mod => return modulo(args, shared_code),
+            Builtin::Length => "length",
+            Builtin::Dot => "dot",
+            Builtin::Vec2 => "vec2f",
+            Builtin::Vec3 => "vec3f",
+            Builtin::Mat2 => "mat2x2f",
+        };
+        let args = match f {
+            Builtin::Min | Builtin::Max | Builtin::Clamp => splat(args),
+            _ => args.iter().map(|(a, _)| a.clone()).collect(),
+        };
+        format!("{}({})", name, args.join(", "))
+    }
+
+    fn select(condition: &str, a: &str, b: &str) -> String {
+        format!("select({}, {}, {})", b, a, condition)
+    }
+
+    fn declare(name: &str, ty: Type, value: Option<&str>) -> String {
+        match value {
+            Some(value) => format!("let {}: {} = {};", name, Wgsl::type_name(ty), value),
+            None => format!("var {}: {};", name, Wgsl::type_name(ty)),
+        }
+    }
+
+    fn signature(name: &str, parameters: &[(&str, Type)], result: Type) -> String {
+        let parameters = parameters
+            .iter()
+            .map(|(p, ty)| format!("{}: {}", p, Wgsl::type_name(*ty)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("fn {}({}) -> {}", name, parameters, Wgsl::type_name(result))
+    }
+
+    fn declare_array(name: &str, ty: Type, elements: &[String]) -> String {
+        // Only arrays in variables can be indexed by the loop counter.
+        let ty = format!("array<{}, {}>", Wgsl::type_name(ty), elements.len());
+        format!("var {}: {} = {}({});", name, ty, ty, elements.join(", "))
+    }
+
+    fn for_loop(index: &str, count: usize) -> String {
+        format!(
+            "for (var {i}: i32 = 0; {i} < {}; {i}++) {{",
+            count,
+            i = index
+        )
+    }
+}
+
+/// Returns the arguments with floats converted to the type of the first one, as WGSL does not
+/// mix vectors and scalars in builtin functions.
+fn splat(args: &[(String, Type)]) -> Vec<String> {
+    let ty = args[0].1;
+    args.iter()
+        .map(|(a, a_ty)| {
+            if *a_ty == Type::Float && ty != Type::Float {
+                format!("{}({})", Wgsl::type_name(ty), a)
+            } else {
+                a.clone()
+            }
+        })
+        .collect()
+}
+
+/// WGSL's `%` truncates, so the floored remainder gets a helper function for each type.
+fn modulo(args: &[(String, Type)], shared_code: &mut Vec<String>) -> String {
+    let ty = Wgsl::type_name(args[0].1);
+    let name = match args[0].1 {
+        Type::Vec2 => "ModVec2",
+        Type::Vec3 => "ModVec3",
+        _ => "ModFloat",
+    };
+    let code = format!(
+        "
+fn {name}(x: {ty}, y: {ty}) -> {ty} {{
+    return x - y * floor(x / y);
+}}
+",
+        name = name,
+        ty = ty
+    );
+    if !shared_code.contains(&code) {
+        shared_code.push(code);
+    }
+    format!("{}({})", name, splat(args).join(", "))
+}
@@ -0,0 +1,456 @@
+use super::{Grid, TriangleMesh};
+use crate::primitive::{BoundingBox, Primitive};
+use std::collections::{HashMap, HashSet};
+
+/// Singular values of the QEF, averaged over its crossings, below this are dropped when solving
+/// it, which keeps vertices of flat or curved patches near the mass point of the crossings.
+const SINGULAR_VALUE_THRESHOLD: f64 = 0.1;
+
+/// Octree nodes get merged into one vertex while the root mean square distance of their vertex
+/// to the tangent planes at the crossings stays below this many cells of the finest level.
+const MAX_ERROR: f32 = 0.03;
+
+/// Steps of the Illinois method refining where the surface crosses an edge. Linear
+/// interpolation alone misses sharp features, as the distance is not linear along edges
+/// passing near them.
+const CROSSING_STEPS: usize = 6;
+
+/// Corner offsets of a cell.
+const CORNERS: [[u32; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+/// The edges of a cell as pairs of corner offsets and the axis they run along.
+const EDGES: [([u32; 3], usize); 12] = [
+    ([0, 0, 0], 0),
+    ([0, 1, 0], 0),
+    ([0, 0, 1], 0),
+    ([0, 1, 1], 0),
+    ([0, 0, 0], 1),
+    ([1, 0, 0], 1),
+    ([0, 0, 1], 1),
+    ([1, 0, 1], 1),
+    ([0, 0, 0], 2),
+    ([1, 0, 0], 2),
+    ([0, 1, 0], 2),
+    ([1, 1, 0], 2),
+];
+
+/// Where the surface crosses an edge, and the normal there.
+type Crossing = (glm::Vec3, glm::Vec3);
+
+/// The quadratic error function of a node, i.e. the sum of squared distances to the tangent
+/// planes at the surface crossings within it. Kept in double precision, as merged nodes sum up
+/// many crossings.
+#[derive(Clone, Copy)]
+struct Qef {
+    ata: glm::DMat3,
+    atb: glm::DVec3,
+    btb: f64,
+    points: glm::DVec3,
+    count: u32,
+}
+
+impl Qef {
+    fn new() -> Qef {
+        Qef {
+            ata: glm::DMat3::zeros(),
+            atb: glm::DVec3::zeros(),
+            btb: 0.,
+            points: glm::DVec3::zeros(),
+            count: 0,
+        }
+    }
+
+    /// Adds the tangent plane through `x` with the normal `n`.
+    fn add(&mut self, x: glm::Vec3, n: glm::Vec3) {
+        let (x, n) = (x.cast::<f64>(), n.cast::<f64>());
+        let b = n.dot(&x);
+        self.ata += n * n.transpose();
+        self.atb += n * b;
+        self.btb += b * b;
+        self.points += x;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Qef) {
+        self.ata += other.ata;
+        self.atb += other.atb;
+        self.btb += other.btb;
+        self.points += other.points;
+        self.count += other.count;
+    }
+
+    /// Returns the point within `min` and `max` with the least error, and that error.
+    fn solve(&self, min: glm::Vec3, max: glm::Vec3) -> (glm::Vec3, f64) {
+        let count = self.count as f64;
+        let mass_point = self.points / count;
+        // Minimize relative to the mass point, which is kept along dropped singular vectors.
+        let offset = (self.ata / count)
+            .svd(true, true)
+            .solve(
+                &((self.atb - self.ata * mass_point) / count),
+                SINGULAR_VALUE_THRESHOLD,
+            )
+            .unwrap_or_default();
+        let vertex = glm::clamp_vec(&(mass_point + offset).cast::<f32>(), &min, &max);
+        let v = vertex.cast::<f64>();
+        let error = v.dot(&(self.ata * v)) - 2. * v.dot(&self.atb) + self.btb;
+        (vertex, error.max(0.))
+    }
+}
+
+struct DualContouring<'a> {
+    primitive: &'a dyn Primitive,
+    grid: &'a Grid,
+    cell_size: f32,
+    cells: u32,
+    corners: HashMap<[u32; 3], f32>,
+    crossings: HashMap<([u32; 3], usize), Option<Crossing>>,
+    /// The octree leaves crossed by the surface, by their minimum corner and size.
+    leaves: HashMap<([u32; 3], u32), Qef>,
+    leaf_vertices: HashMap<([u32; 3], u32), u32>,
+    vertices: Vec<glm::Vec3>,
+    normals: Vec<glm::Vec3>,
+}
+
+/// Extracts the surface of `p` within `grid` by adaptive dual contouring.
+///
+/// An octree is refined only where the surface may pass, i.e. where the interval evaluation
+/// contains zero. Every cell of the finest level crossed by the surface gets the quadratic
+/// error to the tangent planes at the crossings of its edges, which keeps sharp edges and
+/// corners. Going up, the children of a node are merged into one vertex where the error of the
+/// merged vertex stays small and the signs at the corners of the children show a single patch
+/// of surface, so flat regions get larger triangles without changing the topology. The leaves
+/// around each crossed edge of the finest level are connected by a quad, or a triangle where
+/// two of them are the same, so the mesh is closed.
+pub fn mesh(p: &dyn Primitive, grid: &Grid) -> TriangleMesh {
+    let cells = 1 << grid.depth;
+    let mut dc = DualContouring {
+        primitive: p,
+        grid,
+        cell_size: grid.extent / cells as f32,
+        cells,
+        corners: HashMap::new(),
+        crossings: HashMap::new(),
+        leaves: HashMap::new(),
+        leaf_vertices: HashMap::new(),
+        vertices: Vec::new(),
+        normals: Vec::new(),
+    };
+    let mut fine_cells = Vec::new();
+    if let Some(qef) = dc.refine([0, 0, 0], cells, &mut fine_cells) {
+        dc.insert_leaf([0, 0, 0], cells, qef);
+    }
+
+    let mut visited_edges = HashSet::new();
+    let mut triangles = Vec::new();
+    for cell in fine_cells {
+        for (offset, axis) in EDGES {
+            let start = add(cell, offset);
+            if visited_edges.insert((start, axis)) {
+                dc.contour_edge(start, axis, &mut triangles);
+            }
+        }
+    }
+    TriangleMesh {
+        vertices: dc.vertices,
+        normals: dc.normals,
+        triangles,
+    }
+}
+
+fn add(a: [u32; 3], b: [u32; 3]) -> [u32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(a: [u32; 3], size: u32) -> [u32; 3] {
+    a.map(|a| a * size)
+}
+
+impl DualContouring<'_> {
+    fn position(&self, corner: [u32; 3]) -> glm::Vec3 {
+        self.grid.origin
+            + glm::vec3(corner[0] as f32, corner[1] as f32, corner[2] as f32) * self.cell_size
+    }
+
+    fn sample(&self, x: glm::Vec3) -> f32 {
+        self.grid.sample(x)
+    }
+
+    fn corner(&mut self, corner: [u32; 3]) -> f32 {
+        if let Some(d) = self.corners.get(&corner) {
+            return *d;
+        }
+        let d = self.sample(self.position(corner));
+        self.corners.insert(corner, d);
+        d
+    }
+
+    fn normal(&self, x: glm::Vec3) -> glm::Vec3 {
+        self.grid
+            .gradient(self.primitive, x)
+            .try_normalize(0.0)
+            .unwrap_or_default()
+    }
+
+    /// Builds the octree below a node, collecting the cells of the finest level the surface
+    /// may pass through. Returns the error function of the node if it is a leaf, otherwise its
+    /// children that are leaves get stored.
+    fn refine(&mut self, min: [u32; 3], size: u32, fine_cells: &mut Vec<[u32; 3]>) -> Option<Qef> {
+        let region = BoundingBox::new(
+            self.position(min),
+            self.position(add(min, [size, size, size])),
+        );
+        if !self
+            .grid
+            .sample_interval(self.primitive, &region)
+            .contains(0.)
+        {
+            return Some(Qef::new());
+        }
+        if size == 1 {
+            fine_cells.push(min);
+            return Some(self.cell_qef(min));
+        }
+        let half = size / 2;
+        let children = CORNERS.map(|offset| {
+            let child = add(min, scale(offset, half));
+            (child, self.refine(child, half, fine_cells))
+        });
+        if let Some(qef) = self.merge(min, size, &children) {
+            return Some(qef);
+        }
+        for (child, qef) in children {
+            if let Some(qef) = qef {
+                self.insert_leaf(child, half, qef);
+            }
+        }
+        None
+    }
+
+    /// Returns the error function of the children merged into one leaf, unless that loses
+    /// detail or changes the topology of the surface.
+    fn merge(
+        &mut self,
+        min: [u32; 3],
+        size: u32,
+        children: &[([u32; 3], Option<Qef>); 8],
+    ) -> Option<Qef> {
+        let mut qef = Qef::new();
+        for (_, child) in children {
+            qef.merge(&(*child)?);
+        }
+        if qef.count == 0 {
+            return Some(qef);
+        }
+        let half = size / 2;
+        // Larger children were checked when they were merged themselves.
+        if half == 1
+            && !children
+                .iter()
+                .all(|(child, _)| self.is_manifold(*child, 1))
+        {
+            return None;
+        }
+        if !self.is_manifold(min, size) || !self.keeps_topology(min, half) {
+            return None;
+        }
+        let max = self.position(add(min, [size, size, size]));
+        let (_, error) = qef.solve(self.position(min), max);
+        let max_error = (MAX_ERROR * self.cell_size) as f64;
+        (error <= max_error * max_error * qef.count as f64).then_some(qef)
+    }
+
+    /// Checks that the surface passes a node as a single patch, i.e. that the corners inside
+    /// and the corners outside are each connected along the edges of the node.
+    fn is_manifold(&mut self, min: [u32; 3], size: u32) -> bool {
+        let inside = CORNERS.map(|offset| self.corner(add(min, scale(offset, size))) < 0.);
+        // Corners share an edge where their indices differ in a single bit.
+        [false, true].into_iter().all(|sign| {
+            let Some(first) = (0..8).find(|&i| inside[i] == sign) else {
+                return true;
+            };
+            let mut reached = 1 << first;
+            let mut stack = vec![first];
+            while let Some(i) = stack.pop() {
+                for bit in [1, 2, 4] {
+                    let j = i ^ bit;
+                    if inside[j] == sign && reached & (1 << j) == 0 {
+                        reached |= 1 << j;
+                        stack.push(j);
+                    }
+                }
+            }
+            (0..8).all(|i| inside[i] != sign || reached & (1 << i) != 0)
+        })
+    }
+
+    /// Checks that the sign at the middle of every edge and face of a node, and at its center,
+    /// matches one of the corners of that edge, face or node. Otherwise the surface passes
+    /// between the corners of the children in a way the node alone cannot show.
+    fn keeps_topology(&mut self, min: [u32; 3], half: u32) -> bool {
+        (0..27).all(|i| {
+            let lattice = [i % 3, i / 3 % 3, i / 9];
+            if !lattice.contains(&1) {
+                return true;
+            }
+            let inside = self.corner(add(min, scale(lattice, half))) < 0.;
+            // The corners of the edge, face or node, some of them repeatedly.
+            CORNERS.iter().any(|offset| {
+                let corner = [0, 1, 2].map(|j| {
+                    if lattice[j] == 1 {
+                        offset[j] * 2
+                    } else {
+                        lattice[j]
+                    }
+                });
+                (self.corner(add(min, scale(corner, half))) < 0.) == inside
+            })
+        })
+    }
+
+    fn insert_leaf(&mut self, min: [u32; 3], size: u32, qef: Qef) {
+        if qef.count > 0 {
+            self.leaves.insert((min, size), qef);
+        }
+    }
+
+    /// Returns where the surface crosses the edge and the normal there, if it does.
+    fn crossing(&mut self, start: [u32; 3], axis: usize) -> Option<Crossing> {
+        if let Some(x) = self.crossings.get(&(start, axis)) {
+            return *x;
+        }
+        let mut end = start;
+        end[axis] += 1;
+        let (mut d0, mut d1) = (self.corner(start), self.corner(end));
+        let crossing = ((d0 < 0.) != (d1 < 0.)).then(|| {
+            let (mut x0, mut x1) = (self.position(start), self.position(end));
+            let mut x = x0.lerp(&x1, d0 / (d0 - d1));
+            // An end kept twice in a row gets its distance halved, so it cannot stall the method.
+            let mut replaced_start = None;
+            for _ in 0..CROSSING_STEPS {
+                let d = self.sample(x);
+                if d == 0. {
+                    break;
+                }
+                if (d < 0.) == (d0 < 0.) {
+                    (x0, d0) = (x, d);
+                    if replaced_start == Some(true) {
+                        d1 *= 0.5;
+                    }
+                    replaced_start = Some(true);
+                } else {
+                    (x1, d1) = (x, d);
+                    if replaced_start == Some(false) {
+                        d0 *= 0.5;
+                    }
+                    replaced_start = Some(false);
+                }
+                x = x0.lerp(&x1, d0 / (d0 - d1));
+            }
+            (x, self.normal(x))
+        });
+        self.crossings.insert((start, axis), crossing);
+        crossing
+    }
+
+    /// Returns the error function of a cell of the finest level.
+    fn cell_qef(&mut self, cell: [u32; 3]) -> Qef {
+        let mut qef = Qef::new();
+        for (offset, axis) in EDGES {
+            if let Some((x, n)) = self.crossing(add(cell, offset), axis) {
+                qef.add(x, n);
+            }
+        }
+        qef
+    }
+
+    /// Returns the index of the vertex of the leaf containing a cell of the finest level,
+    /// placing it first if needed.
+    fn leaf_vertex(&mut self, cell: [u32; 3]) -> u32 {
+        let leaf = (0..=self.grid.depth)
+            .map(|level| {
+                let size = 1 << level;
+                (cell.map(|c| c & !(size - 1)), size)
+            })
+            .find(|leaf| self.leaves.contains_key(leaf))
+            .unwrap_or((cell, 1));
+        if let Some(i) = self.leaf_vertices.get(&leaf) {
+            return *i;
+        }
+        let qef = match self.leaves.get(&leaf) {
+            Some(qef) => *qef,
+            None => self.cell_qef(cell),
+        };
+        let (min, size) = leaf;
+        let (vertex, _) = qef.solve(
+            self.position(min),
+            self.position(add(min, [size, size, size])),
+        );
+
+        let i = self.vertices.len() as u32;
+        self.vertices.push(vertex);
+        self.normals.push(self.normal(vertex));
+        self.leaf_vertices.insert(leaf, i);
+        i
+    }
+
+    /// Connects the vertices of the four cells around a crossed edge.
+    fn contour_edge(&mut self, start: [u32; 3], axis: usize, triangles: &mut Vec<[u32; 3]>) {
+        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+        // The surface never reaches the outermost cells, as the grid is padded.
+        if start[b] == 0 || start[c] == 0 || start[b] >= self.cells || start[c] >= self.cells {
+            return;
+        }
+        if self.crossing(start, axis).is_none() {
+            return;
+        }
+        let neighbor = |db: u32, dc: u32| {
+            let mut cell = start;
+            cell[b] -= db;
+            cell[c] -= dc;
+            cell
+        };
+        // Counterclockwise around the edge when looking against its direction.
+        let mut quad = [
+            neighbor(0, 0),
+            neighbor(1, 0),
+            neighbor(1, 1),
+            neighbor(0, 1),
+        ]
+        .map(|cell| self.leaf_vertex(cell));
+        if self.corner(start) >= 0. {
+            quad.reverse();
+        }
+        // Cells merged into the same leaf share their vertex, which leaves a triangle if two
+        // neighbors do, and nothing if more or opposite ones do.
+        let mut polygon = quad.to_vec();
+        polygon.dedup();
+        if polygon.len() > 1 && polygon.first() == polygon.last() {
+            polygon.pop();
+        }
+        match polygon[..] {
+            [a, b, c] => triangles.push([a, b, c]),
+            [_, _, _, _] if quad[0] != quad[2] && quad[1] != quad[3] => {
+                // Split along the shorter diagonal.
+                let v = quad.map(|i| self.vertices[i as usize]);
+                if glm::distance2(&v[0], &v[2]) <= glm::distance2(&v[1], &v[3]) {
+                    triangles.push([quad[0], quad[1], quad[2]]);
+                    triangles.push([quad[0], quad[2], quad[3]]);
+                } else {
+                    triangles.push([quad[1], quad[2], quad[3]]);
+                    triangles.push([quad[1], quad[3], quad[0]]);
+                }
+            }
+            _ => {}
+        }
+    }
+}
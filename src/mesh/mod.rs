@@ -0,0 +1,263 @@
+use crate::primitive::{split_parts, BoundingBox, Compiled, Interval, Primitive};
+use anyhow::{bail, Result};
+use isosurface::linear_hashed_marching_cubes::LinearHashedMarchingCubes;
+use isosurface::math;
+use isosurface::source::{HermiteSource, Source};
+
+mod dual_contouring;
+
+/// Along axes where the primitive is unbounded, this far from the origin gets meshed.
+/// This roughly matches the volume visible in the shader canvas.
+const DEFAULT_HALF_EXTENT: f32 = 2.0;
+
+/// Marching cubes does not get any more detailed than this octree depth.
+const MAX_DEPTH: usize = 10;
+
+/// How finely to sample the meshed region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Resolution {
+    /// Cells are about this long.
+    CellSize(f32),
+    /// The octree is subdivided this many times.
+    Depth(usize),
+}
+
+/// The algorithm used to extract the surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mesher {
+    /// Adaptive dual contouring, keeps sharp edges and corners.
+    DualContouring,
+    /// Rounds off sharp features.
+    MarchingCubes,
+}
+
+impl Mesher {
+    pub fn from_name(name: &str) -> Result<Mesher> {
+        match name {
+            "dual_contouring" => Ok(Mesher::DualContouring),
+            "marching_cubes" => Ok(Mesher::MarchingCubes),
+            _ => bail!("unknown mesher: {}", name),
+        }
+    }
+}
+
+/// Configures how primitives get turned into meshes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshSettings {
+    /// The region to mesh. If `None`, the bounds of the primitive are used.
+    /// The mesh is closed where the object gets clipped by this region.
+    pub bounds: Option<BoundingBox>,
+    pub resolution: Resolution,
+    pub mesher: Mesher,
+    /// Step used for estimating normals by central differences, where primitives do not
+    /// compute them analytically.
+    pub normal_epsilon: f32,
+}
+
+impl Default for MeshSettings {
+    fn default() -> Self {
+        MeshSettings {
+            bounds: None,
+            resolution: Resolution::CellSize(0.05),
+            mesher: Mesher::DualContouring,
+            normal_epsilon: 1e-4,
+        }
+    }
+}
+
+impl MeshSettings {
+    /// Returns the region that gets meshed for `p`.
+    fn region(&self, p: &dyn Primitive) -> Result<BoundingBox> {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => {
+                // Fall back to the default extent along unbounded axes.
+                let mut bounds = p.bounds();
+                for i in 0..3 {
+                    if !bounds.min[i].is_finite() {
+                        bounds.min[i] = -DEFAULT_HALF_EXTENT;
+                    }
+                    if !bounds.max[i].is_finite() {
+                        bounds.max[i] = DEFAULT_HALF_EXTENT;
+                    }
+                }
+                bounds
+            }
+        };
+        if !bounds.is_finite() || bounds.is_empty() || bounds.size().min() <= 0. {
+            bail!("cannot mesh within {:?}.", bounds);
+        }
+        Ok(bounds)
+    }
+    fn depth(&self, extent: f32) -> Result<usize> {
+        let depth = match self.resolution {
+            Resolution::CellSize(size) if size > 0. => {
+                (extent / size).log2().ceil().max(1.) as usize
+            }
+            Resolution::CellSize(size) => bail!("cell size must be greater zero (was {}).", size),
+            Resolution::Depth(0) => bail!("depth must be at least 1 (was 0)."),
+            Resolution::Depth(depth) => depth,
+        };
+        if depth > MAX_DEPTH {
+            bail!(
+                "resolution is too fine, cells must be at least {} long.",
+                extent / (1 << MAX_DEPTH) as f32
+            );
+        }
+        Ok(depth)
+    }
+}
+
+/// An indexed triangle mesh with one normal per vertex.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    pub vertices: Vec<glm::Vec3>,
+    pub normals: Vec<glm::Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+/// The mesh of one named part of an object.
+pub struct PartMesh {
+    pub name: String,
+    pub color: Option<glm::Vec3>,
+    pub mesh: TriangleMesh,
+}
+
+/// The cubic volume that gets sampled, slightly larger than the meshed region.
+struct Grid {
+    /// The meshed region, the object gets clipped to it.
+    bounds: BoundingBox,
+    origin: glm::Vec3,
+    extent: f32,
+    depth: usize,
+    normal_epsilon: f32,
+    distance: Compiled,
+}
+
+impl Grid {
+    fn new(p: &dyn Primitive, settings: &MeshSettings) -> Result<Grid> {
+        let bounds = settings.region(p)?;
+        let depth = settings.depth(bounds.size().max())?;
+        if settings.normal_epsilon <= 0. {
+            bail!(
+                "normal epsilon must be greater zero (was {}).",
+                settings.normal_epsilon
+            );
+        }
+        // Sample one more cell on each side, so surfaces on the border of the bounds are kept.
+        let extent = bounds.size().max() * (1. + 2. / (1 << depth) as f32);
+        let origin = bounds.center() - glm::vec3(extent, extent, extent) * 0.5;
+        Ok(Grid {
+            bounds,
+            origin,
+            extent,
+            depth,
+            normal_epsilon: settings.normal_epsilon,
+            distance: Compiled::new(p),
+        })
+    }
+    /// Evaluates the distance at `x`, intersected with the bounds to close the surface where it
+    /// gets clipped.
+    fn sample(&self, x: glm::Vec3) -> f32 {
+        self.distance.eval(x).max(self.bounds.distance(x))
+    }
+    /// Returns the range of [`Grid::sample`] within `region`.
+    fn sample_interval(&self, p: &dyn Primitive, region: &BoundingBox) -> Interval {
+        p.eval_interval(region)
+            .max(&self.bounds.distance_interval(region))
+    }
+    /// Returns the gradient of [`Grid::sample`].
+    fn gradient(&self, p: &dyn Primitive, x: glm::Vec3) -> glm::Vec3 {
+        if self.distance.eval(x) >= self.bounds.distance(x) {
+            p.gradient(x, self.normal_epsilon)
+        } else {
+            self.bounds.gradient(x)
+        }
+    }
+}
+
+/// Samples a primitive in the unit cube the marching cubes implementation expects.
+struct PrimitiveSource {
+    primitive: Box<dyn Primitive>,
+    grid: Grid,
+}
+
+impl Source for PrimitiveSource {
+    fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = self.grid.origin + glm::vec3(x, y, z) * self.grid.extent;
+        self.grid.sample(p) / self.grid.extent
+    }
+}
+
+impl HermiteSource for PrimitiveSource {
+    fn sample_normal(&self, x: f32, y: f32, z: f32) -> math::Vec3 {
+        let p = self.grid.origin + glm::vec3(x, y, z) * self.grid.extent;
+        let n = self.grid.gradient(&*self.primitive, p);
+        math::Vec3::new(n[0], n[1], n[2])
+    }
+}
+
+/// Extracts the surface of `p` as configured by `settings`.
+pub fn mesh_primitive(p: &dyn Primitive, settings: &MeshSettings) -> Result<TriangleMesh> {
+    let p = &*p.simplify();
+    let grid = Grid::new(p, settings)?;
+    Ok(match settings.mesher {
+        Mesher::DualContouring => dual_contouring::mesh(p, &grid),
+        Mesher::MarchingCubes => marching_cubes(p, grid),
+    })
+}
+
+fn marching_cubes(p: &dyn Primitive, grid: Grid) -> TriangleMesh {
+    let (origin, extent, depth) = (grid.origin, grid.extent, grid.depth);
+    let source = PrimitiveSource {
+        primitive: p.clone_box(),
+        grid,
+    };
+    let mut marching_cubes = LinearHashedMarchingCubes::new(depth);
+    let mut interleaved = Vec::new();
+    let mut indices = Vec::new();
+    marching_cubes.extract_with_normals(&source, &mut interleaved, &mut indices);
+
+    let (vertices, normals) = interleaved
+        .chunks(6)
+        .map(|v| {
+            let vertex = origin + glm::vec3(v[0], v[1], v[2]) * extent;
+            let normal = glm::vec3(v[3], v[4], v[5])
+                .try_normalize(0.0)
+                .unwrap_or_default();
+            (vertex, normal)
+        })
+        .unzip();
+    // isosurface winds its triangles clockwise, flip them to face outwards.
+    let triangles = indices.chunks(3).map(|t| [t[0], t[2], t[1]]).collect();
+    TriangleMesh {
+        vertices,
+        normals,
+        triangles,
+    }
+}
+
+/// Meshes every top-level part of `p` separately (see [`split_parts`]).
+pub fn mesh_parts(p: &dyn Primitive, settings: &MeshSettings) -> Result<Vec<PartMesh>> {
+    split_parts(p)?
+        .into_iter()
+        .map(|part| {
+            Ok(PartMesh {
+                name: part.name().to_string(),
+                color: part.color(),
+                mesh: mesh_primitive(&*part, settings)?,
+            })
+        })
+        .collect()
+}
+
+impl TriangleMesh {
+    /// Returns the unit normal of a triangle as given by its winding order.
+    pub fn face_normal(&self, triangle: &[u32; 3]) -> glm::Vec3 {
+        let [a, b, c] = triangle.map(|i| self.vertices[i as usize]);
+        (b - a)
+            .cross(&(c - a))
+            .try_normalize(0.0)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,101 @@
+use super::super::BoundingBox;
+use crate::ir::{shortcut, Expr, Function, Type};
+use std::rc::Rc;
+
+/// Unions with more bounded children than this get grouped hierarchically.
+const MAX_GROUP_SIZE: usize = 4;
+
+/// A hierarchy of bounding boxes over the children of a union. Skipping children by their boxes
+/// assumes their distances are exact, see [`guarded_min`].
+enum Node {
+    Child(usize),
+    Group(BoundingBox, Vec<Node>),
+}
+
+/// Builds the minimum of all children, given their distances and bounding boxes.
+///
+/// Children (and groups of children) are skipped when the distance to their bounding box is
+/// not smaller than the current minimum. This requires the distance of every child with finite
+/// bounds to never be smaller than the distance to its box, which holds for exact distances
+/// (see [`crate::primitive::Primitive::is_exact`]). Children only giving a bound must come with
+/// infinite bounds.
+pub(super) fn guarded_min(p: &Expr, children: &[(Expr, BoundingBox)]) -> Expr {
+    let mut m = Expr::from(1e10);
+    let mut bounded = Vec::new();
+    for (i, (d, bounds)) in children.iter().enumerate() {
+        if bounds.is_finite() {
+            bounded.push(i);
+        } else {
+            m = m.min(d);
+        }
+    }
+    group(children, bounded)
+        .iter()
+        .fold(m, |m, node| guard(node, p, children, m))
+}
+
+/// Recursively splits the children along the longest axis of their centers.
+fn group(children: &[(Expr, BoundingBox)], mut indices: Vec<usize>) -> Vec<Node> {
+    if indices.len() <= MAX_GROUP_SIZE {
+        return indices.into_iter().map(Node::Child).collect();
+    }
+    let centers = indices
+        .iter()
+        .map(|i| BoundingBox::new(children[*i].1.center(), children[*i].1.center()))
+        .reduce(|a, b| a.union(&b))
+        .unwrap();
+    let axis = centers.size().imax();
+    indices.sort_by(|a, b| {
+        let a = children[*a].1.center()[axis];
+        let b = children[*b].1.center()[axis];
+        a.total_cmp(&b)
+    });
+    let second = indices.split_off(indices.len() / 2);
+    [indices, second]
+        .into_iter()
+        .map(|indices| {
+            let bounds = indices
+                .iter()
+                .map(|i| children[*i].1)
+                .reduce(|a, b| a.union(&b))
+                .unwrap();
+            Node::Group(bounds, group(children, indices))
+        })
+        .collect()
+}
+
+/// Lowers `m` to the minimum with the children of `node`, unless its bounds are too far away.
+fn guard(node: &Node, p: &Expr, children: &[(Expr, BoundingBox)], m: Expr) -> Expr {
+    m.bind("m", |m| {
+        let (bounds, lowered) = match node {
+            Node::Child(i) => (&children[*i].1, m.min(&children[*i].0)),
+            Node::Group(bounds, nodes) => (
+                bounds,
+                nodes
+                    .iter()
+                    .fold(m.clone(), |m, node| guard(node, p, children, m)),
+            ),
+        };
+        shortcut(bounds_distance(p, bounds).lt(m), lowered, m)
+    })
+}
+
+fn bounds_distance(p: &Expr, bounds: &BoundingBox) -> Expr {
+    thread_local! {
+        static BOUNDS_DISTANCE: Rc<Function> = Function::new(
+            "BoundsDistance",
+            [("p", Type::Vec3), ("c", Type::Vec3), ("h", Type::Vec3)],
+            |[p, c, h]| {
+                (((p - c).abs()) - h).bind("q", |q| {
+                    q.max(0.).length() + q.x().max(q.y().max(q.z())).min(0.)
+                })
+            },
+        );
+    }
+    let bounds_distance = BOUNDS_DISTANCE.with(Rc::clone);
+    bounds_distance.call([
+        p.clone(),
+        bounds.center().into(),
+        (bounds.size() * 0.5).into(),
+    ])
+}
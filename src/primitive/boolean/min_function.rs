@@ -1,14 +1,65 @@
-use super::Primitive;
+use super::bounding_volumes::guarded_min;
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::{select, Expr, Function, Type};
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::rc::Rc;
 
-pub trait MinFunction: MinFunctionClone {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String>;
-    fn eval(&self, d: &[f32]) -> Result<f32>;
+/// Step used to estimate partial derivatives by central differences.
+const DERIVATIVE_EPSILON: f32 = 1e-4;
+
+#[typetag::serde]
+pub trait MinFunction: MinFunctionClone + Any {
+    /// Combines the distances of the children.
+    fn combine(&self, d: &[Expr]) -> Result<Expr>;
+    /// Builds the combined distance of the children at `p`.
+    fn sdf(&self, p: &Expr, children: &[Box<dyn Primitive>]) -> Result<Expr> {
+        self.combine(&children.iter().map(|c| c.sdf(p)).collect::<Vec<_>>())
+    }
+    fn eval(&self, d: &[f32]) -> Result<f32> {
+        let d = d.iter().map(|d| Expr::from(*d)).collect::<Vec<_>>();
+        match self.combine(&d)?.constant() {
+            Some(value) => Ok(value.float()),
+            None => bail!(
+                "{} does not combine constant distances into a constant",
+                self.description()
+            ),
+        }
+    }
+    /// Returns the partial derivatives of `eval` with respect to each of the distances.
+    /// Estimated by central differences unless computed analytically.
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        let mut d = d.to_vec();
+        (0..d.len())
+            .map(|i| {
+                let di = d[i];
+                d[i] = di + DERIVATIVE_EPSILON;
+                let upper = self.eval(&d)?;
+                d[i] = di - DERIVATIVE_EPSILON;
+                let lower = self.eval(&d)?;
+                d[i] = di;
+                Ok((upper - lower) / (2. * DERIVATIVE_EPSILON))
+            })
+            .collect()
+    }
+    /// Returns a range containing all results for distances within the given ranges.
+    /// Unless overridden, `eval` must not decrease when any of the distances increases.
+    fn eval_interval(&self, d: &[Interval]) -> Result<Interval> {
+        let min = d.iter().map(|d| d.min).collect::<Vec<_>>();
+        let max = d.iter().map(|d| d.max).collect::<Vec<_>>();
+        Ok(Interval::new(self.eval(&min)?, self.eval(&max)?))
+    }
+    /// Returns how far the blended surface of `n` children can reach beyond their plain union.
+    fn max_offset(&self, _n: usize) -> f32 {
+        0.0
+    }
+    /// Returns the name and constructor parameters, see [`describe`](super::super::describe).
+    fn description(&self) -> Description;
+    /// Checks the parameters the way the constructor does, e.g. after deserializing.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait MinFunctionClone {
@@ -32,45 +83,60 @@ impl Clone for Box<dyn MinFunction> {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinDefault;
 
+#[typetag::serde]
 impl MinFunction for MinDefault {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        let local_p = "p";
-        let min_exps = children
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        Ok(d.iter().fold(1e10.into(), |min, d| min.min(d)))
+    }
+    fn sdf(&self, p: &Expr, children: &[Box<dyn Primitive>]) -> Result<Expr> {
+        if p.constant().is_some() {
+            // Skipping children only pays off in shaders. It gives the same result, as only
+            // exact children get skipped.
+            return self.combine(&children.iter().map(|c| c.sdf(p)).collect::<Vec<_>>());
+        }
+        // Only exact distances can be skipped by their bounds.
+        let children = children
             .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            .map(|c_expr| format!("    m = min(m, {});", c_expr))
-            .collect::<Vec<_>>()
-            .join("\n");
-        let function_name = format!("MinDefault{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float m = 1e10;
-{min_exps}
-    return m;
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            min_exps = min_exps
-        ));
-        Ok(format!("{}({})", function_name, p))
+            .map(|c| {
+                let bounds = if c.is_exact() {
+                    c.bounds()
+                } else {
+                    BoundingBox::infinite()
+                };
+                (c.sdf(p), bounds)
+            })
+            .collect::<Vec<_>>();
+        Ok(guarded_min(p, &children))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
-        Ok(d.iter().fold(1e10, |min, x| min.min(*x)))
+    fn description(&self) -> Description {
+        Description::new("MinDefault")
+    }
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        Ok(one_hot_min(d))
+    }
+}
+
+/// Returns the distances of exactly two children.
+fn two_children<'a>(name: &str, d: &'a [Expr]) -> Result<[&'a Expr; 2]> {
+    if d.len() != 2 {
+        bail!("{} requires exactly 2 children - got {}.", name, d.len());
+    }
+    Ok([&d[0], &d[1]])
+}
+
+/// Returns the derivatives of the plain minimum, which only depends on the smallest distance.
+fn one_hot_min(d: &[f32]) -> Vec<f32> {
+    let mut weights = vec![0.; d.len()];
+    if let Some(i) = (0..d.len()).min_by(|a, b| d[*a].total_cmp(&d[*b])) {
+        weights[i] = 1.;
     }
+    weights
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinPolynomial {
     k: f32,
 }
@@ -84,54 +150,47 @@ impl MinPolynomial {
     }
 }
 
+#[typetag::serde]
 impl MinFunction for MinPolynomial {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        if children.len() != 2 {
-            bail!(
-                "MinPolynomial requires exactly 2 children - got {}.",
-                children.len()
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        let [d0, d1] = two_children("MinPolynomial", d)?;
+        thread_local! {
+            static BLEND: Rc<Function> = Function::new(
+                "MinPolynomial",
+                [("d0", Type::Float), ("d1", Type::Float), ("k", Type::Float)],
+                |[d0, d1, k]| {
+                    (&k - (&d0 - &d1).abs()).max(0.).bind("h", |h| {
+                        d0.min(d1) - h * h * 0.25 / k
+                    })
+                },
             );
         }
-        let local_p = "p";
-        let children = children
-            .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?;
-        let function_name = format!("MinPolynomial{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float d0 = {child0};
-    float d1 = {child1};
-    float h = max(k - abs(d0 - d1), 0.0);
-    return min(d0, d1) - h * h * 0.25 / {k:.8};
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            child0 = children[0],
-            child1 = children[1],
-            k = self.k
-        ));
-        Ok(format!("{}({})", function_name, p))
+        let blend = BLEND.with(Rc::clone);
+        Ok(blend.call([d0.clone(), d1.clone(), self.k.into()]))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
-        if d.len() != 2 {
-            bail!(
-                "MinPolynomial requires exactly 2 children - got {}.",
-                d.len()
-            );
-        }
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        self.eval(d)?;
+        // Blends from the closer to the farther child within k.
         let h = (self.k - (d[0] - d[1]).abs()).max(0.0);
-        Ok(d[0].min(d[1]) - h * h * 0.25 / self.k)
+        let w = 0.5 * h / self.k;
+        Ok(if d[0] < d[1] {
+            vec![1. - w, w]
+        } else {
+            vec![w, 1. - w]
+        })
+    }
+    fn max_offset(&self, _n: usize) -> f32 {
+        self.k * 0.25
+    }
+    fn description(&self) -> Description {
+        Description::new("MinPolynomial").parameter("k", self.k)
+    }
+    fn validate(&self) -> Result<()> {
+        MinPolynomial::new(self.k).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinCubicPolynomial {
     k: f32,
 }
@@ -145,55 +204,46 @@ impl MinCubicPolynomial {
     }
 }
 
+#[typetag::serde]
 impl MinFunction for MinCubicPolynomial {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        if children.len() != 2 {
-            bail!(
-                "MinCubicPolynomial requires exactly 2 children - got {}.",
-                children.len()
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        let [d0, d1] = two_children("MinCubicPolynomial", d)?;
+        thread_local! {
+            static BLEND: Rc<Function> = Function::new(
+                "MinCubicPolynomial",
+                [("d0", Type::Float), ("d1", Type::Float), ("k", Type::Float)],
+                |[d0, d1, k]| {
+                    ((&k - (&d0 - &d1).abs()).max(0.) / &k).bind("h", |h| {
+                        d0.min(d1) - h * h * h * k * (1. / 6.)
+                    })
+                },
             );
         }
-        let local_p = "p";
-        let children = children
-            .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?;
-        let function_name = format!("MinCubicPolynomial{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float d0 = {child0};
-    float d1 = {child1};
-    float k = {k:.8};
-    float h = max(k - abs(d0 - d1), 0.0) / k;
-    return min(d0, d1) - h * h * h * k * (1./6.);
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            child0 = children[0],
-            child1 = children[1],
-            k = self.k
-        ));
-        Ok(format!("{}({})", function_name, p))
+        let blend = BLEND.with(Rc::clone);
+        Ok(blend.call([d0.clone(), d1.clone(), self.k.into()]))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
-        if d.len() != 2 {
-            bail!(
-                "MinCubicPolynomial requires exactly 2 children - got {}.",
-                d.len()
-            );
-        }
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        self.eval(d)?;
         let h = (self.k - (d[0] - d[1]).abs()).max(0.0) / self.k;
-        Ok(d[0].min(d[1]) - h * h * h * self.k * (1. / 6.))
+        let w = 0.5 * h * h;
+        Ok(if d[0] < d[1] {
+            vec![1. - w, w]
+        } else {
+            vec![w, 1. - w]
+        })
+    }
+    fn max_offset(&self, _n: usize) -> f32 {
+        self.k / 6.
+    }
+    fn description(&self) -> Description {
+        Description::new("MinCubicPolynomial").parameter("k", self.k)
+    }
+    fn validate(&self) -> Result<()> {
+        MinCubicPolynomial::new(self.k).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinRoot {
     k: f32,
 }
@@ -207,51 +257,40 @@ impl MinRoot {
     }
 }
 
+#[typetag::serde]
 impl MinFunction for MinRoot {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        if children.len() != 2 {
-            bail!(
-                "MinRoot requires exactly 2 children - got {}.",
-                children.len()
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        let [d0, d1] = two_children("MinRoot", d)?;
+        thread_local! {
+            static BLEND: Rc<Function> = Function::new(
+                "MinRoot",
+                [("d0", Type::Float), ("d1", Type::Float), ("k", Type::Float)],
+                |[d0, d1, k]| {
+                    (&d0 - &d1).bind("h", |h| 0.5 * ((d0 + d1) - (h * h + k).sqrt()))
+                },
             );
         }
-        let local_p = "p";
-        let children = children
-            .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?;
-        let function_name = format!("MinRoot{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float d0 = {child0};
-    float d1 = {child1};
-    float h = d0 - d1;
-    return 0.5 * ((d0 + d1) - sqrt(h *h + {k:.8}));
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            child0 = children[0],
-            child1 = children[1],
-            k = self.k
-        ));
-        Ok(format!("{}({})", function_name, p))
+        let blend = BLEND.with(Rc::clone);
+        Ok(blend.call([d0.clone(), d1.clone(), self.k.into()]))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
-        if d.len() != 2 {
-            bail!("MinRoot requires exactly 2 children - got {}.", d.len());
-        }
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        self.eval(d)?;
         let h = d[0] - d[1];
-        Ok(0.5 * ((d[0] + d[1]) - (h * h + self.k).sqrt()))
+        let s = h / (h * h + self.k).sqrt();
+        Ok(vec![0.5 * (1. - s), 0.5 * (1. + s)])
+    }
+    fn max_offset(&self, _n: usize) -> f32 {
+        self.k.sqrt() * 0.5
+    }
+    fn description(&self) -> Description {
+        Description::new("MinRoot").parameter("k", self.k)
+    }
+    fn validate(&self) -> Result<()> {
+        MinRoot::new(self.k).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinChamfer {
     k: f32,
 }
@@ -265,49 +304,43 @@ impl MinChamfer {
     }
 }
 
+#[typetag::serde]
 impl MinFunction for MinChamfer {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        if children.len() != 2 {
-            bail!(
-                "MinChamfer requires exactly 2 children - got {}.",
-                children.len()
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        let [d0, d1] = two_children("MinChamfer", d)?;
+        thread_local! {
+            static BLEND: Rc<Function> = Function::new(
+                "MinChamfer",
+                [("d0", Type::Float), ("d1", Type::Float), ("k", Type::Float)],
+                |[d0, d1, k]| {
+                    d0.min(&d1).min((&d0 - k + &d1) * 0.5_f32.sqrt())
+                },
             );
         }
-        let local_p = "p";
-        let children = children
-            .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?;
-        let function_name = format!("MinChamfer{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float d0 = {child0};
-    float d1 = {child1};
-    return min(min(d0, d1), (d0 - {k:.8} + d1) * sqrt(0.5));
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            child0 = children[0],
-            child1 = children[1],
-            k = self.k
-        ));
-        Ok(format!("{}({})", function_name, p))
+        let blend = BLEND.with(Rc::clone);
+        Ok(blend.call([d0.clone(), d1.clone(), self.k.into()]))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
-        if d.len() != 2 {
-            bail!("MinChamfer requires exactly 2 children - got {}.", d.len());
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        self.eval(d)?;
+        let chamfer = (d[0] - self.k + d[1]) * 0.5_f32.sqrt();
+        if chamfer < d[0].min(d[1]) {
+            Ok(vec![0.5_f32.sqrt(); 2])
+        } else {
+            Ok(one_hot_min(d))
         }
-        Ok(d[0].min(d[1]).min((d[0] - self.k + d[1]) * 0.5_f32.sqrt()))
+    }
+    fn max_offset(&self, _n: usize) -> f32 {
+        self.k * 0.5
+    }
+    fn description(&self) -> Description {
+        Description::new("MinChamfer").parameter("k", self.k)
+    }
+    fn validate(&self) -> Result<()> {
+        MinChamfer::new(self.k).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinStairs {
     k: f32,
     n: i32,
@@ -325,57 +358,51 @@ impl MinStairs {
     }
 }
 
+#[typetag::serde]
 impl MinFunction for MinStairs {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        if children.len() != 2 {
-            bail!(
-                "MinStairs requires exactly 2 children - got {}.",
-                children.len()
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        let [d0, d1] = two_children("MinStairs", d)?;
+        thread_local! {
+            static BLEND: Rc<Function> = Function::new(
+                "MinStairs",
+                [
+                    ("d0", Type::Float),
+                    ("d1", Type::Float),
+                    ("k", Type::Float),
+                    ("n", Type::Float),
+                ],
+                |[d0, d1, k, n]| {
+                    (&k / n).bind("s", |s| {
+                        (&d1 - k).bind("u", |u| {
+                            let stairs = (u - &d0 + s).modulo(2. * s) - s;
+                            d0.min(&d1).min(0.5 * (u + &d0 + stairs.abs()))
+                        })
+                    })
+                },
             );
         }
-        let local_p = "p";
-        let children = children
-            .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?;
-        let function_name = format!("MinStairs{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float d0 = {child0};
-    float d1 = {child1};
-    float k = {k:.8};
-    float s = k / {n};
-    float u = d1 - k;
-    return min(min(d0, d1), 0.5 * (u + d0 + abs((mod(u - d0 + s, 2. * s)) - s)));
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            child0 = children[0],
-            child1 = children[1],
-            k = self.k,
-            n = self.n
-        ));
-        Ok(format!("{}({})", function_name, p))
+        let blend = BLEND.with(Rc::clone);
+        Ok(blend.call([
+            d0.clone(),
+            d1.clone(),
+            self.k.into(),
+            (self.n as f32).into(),
+        ]))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
-        if d.len() != 2 {
-            bail!("MinStairs requires exactly 2 children - got {}.", d.len());
-        }
-        let s = self.k / self.n as f32;
-        let u = d[1] / self.k;
-        Ok(d[0]
-            .min(d[1])
-            .min(0.5 * ((u + d[0] + s).rem_euclid(2. * s) - s).abs()))
+    fn max_offset(&self, _n: usize) -> f32 {
+        self.k
+    }
+    fn description(&self) -> Description {
+        Description::new("MinStairs")
+            .parameter("k", self.k)
+            .parameter("n", self.n)
+    }
+    fn validate(&self) -> Result<()> {
+        MinStairs::new(self.k, self.n).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct MinExponential {
     k: f32,
 }
@@ -389,66 +416,56 @@ impl MinExponential {
     }
 }
 
+#[typetag::serde]
 impl MinFunction for MinExponential {
-    fn expression(
-        &self,
-        p: &str,
-        shared_code: &mut Vec<String>,
-        children: &[Box<dyn Primitive>],
-    ) -> Result<String> {
-        if children.len() < 2 {
+    fn combine(&self, d: &[Expr]) -> Result<Expr> {
+        if d.len() < 2 {
             bail!(
-                "MinExponential requires at least two arguments (got {}).",
-                children.len()
+                "MinExponential requires at least 2 children - got {}.",
+                d.len()
             );
         }
-        let local_p = "p";
-        let min_exps = children
-            .iter()
-            .map(|c| c.expression(local_p, shared_code))
-            .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            .map(|c_expr| {
-                format!(
-                    "    t = {}; d = min(d, t); res += exp2(-{:.8} * t);",
-                    c_expr, self.k
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
-        let function_name = format!("MinExponential{}", shared_code.len());
-        shared_code.push(format!(
-            "
-float {function_name}(vec3 {local_p}) {{
-    float t = 0.0;
-    float d = 1e10;
-    float res = 0.0;
-{min_exps}
-    if (res < 10.0) {{
-        return -log2(res) / {k:.8};
-    }} else {{
-        return d;
-    }}
-}}",
-            function_name = function_name,
-            local_p = local_p,
-            min_exps = min_exps,
-            k = self.k,
-        ));
-        Ok(format!("{}({})", function_name, p))
+        Ok(exponential(d, self.k, 1e10.into(), 0.0.into()))
     }
-    fn eval(&self, d: &[f32]) -> Result<f32> {
+    fn partial_derivatives(&self, d: &[f32]) -> Result<Vec<f32>> {
+        self.eval(d)?;
+        let terms = d.iter().map(|d| (d * -self.k).exp2()).collect::<Vec<_>>();
+        let res: f32 = terms.iter().sum();
+        if res < 10.0 {
+            // A softmin, weighted by each term's share of the sum.
+            Ok(terms.iter().map(|t| t / res).collect())
+        } else {
+            Ok(one_hot_min(d))
+        }
+    }
+    fn eval_interval(&self, d: &[Interval]) -> Result<Interval> {
         if d.len() < 2 {
             bail!(
                 "MinExponential requires at least 2 children - got {}.",
                 d.len()
             );
         }
-        let res: f32 = d.iter().map(|d| (d * -self.k).exp2()).sum();
-        if res < 10.0 {
-            Ok(-(res.log2()) / self.k)
-        } else {
-            Ok(d.iter().fold(1e10, |min, x| min.min(*x)))
-        }
+        // Switching to the plain minimum is not monotonic, but both stay within max_offset.
+        let min = d[1..].iter().fold(d[0], |min, d| min.min(d));
+        Ok(Interval::new(min.min - self.max_offset(d.len()), min.max))
+    }
+    fn max_offset(&self, n: usize) -> f32 {
+        (n as f32).log2() / self.k
+    }
+    fn description(&self) -> Description {
+        Description::new("MinExponential").parameter("k", self.k)
+    }
+    fn validate(&self) -> Result<()> {
+        MinExponential::new(self.k).map(|_| ())
+    }
+}
+
+/// Accumulates the plain minimum `d` and the sum of exponentials `res` over the children.
+fn exponential(children: &[Expr], k: f32, d: Expr, res: Expr) -> Expr {
+    match children.split_first() {
+        Some((child, rest)) => child.bind("t", |t| {
+            exponential(rest, k, d.min(t), res + (t * -k).exp2())
+        }),
+        None => res.bind("res", |res| select(res.lt(10.), -res.log2() / k, d)),
     }
 }
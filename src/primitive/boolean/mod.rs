@@ -1,13 +1,17 @@
-use super::Primitive;
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::Expr;
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 
+mod bounding_volumes;
 pub mod min_function;
 pub use min_function::{
     MinChamfer, MinCubicPolynomial, MinDefault, MinExponential, MinFunction, MinPolynomial,
     MinRoot, MinStairs,
 };
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Boolean {
     children: Vec<Box<dyn Primitive>>,
     min_function: Box<dyn MinFunction>,
@@ -16,17 +20,13 @@ pub struct Boolean {
 
 impl Boolean {
     fn new_maybe_negate(children: Vec<Box<dyn Primitive>>, negate: bool) -> Result<Box<Boolean>> {
-        if children.len() < 2 {
-            bail!(
-                "Boolean requires at least 2 children (got only {}).",
-                children.len()
-            );
-        }
-        Ok(Box::new(Boolean {
+        let boolean = Boolean {
             children,
             min_function: Box::new(MinDefault {}),
             negate,
-        }))
+        };
+        boolean.check()?;
+        Ok(Box::new(boolean))
     }
     pub fn new_union(children: Vec<Box<dyn Primitive>>) -> Result<Box<Boolean>> {
         Boolean::new_maybe_negate(children, false)
@@ -34,56 +34,235 @@ impl Boolean {
     pub fn new_intersection(children: Vec<Box<dyn Primitive>>) -> Result<Box<Boolean>> {
         let neg_children = children
             .into_iter()
-            .map(|child| Box::new(Negation { child: child }) as Box<dyn Primitive>)
+            .map(|child| Box::new(Negation { child }) as Box<dyn Primitive>)
             .collect();
         Boolean::new_maybe_negate(neg_children, true)
     }
     pub fn new_difference(mut children: Vec<Box<dyn Primitive>>) -> Result<Box<Boolean>> {
-        if children.len() == 0 {
+        if children.is_empty() {
             bail!("Difference requires at least one child (got none).");
         }
-        let mut new_children = vec![children.swap_remove(0)];
-        while !children.is_empty() {
-            let child = children.pop().unwrap();
-            new_children.push(Box::new(Negation { child }));
+        let first = children.remove(0);
+        let subtracted = children
+            .into_iter()
+            .map(|child| Box::new(Negation { child }) as Box<dyn Primitive>);
+        Boolean::new_intersection(std::iter::once(first).chain(subtracted).collect())
+    }
+    /// Returns the children if this is a union.
+    pub fn union_children(&self) -> Option<&[Box<dyn Primitive>]> {
+        if self.negate {
+            None
+        } else {
+            Some(&self.children)
         }
-        Boolean::new_intersection(new_children)
     }
     pub fn set_min_function(&mut self, f: Box<dyn MinFunction>) -> Result<()> {
-        // Test if this expression works for our number of children.
-        let test_child_d = self.children.iter().map(|_| 0_f32).collect::<Vec<_>>();
-        f.eval(&test_child_d)?;
+        check_min_function(&*f, self.children.len())?;
         self.min_function = f;
         Ok(())
     }
+    fn check(&self) -> Result<()> {
+        if self.children.len() < 2 {
+            bail!(
+                "Boolean requires at least 2 children (got only {}).",
+                self.children.len()
+            );
+        }
+        check_min_function(&*self.min_function, self.children.len())
+    }
+}
+
+fn check_min_function(f: &dyn MinFunction, children: usize) -> Result<()> {
+    f.validate()?;
+    // Test if this expression works for our number of children.
+    f.eval(&vec![0.; children])?;
+    Ok(())
 }
 
+#[typetag::serde]
 impl Primitive for Boolean {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        let expression = self
-            .min_function
-            .expression(p, shared_code, &self.children)?;
-        let negate = if self.negate { "-" } else { "" };
-        Ok(format!("{}{}", negate, expression))
+    fn sdf(&self, p: &Expr) -> Expr {
+        p.bind("p", |p| {
+            let d = self.min_function.sdf(p, &self.children).expect(
+                "MinFunction failed despite having been tested in set_min_function before.",
+            );
+            if self.negate {
+                -d
+            } else {
+                d
+            }
+        })
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
         let min_d = self.children.iter().map(|c| c.eval(p)).collect::<Vec<_>>();
-        self.min_function
-            .eval(&min_d)
-            .expect("MinFunction failed despite having been tested in set_min_function before.")
+        let weights = self
+            .min_function
+            .partial_derivatives(&min_d)
+            .expect("MinFunction failed despite having been tested in set_min_function before.");
+        // Chain rule: children that do not affect the result need not be evaluated.
+        let g = self
+            .children
+            .iter()
+            .zip(weights)
+            .filter(|(_, w)| *w != 0.)
+            .fold(glm::Vec3::zeros(), |g, (c, w)| {
+                g + c.gradient(p, epsilon) * w
+            });
+        if self.negate {
+            -g
+        } else {
+            g
+        }
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        let min_d = self
+            .children
+            .iter()
+            .map(|c| c.eval_interval(region))
+            .collect::<Vec<_>>();
+        let d = self
+            .min_function
+            .eval_interval(&min_d)
+            .expect("MinFunction failed despite having been tested in set_min_function before.");
+        if self.negate {
+            -d
+        } else {
+            d
+        }
+    }
+    fn bounds(&self) -> BoundingBox {
+        if self.negate {
+            // An intersection of negated children. Blending only shrinks intersections.
+            self.children
+                .iter()
+                .map(|c| match (&**c as &dyn Any).downcast_ref::<Negation>() {
+                    Some(n) => n.child.bounds(),
+                    None => BoundingBox::infinite(),
+                })
+                .fold(BoundingBox::infinite(), |a, b| a.intersection(&b))
+        } else {
+            let union = self.children[1..]
+                .iter()
+                .fold(self.children[0].bounds(), |a, c| a.union(&c.bounds()));
+            union.dilate(self.min_function.max_offset(self.children.len()))
+        }
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        // Without blending, the minimum of nested minima is the minimum of all their children.
+        let flatten = (&*self.min_function as &dyn Any).is::<MinDefault>();
+        let mut children = Vec::new();
+        for child in &self.children {
+            let child = child.simplify();
+            if let Some(grandchildren) = min_children(&*child).filter(|_| flatten) {
+                children.extend_from_slice(grandchildren);
+            } else {
+                children.push(child);
+            }
+        }
+        Box::new(Boolean {
+            children,
+            min_function: self.min_function.clone(),
+            negate: self.negate,
+        })
+    }
+    fn description(&self) -> Description {
+        let intersection = |children: Vec<Description>| {
+            children
+                .into_iter()
+                .fold(Description::new("Intersection"), Description::child)
+        };
+        let (name, children) = if self.negate {
+            // Intersections negate their children, differences negate the subtracted ones twice.
+            let mut kept = Vec::new();
+            let mut subtracted = Vec::new();
+            for child in &self.children {
+                match negated(&**child).map(|c| (c, negated(c))) {
+                    Some((_, Some(c))) => subtracted.push(c.description()),
+                    Some((c, None)) => kept.push(c.description()),
+                    // Simplifying removes the double negation.
+                    None => subtracted.push(child.description()),
+                }
+            }
+            if subtracted.is_empty() {
+                ("Intersection", kept)
+            } else {
+                let first = match kept.len() {
+                    1 => kept.remove(0),
+                    _ => intersection(kept),
+                };
+                subtracted.insert(0, first);
+                ("Difference", subtracted)
+            }
+        } else {
+            let children = self.children.iter().map(|c| c.description()).collect();
+            ("Union", children)
+        };
+        let mut description = Description::new(name);
+        if !(&*self.min_function as &dyn Any).is::<MinDefault>() {
+            description = description.parameter("min_function", self.min_function.description());
+        }
+        children.into_iter().fold(description, Description::child)
+    }
+    fn validate(&self) -> Result<()> {
+        for child in &self.children {
+            child.validate()?;
+        }
+        self.check()
     }
 }
 
-#[derive(Clone)]
+/// Returns the children if `p` computes their minimum without blending.
+fn min_children(p: &dyn Primitive) -> Option<&[Box<dyn Primitive>]> {
+    let any = p as &dyn Any;
+    let boolean = match any.downcast_ref::<Negation>() {
+        // Negating an intersection gives the minimum of its negated children.
+        Some(n) => (&*n.child as &dyn Any)
+            .downcast_ref::<Boolean>()
+            .filter(|b| b.negate)?,
+        None => any.downcast_ref::<Boolean>().filter(|b| !b.negate)?,
+    };
+    (&*boolean.min_function as &dyn Any)
+        .is::<MinDefault>()
+        .then_some(&boolean.children[..])
+}
+
+/// Returns the child if `p` is a negation.
+fn negated(p: &dyn Primitive) -> Option<&dyn Primitive> {
+    (p as &dyn Any)
+        .downcast_ref::<Negation>()
+        .map(|n| &*n.child)
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 struct Negation {
     child: Box<dyn Primitive>,
 }
 
+#[typetag::serde]
 impl Primitive for Negation {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        Ok(format!("-({})", self.child.expression(p, shared_code)?))
+    fn sdf(&self, p: &Expr) -> Expr {
+        -self.child.sdf(p)
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        -self.child.gradient(p, epsilon)
+    }
+    fn is_exact(&self) -> bool {
+        self.child.is_exact()
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        -self.child.eval_interval(region)
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        let child = self.child.simplify();
+        match (&*child as &dyn Any).downcast_ref::<Negation>() {
+            Some(n) => n.child.clone(),
+            None => Box::new(Negation { child }),
+        }
+    }
+    fn description(&self) -> Description {
+        Description::new("Negation").child(self.child.description())
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        -self.child.eval(p)
+    fn validate(&self) -> Result<()> {
+        self.child.validate()
     }
 }
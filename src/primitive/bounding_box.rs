@@ -0,0 +1,115 @@
+use super::boxes::{box_gradient, box_interval};
+use super::Interval;
+
+/// An axis aligned box. Infinite extents are represented by infinite coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl BoundingBox {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> BoundingBox {
+        BoundingBox { min, max }
+    }
+    /// A box containing everything.
+    pub fn infinite() -> BoundingBox {
+        BoundingBox {
+            min: glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            max: glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+    pub fn is_finite(&self) -> bool {
+        self.min
+            .iter()
+            .chain(self.max.iter())
+            .all(|c| c.is_finite())
+    }
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+    pub fn size(&self) -> glm::Vec3 {
+        self.max - self.min
+    }
+    pub fn center(&self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+    pub fn intersection(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: self.min.sup(&other.min),
+            max: self.max.inf(&other.max),
+        }
+    }
+    /// Grows the box by `d` in every direction.
+    pub fn dilate(&self, d: f32) -> BoundingBox {
+        let d = glm::vec3(d, d, d);
+        BoundingBox {
+            min: self.min - d,
+            max: self.max + d,
+        }
+    }
+    pub fn translate(&self, v: &glm::Vec3) -> BoundingBox {
+        BoundingBox {
+            min: self.min + v,
+            max: self.max + v,
+        }
+    }
+    /// Returns the box containing this box transformed by `m`.
+    pub fn transform(&self, m: &glm::Mat3x3) -> BoundingBox {
+        let mut result = BoundingBox::new(glm::Vec3::zeros(), glm::Vec3::zeros());
+        for i in 0..3 {
+            for j in 0..3 {
+                let a = m[(i, j)];
+                // Take care not to multiply infinity with zero.
+                if a == 0. {
+                    continue;
+                }
+                let (low, high) = (a * self.min[j], a * self.max[j]);
+                result.min[i] += low.min(high);
+                result.max[i] += low.max(high);
+            }
+        }
+        result
+    }
+    /// Scales the box component wise.
+    pub fn scale(&self, s: &glm::Vec3) -> BoundingBox {
+        self.transform(&glm::Mat3x3::from_diagonal(s))
+    }
+    /// Returns the largest distance of the box to the z axis.
+    pub fn radius_xy(&self) -> f32 {
+        let x = self.min[0].abs().max(self.max[0].abs());
+        let y = self.min[1].abs().max(self.max[1].abs());
+        x.hypot(y)
+    }
+    /// Returns the range of the `i`th coordinate.
+    pub fn axis(&self, i: usize) -> Interval {
+        Interval::new(self.min[i], self.max[i])
+    }
+    /// Returns the range of distances of points in the box to the origin.
+    pub fn norm(&self) -> Interval {
+        (self.axis(0).square() + self.axis(1).square() + self.axis(2).square()).sqrt()
+    }
+    /// Returns the range of distances of points in the box to the z axis.
+    pub fn norm_xy(&self) -> Interval {
+        (self.axis(0).square() + self.axis(1).square()).sqrt()
+    }
+    /// Signed distance of `p` to the box surface, negative inside.
+    pub fn distance(&self, p: glm::Vec3) -> f32 {
+        let q = (p - self.center()).abs() - self.size() * 0.5;
+        q.sup(&glm::vec3(0., 0., 0.)).norm() + q.max().min(0.)
+    }
+    /// Range of [`BoundingBox::distance`] within `region`.
+    pub fn distance_interval(&self, region: &BoundingBox) -> Interval {
+        box_interval(&region.translate(&-self.center()), self.size() * 0.5)
+    }
+    /// Gradient of [`BoundingBox::distance`].
+    pub fn gradient(&self, p: glm::Vec3) -> glm::Vec3 {
+        box_gradient(p - self.center(), self.size() * 0.5)
+    }
+}
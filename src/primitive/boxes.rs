@@ -1,7 +1,10 @@
-use super::{shader_vec3, Primitive};
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::{Expr, Function, Type};
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct ExactBox {
     size: glm::Vec3,
 }
@@ -15,26 +18,32 @@ impl ExactBox {
     }
 }
 
+#[typetag::serde]
 impl Primitive for ExactBox {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        shared_code.push(
-            r#"
-float Box( vec3 p, vec3 b ) {
-    vec3 q = abs(p) - b;
-    return length(max(q,0.0)) + min(max(q.x,max(q.y,q.z)),0.0);
-}
-"#
-            .to_string(),
-        );
-        Ok(format!("Box({}, {})", p, shader_vec3(&self.size)))
+    fn sdf(&self, p: &Expr) -> Expr {
+        box_function().call([p.clone(), self.size.into()])
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        box_gradient(p, self.size)
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        box_interval(region, self.size)
+    }
+    fn is_exact(&self) -> bool {
+        true
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        let q = v3_abs(p) - self.size;
-        q.sup(&glm::vec3(0_f32, 0_f32, 0_f32)).norm() + q[1].max(q[2]).max(q[0]).min(0.0)
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(-self.size, self.size)
+    }
+    fn description(&self) -> Description {
+        Description::new("ExactBox").parameter("size", self.size * 2.)
+    }
+    fn validate(&self) -> Result<()> {
+        ExactBox::new(self.size * 2.).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct RoundBox {
     size: glm::Vec3,
     radius: f32,
@@ -55,29 +64,79 @@ impl RoundBox {
     }
 }
 
+#[typetag::serde]
 impl Primitive for RoundBox {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        shared_code.push(
-            r#"
-float RoundBox( vec3 p, vec3 b, float r ) {
-    vec3 q = abs(p) - b;
-    return length(max(q,0.0)) + min(max(q.x,max(q.y,q.z)),0.0) - r;
+    fn sdf(&self, p: &Expr) -> Expr {
+        box_function().call([p.clone(), self.size.into()]) - self.radius
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        box_gradient(p, self.size)
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        box_interval(region, self.size) - Interval::point(self.radius)
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(-self.size, self.size).dilate(self.radius)
+    }
+    fn description(&self) -> Description {
+        Description::new("RoundBox")
+            .parameter("size", self.size.add_scalar(self.radius) * 2.)
+            .parameter("radius", self.radius)
+    }
+    fn validate(&self) -> Result<()> {
+        let r = glm::vec3(self.radius, self.radius, self.radius);
+        RoundBox::new((self.size + r) * 2., self.radius).map(|_| ())
+    }
 }
-"#
-            .to_string(),
+
+/// The distance of `p` to a box centered at the origin with half size `b`.
+pub(super) fn box_function() -> Rc<Function> {
+    thread_local! {
+        static BOX: Rc<Function> = Function::new(
+            "Box",
+            [("p", Type::Vec3), ("b", Type::Vec3)],
+            |[p, b]| {
+                (p.abs() - b).bind("q", |q| {
+                    q.max(0.).length() + q.x().max(q.y().max(q.z())).min(0.)
+                })
+            },
         );
-        Ok(format!(
-            "RoundBox({}, {}, {:.8})",
-            p,
-            shader_vec3(&self.size),
-            self.radius
-        ))
-    }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        let q = v3_abs(p) - self.size;
-        q.sup(&glm::vec3(0_f32, 0_f32, 0_f32)).norm() + q[1].max(q[2]).max(q[0]).min(0.0)
-            - self.radius
     }
+    BOX.with(Rc::clone)
+}
+
+/// Returns the gradient of the distance to a box centered at the origin.
+pub(super) fn box_gradient(p: glm::Vec3, half_size: glm::Vec3) -> glm::Vec3 {
+    let q = v3_abs(p) - half_size;
+    let g = if q.max() > 0. {
+        // Outside, towards the closest point on the box.
+        q.sup(&glm::Vec3::zeros()).normalize()
+    } else {
+        // Inside, towards the closest face.
+        let mut g = glm::Vec3::zeros();
+        g[q.imax()] = 1.;
+        g
+    };
+    glm::vec3(
+        g[0].copysign(p[0]),
+        g[1].copysign(p[1]),
+        g[2].copysign(p[2]),
+    )
+}
+
+/// Returns the range of distances to a box centered at the origin within `region`.
+pub(super) fn box_interval(region: &BoundingBox, half_size: glm::Vec3) -> Interval {
+    let q = [0, 1, 2].map(|i| region.axis(i).abs() - Interval::point(half_size[i]));
+    let zero = Interval::point(0.);
+    let outside = q
+        .iter()
+        .fold(zero, |sum, q| sum + q.max(&zero).square())
+        .sqrt();
+    let inside = q[0].max(&q[1]).max(&q[2]).min(&zero);
+    outside + inside
 }
 
 fn v3_abs(v: glm::Vec3) -> glm::Vec3 {
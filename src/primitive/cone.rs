@@ -0,0 +1,304 @@
+use super::{BoundingBox, Description, Primitive};
+use crate::ir::{select, Expr, Function, Type};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+/// A cone with its base disc centered at the origin and its tip on the positive z axis.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Cone {
+    radius: f32,
+    height: f32,
+}
+
+impl Cone {
+    pub fn new(radius: f32, height: f32) -> Result<Box<dyn Primitive>> {
+        if radius <= 0. {
+            bail!("radius should be positive (was {}).", radius);
+        }
+        if height <= 0. {
+            bail!("height should be positive (was {}).", height);
+        }
+        Ok(Box::new(Cone { radius, height }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Cone {
+    fn sdf(&self, p: &Expr) -> Expr {
+        capped_cone(
+            p,
+            glm::vec3(0., 0., 0.),
+            glm::vec3(0., 0., self.height),
+            self.radius,
+            0.,
+        )
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        let r = self.radius;
+        BoundingBox::new(glm::vec3(-r, -r, 0.), glm::vec3(r, r, self.height))
+    }
+    fn description(&self) -> Description {
+        Description::new("Cone")
+            .parameter("radius", self.radius)
+            .parameter("height", self.height)
+    }
+    fn validate(&self) -> Result<()> {
+        Cone::new(self.radius, self.height).map(|_| ())
+    }
+}
+
+/// A truncated cone between two points, with a radius at each of them.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CappedCone {
+    begin_radius: f32,
+    end_radius: f32,
+    begin: glm::Vec3,
+    end: glm::Vec3,
+}
+
+impl CappedCone {
+    pub fn new(
+        begin_radius: f32,
+        end_radius: f32,
+        begin: glm::Vec3,
+        end: glm::Vec3,
+    ) -> Result<Box<dyn Primitive>> {
+        if begin_radius < 0. || end_radius < 0. {
+            bail!(
+                "radii should not be negative (were {} and {}).",
+                begin_radius,
+                end_radius
+            );
+        }
+        if begin_radius.max(end_radius) == 0. {
+            bail!("one of the radii should be positive.");
+        }
+        if begin == end {
+            bail!("Height must be > 0.");
+        }
+        Ok(Box::new(CappedCone {
+            begin_radius,
+            end_radius,
+            begin,
+            end,
+        }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for CappedCone {
+    fn sdf(&self, p: &Expr) -> Expr {
+        capped_cone(p, self.begin, self.end, self.begin_radius, self.end_radius)
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        // The caps are discs perpendicular to the axis.
+        let e = disc_extent(self.end - self.begin);
+        let disc = |center: glm::Vec3, radius: f32| {
+            BoundingBox::new(center - e * radius, center + e * radius)
+        };
+        disc(self.begin, self.begin_radius).union(&disc(self.end, self.end_radius))
+    }
+    fn description(&self) -> Description {
+        Description::new("CappedCone")
+            .parameter("begin_radius", self.begin_radius)
+            .parameter("end_radius", self.end_radius)
+            .parameter("begin", self.begin)
+            .parameter("end", self.end)
+    }
+    fn validate(&self) -> Result<()> {
+        CappedCone::new(self.begin_radius, self.end_radius, self.begin, self.end).map(|_| ())
+    }
+}
+
+/// The convex hull of two spheres.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RoundCone {
+    begin_radius: f32,
+    end_radius: f32,
+    begin: glm::Vec3,
+    end: glm::Vec3,
+}
+
+impl RoundCone {
+    pub fn new(
+        begin_radius: f32,
+        end_radius: f32,
+        begin: glm::Vec3,
+        end: glm::Vec3,
+    ) -> Result<Box<dyn Primitive>> {
+        if begin_radius <= 0. || end_radius <= 0. {
+            bail!(
+                "radii should be positive (were {} and {}).",
+                begin_radius,
+                end_radius
+            );
+        }
+        let length = (end - begin).norm();
+        if length <= (begin_radius - end_radius).abs() {
+            bail!(
+                "the spheres should not contain each other (distance {}, radii {} and {}).",
+                length,
+                begin_radius,
+                end_radius
+            );
+        }
+        Ok(Box::new(RoundCone {
+            begin_radius,
+            end_radius,
+            begin,
+            end,
+        }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for RoundCone {
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static ROUND_CONE: Rc<Function> = Function::new(
+                "RoundCone",
+                [
+                    ("p", Type::Vec3),
+                    ("a", Type::Vec3),
+                    ("b", Type::Vec3),
+                    ("r1", Type::Float),
+                    ("r2", Type::Float),
+                ],
+                |[p, a, b, r1, r2]| {
+                    (&b - &a).bind("ba", |ba| {
+                        (p - a).bind("pa", |pa| {
+                            ba.dot(ba).bind("l2", |l2| {
+                                (&r1 - &r2).bind("rr", |rr| {
+                                    (l2 - rr * rr).bind("a2", |a2| {
+                                        pa.dot(ba).bind("y", |y| {
+                                            (pa * l2 - ba * y).bind("q", |q| {
+                                                q.dot(q).bind("x2", |x2| {
+                                                    (y - l2).bind("z", |z| {
+                                                        // Which of the spheres or the cone
+                                                        // between them is closest.
+                                                        let k = rr.sign() * rr * rr * x2;
+                                                        k.bind("k", |k| {
+                                                            let z2 = z * z * l2;
+                                                            let y2 = y * y * l2;
+                                                            select(
+                                                                (z.sign() * a2 * &z2).gt(k),
+                                                                (x2 + z2).sqrt() / l2 - &r2,
+                                                                select(
+                                                                    (y.sign() * a2 * &y2).lt(k),
+                                                                    (x2 + y2).sqrt() / l2 - &r1,
+                                                                    ((x2 * a2 / l2).sqrt()
+                                                                        + y * rr)
+                                                                        / l2
+                                                                        - &r1,
+                                                                ),
+                                                            )
+                                                        })
+                                                    })
+                                                })
+                                            })
+                                        })
+                                    })
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+        }
+        let round_cone = ROUND_CONE.with(Rc::clone);
+        round_cone.call([
+            p.clone(),
+            self.begin.into(),
+            self.end.into(),
+            self.begin_radius.into(),
+            self.end_radius.into(),
+        ])
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        let sphere =
+            |center: glm::Vec3, radius: f32| BoundingBox::new(center, center).dilate(radius);
+        sphere(self.begin, self.begin_radius).union(&sphere(self.end, self.end_radius))
+    }
+    fn description(&self) -> Description {
+        Description::new("RoundCone")
+            .parameter("begin_radius", self.begin_radius)
+            .parameter("end_radius", self.end_radius)
+            .parameter("begin", self.begin)
+            .parameter("end", self.end)
+    }
+    fn validate(&self) -> Result<()> {
+        RoundCone::new(self.begin_radius, self.end_radius, self.begin, self.end).map(|_| ())
+    }
+}
+
+/// Builds the exact distance to the truncated cone from `a` with radius `ra` to `b` with radius
+/// `rb`.
+fn capped_cone(p: &Expr, a: glm::Vec3, b: glm::Vec3, ra: f32, rb: f32) -> Expr {
+    thread_local! {
+        static CAPPED_CONE: Rc<Function> = Function::new(
+            "CappedCone",
+            [
+                ("p", Type::Vec3),
+                ("a", Type::Vec3),
+                ("b", Type::Vec3),
+                ("ra", Type::Float),
+                ("rb", Type::Float),
+            ],
+            |[p, a, b, ra, rb]| {
+                (&b - &a).bind("ba", |ba| {
+                    (p - a).bind("pa", |pa| {
+                        ba.dot(ba).bind("baba", |baba| {
+                            (pa.dot(ba) / baba).bind("paba", |paba| {
+                                // The distance from the axis.
+                                let x = (pa.dot(pa) - paba * paba * baba).max(0.).sqrt();
+                                x.bind("x", |x| {
+                                    (&rb - &ra).bind("rba", |rba| {
+                                        // Towards the closest cap.
+                                        let cax = (x - select(paba.lt(0.5), &ra, &rb)).max(0.);
+                                        let cay = (paba - 0.5).abs() - 0.5;
+                                        // Towards the slanted side.
+                                        let k = rba * rba + baba;
+                                        let f = ((rba * (x - &ra) + paba * baba) / k).clamp(0., 1.);
+                                        f.bind("f", |f| {
+                                            (x - &ra - f * rba).bind("cbx", |cbx| {
+                                                cay.bind("cay", |cay| {
+                                                    let cby = paba - f;
+                                                    let s = select(cbx.max(cay).lt(0.), -1., 1.);
+                                                    s * (&cax * &cax + cay * cay * baba)
+                                                        .min(cbx * cbx + &cby * &cby * baba)
+                                                        .sqrt()
+                                                })
+                                            })
+                                        })
+                                    })
+                                })
+                            })
+                        })
+                    })
+                })
+            },
+        );
+    }
+    let capped_cone = CAPPED_CONE.with(Rc::clone);
+    capped_cone.call([p.clone(), a.into(), b.into(), ra.into(), rb.into()])
+}
+
+/// Returns the extent along each coordinate axis of a unit disc perpendicular to `axis`.
+fn disc_extent(axis: glm::Vec3) -> glm::Vec3 {
+    let a = axis.normalize();
+    glm::vec3(
+        (1. - a[0] * a[0]).max(0.).sqrt(),
+        (1. - a[1] * a[1]).max(0.).sqrt(),
+        (1. - a[2] * a[2]).max(0.).sqrt(),
+    )
+}
@@ -1,13 +1,16 @@
-use super::{shader_vec3, Primitive};
+use super::{distance_bound_interval, BoundingBox, Description, Interval, Primitive};
+use crate::ir::{select, vec2, Expr, Function, Type};
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 struct CylinderBounds {
     begin: glm::Vec3,
     end: glm::Vec3,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Cylinder {
     radius: f32,
     bounds: Option<CylinderBounds>,
@@ -30,66 +33,128 @@ impl Cylinder {
                 bail!("Height must be > 0.");
             }
         }
-        return Ok(Box::new(Cylinder { radius, bounds }));
+        Ok(Box::new(Cylinder { radius, bounds }))
     }
 }
 
+#[typetag::serde]
 impl Primitive for Cylinder {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        if self.bounds.is_some() {
-            shared_code.push(
-                r#"
-float CappedCylinder(vec3 p, vec3 a, vec3 b, float r) {
-    vec3  ba = b - a;
-    vec3  pa = p - a;
-    float baba = dot(ba,ba);
-    float paba = dot(pa,ba);
-    float x = length(pa*baba-ba*paba) - r*baba;
-    float y = abs(paba-baba*0.5)-baba*0.5;
-    float x2 = x*x;
-    float y2 = y*y*baba;
-    float d = (max(x, y) < 0.0) ? -min(x2, y2) : (((x > 0.0) ? x2 : 0.0) + ((y > 0.0) ? y2 : 0.0));
-    return sign(d)*sqrt(abs(d))/baba;
-}
-"#
-                .to_string(),
+    fn sdf(&self, p: &Expr) -> Expr {
+        let bounds = match self.bounds {
+            Some(ref bounds) => bounds,
+            None => return p.xy().length() - self.radius,
+        };
+        thread_local! {
+            static CAPPED_CYLINDER: Rc<Function> = Function::new(
+                "CappedCylinder",
+                [
+                    ("p", Type::Vec3),
+                    ("a", Type::Vec3),
+                    ("b", Type::Vec3),
+                    ("r", Type::Float),
+                ],
+                |[p, a, b, r]| {
+                    (&b - &a).bind("ba", |ba| {
+                        (p - a).bind("pa", |pa| {
+                            ba.dot(ba).bind("baba", |baba| {
+                                pa.dot(ba).bind("paba", |paba| {
+                                    let x = (pa * baba - ba * paba).length() - r * baba;
+                                    let y = (paba - baba * 0.5).abs() - baba * 0.5;
+                                    x.bind("x", |x| {
+                                        y.bind("y", |y| {
+                                            let x2 = x * x;
+                                            let y2 = y * y * baba;
+                                            let d = select(
+                                                x.max(y).lt(0.),
+                                                -x2.min(&y2),
+                                                select(x.gt(0.), &x2, 0.)
+                                                    + select(y.gt(0.), &y2, 0.),
+                                            );
+                                            d.bind("d", |d| d.sign() * d.abs().sqrt() / baba)
+                                        })
+                                    })
+                                })
+                            })
+                        })
+                    })
+                },
             );
         }
-        if let Some(ref bounds) = self.bounds {
-            Ok(format!(
-                "CappedCylinder({p}, {a}, {b}, {r:.8})",
-                p = p,
-                a = shader_vec3(&bounds.begin),
-                b = shader_vec3(&bounds.end),
-                r = self.radius
-            ))
-        } else {
-            Ok(format!("length(({}).xy) - {:.8}", p, self.radius))
+        let capped_cylinder = CAPPED_CYLINDER.with(Rc::clone);
+        capped_cylinder.call([
+            p.clone(),
+            bounds.begin.into(),
+            bounds.end.into(),
+            self.radius.into(),
+        ])
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        match self.bounds {
+            Some(ref bounds) => {
+                let axis = bounds.end - bounds.begin;
+                let length = axis.norm();
+                let axis = axis / length;
+                let pa = p - bounds.begin;
+                let h = pa.dot(&axis);
+                let radial = pa - axis * h;
+                let along = h - length * 0.5;
+                corner_gradient(
+                    radial.norm() - self.radius,
+                    along.abs() - length * 0.5,
+                    radial.try_normalize(0.).unwrap_or_default(),
+                    axis * 1_f32.copysign(along),
+                )
+            }
+            None => glm::vec3(p[0], p[1], 0.)
+                .try_normalize(0.)
+                .unwrap_or_default(),
+        }
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        match self.bounds {
+            Some(_) => distance_bound_interval(self.eval(region.center()), region),
+            None => region.norm_xy() - Interval::point(self.radius),
+        }
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        match self.bounds {
+            Some(ref bounds) => {
+                // The caps are discs perpendicular to the axis.
+                let a = (bounds.end - bounds.begin).normalize();
+                let e = glm::vec3(
+                    (1. - a[0] * a[0]).max(0.).sqrt(),
+                    (1. - a[1] * a[1]).max(0.).sqrt(),
+                    (1. - a[2] * a[2]).max(0.).sqrt(),
+                ) * self.radius;
+                BoundingBox::new(
+                    bounds.begin.inf(&bounds.end) - e,
+                    bounds.begin.sup(&bounds.end) + e,
+                )
+            }
+            None => {
+                let r = glm::vec3(self.radius, self.radius, f32::INFINITY);
+                BoundingBox::new(-r, r)
+            }
         }
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        if let Some(ref bounds) = self.bounds {
-            let ba = bounds.end - bounds.begin;
-            let pa = p - bounds.begin;
-            let baba = ba.norm_squared();
-            let paba = pa.dot(&ba);
-            let x = (pa * baba - ba * paba).norm() - self.radius * baba;
-            let y = (paba - baba * 0.5).abs() - baba * 0.5;
-            let x2 = x * x;
-            let y2 = y * y * baba;
-            let d = if x.max(y) < 0.0 {
-                -(x2.min(y2))
-            } else {
-                (if x > 0.0 { x2 } else { 0.0 }) + (if y > 0.0 { y2 } else { 0.0 })
-            };
-            d.abs().sqrt().copysign(d) / baba
-        } else {
-            p.rows(0, 2).norm() - self.radius
+    fn description(&self) -> Description {
+        let description = Description::new("Cylinder").parameter("radius", self.radius);
+        match self.bounds {
+            Some(ref bounds) => description
+                .parameter("begin", bounds.begin)
+                .parameter("end", bounds.end),
+            None => description,
         }
     }
+    fn validate(&self) -> Result<()> {
+        Cylinder::new_impl(self.radius, self.bounds.clone()).map(|_| ())
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct RoundedCylinder {
     main_radius: f32,
     rounding_radius: f32,
@@ -118,30 +183,69 @@ impl RoundedCylinder {
     }
 }
 
+#[typetag::serde]
 impl Primitive for RoundedCylinder {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        shared_code.push(
-            r#"
-float RoundedCylinder(vec3 p, float ra, float rb, float h) {
-    vec2 d = vec2(length(p.xy) - 2.0 * ra + rb, abs(p.z) - h);
-    return min(max(d.x, d.y), 0.0) + length(max(d, 0.0)) - rb;
-}
-"#
-            .to_string(),
-        );
-        Ok(format!(
-            "RoundedCylinder({}, {:.8}, {:.8}, {:.8})",
-            p, self.main_radius, self.rounding_radius, self.height
-        ))
-    }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        let dx = p.rows(0, 2).norm() - 2.0 * self.main_radius + self.rounding_radius;
-        let dy = p[2].abs() - self.height;
-        dx.max(dy).min(0.0) + glm::vec2(dx.max(0.), dy.max(0.)).norm() - self.rounding_radius
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static ROUNDED_CYLINDER: Rc<Function> = Function::new(
+                "RoundedCylinder",
+                [
+                    ("p", Type::Vec3),
+                    ("ra", Type::Float),
+                    ("rb", Type::Float),
+                    ("h", Type::Float),
+                ],
+                |[p, ra, rb, h]| {
+                    vec2(p.xy().length() - 2.0 * ra + &rb, p.z().abs() - h).bind("d", |d| {
+                        d.x().max(d.y()).min(0.) + d.max(0.).length() - rb
+                    })
+                },
+            );
+        }
+        let rounded_cylinder = ROUNDED_CYLINDER.with(Rc::clone);
+        rounded_cylinder.call([
+            p.clone(),
+            self.main_radius.into(),
+            self.rounding_radius.into(),
+            self.height.into(),
+        ])
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        let radial = glm::vec3(p[0], p[1], 0.);
+        corner_gradient(
+            radial.norm() - 2.0 * self.main_radius + self.rounding_radius,
+            p[2].abs() - self.height,
+            radial.try_normalize(0.).unwrap_or_default(),
+            glm::vec3(0., 0., 1_f32.copysign(p[2])),
+        )
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        let zero = Interval::point(0.);
+        let dx = region.norm_xy() - Interval::point(2.0 * self.main_radius - self.rounding_radius);
+        let dy = region.axis(2).abs() - Interval::point(self.height);
+        dx.max(&dy).min(&zero) + (dx.max(&zero).square() + dy.max(&zero).square()).sqrt()
+            - Interval::point(self.rounding_radius)
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        let r = 2.0 * self.main_radius;
+        let r = glm::vec3(r, r, self.height + self.rounding_radius);
+        BoundingBox::new(-r, r)
+    }
+    fn description(&self) -> Description {
+        Description::new("RoundedCylinder")
+            .parameter("main_radius", self.main_radius)
+            .parameter("rounding_radius", self.rounding_radius)
+            .parameter("height", self.height)
+    }
+    fn validate(&self) -> Result<()> {
+        RoundedCylinder::new(self.main_radius, self.rounding_radius, self.height).map(|_| ())
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Capsule {
     radius: f32,
     begin: glm::Vec3,
@@ -157,30 +261,67 @@ impl Capsule {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Capsule {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        shared_code.push(
-            r#"
-float Capsule(vec3 p, vec3 a, vec3 b, float r) {
-    vec3 pa = p - a, ba = b - a;
-    float h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0 );
-    return length(pa - ba * h) - r;
-}
-"#
-            .to_string(),
-        );
-        Ok(format!(
-            "Capsule({}, {}, {}, {:.8})",
-            p,
-            shader_vec3(&self.begin),
-            shader_vec3(&self.end),
-            self.radius
-        ))
-    }
-    fn eval(&self, p: glm::Vec3) -> f32 {
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static CAPSULE: Rc<Function> = Function::new(
+                "Capsule",
+                [
+                    ("p", Type::Vec3),
+                    ("a", Type::Vec3),
+                    ("b", Type::Vec3),
+                    ("r", Type::Float),
+                ],
+                |[p, a, b, r]| {
+                    (p - &a).bind("pa", |pa| {
+                        (b - a).bind("ba", |ba| {
+                            let h = (pa.dot(ba) / ba.dot(ba)).clamp(0., 1.);
+                            (pa - ba * h).length() - r
+                        })
+                    })
+                },
+            );
+        }
+        let capsule = CAPSULE.with(Rc::clone);
+        capsule.call([
+            p.clone(),
+            self.begin.into(),
+            self.end.into(),
+            self.radius.into(),
+        ])
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
         let pa = p - self.begin;
         let ba = self.end - self.begin;
         let h = (pa.dot(&ba) / ba.norm_squared()).clamp(0., 1.);
-        (pa - ba * h).norm() - self.radius
+        (pa - ba * h).try_normalize(0.).unwrap_or_default()
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(self.begin.inf(&self.end), self.begin.sup(&self.end)).dilate(self.radius)
+    }
+    fn description(&self) -> Description {
+        Description::new("Capsule")
+            .parameter("radius", self.radius)
+            .parameter("begin", self.begin)
+            .parameter("end", self.end)
+    }
+    fn validate(&self) -> Result<()> {
+        Capsule::new(self.radius, self.begin, self.end).map(|_| ())
+    }
+}
+
+/// Returns the gradient of the distance to a 2d corner, given the distances `x` and `y` to its
+/// sides and the directions `u` and `v` they increase along.
+fn corner_gradient(x: f32, y: f32, u: glm::Vec3, v: glm::Vec3) -> glm::Vec3 {
+    if x.max(y) > 0. {
+        (u * x.max(0.) + v * y.max(0.)).normalize()
+    } else if x > y {
+        u
+    } else {
+        v
     }
 }
@@ -0,0 +1,132 @@
+//! Prints primitive trees for debugging, one node per line with its children indented below.
+
+use super::Primitive;
+use std::fmt;
+
+/// A node of a primitive tree the way it gets built: the name of the primitive, the parameters
+/// it was constructed with and the descriptions of its children.
+pub struct Description {
+    name: String,
+    parameters: Vec<(String, String)>,
+    children: Vec<Description>,
+}
+
+impl Description {
+    pub fn new(name: &str) -> Description {
+        Description {
+            name: name.to_string(),
+            parameters: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+    pub fn parameter(mut self, name: &str, value: impl Parameter) -> Description {
+        self.parameters.push((name.to_string(), value.format()));
+        self
+    }
+    pub fn child(mut self, child: Description) -> Description {
+        self.children.push(child);
+        self
+    }
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        write!(f, "{}{}", "  ".repeat(depth), self.name)?;
+        if !self.parameters.is_empty() {
+            let parameters = self
+                .parameters
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect::<Vec<_>>();
+            write!(f, "({})", parameters.join(", "))?;
+        }
+        for child in &self.children {
+            writeln!(f)?;
+            child.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+/// Returns the tree of `primitive` with the parameters of every node, e.g.
+///
+/// ```text
+/// Translate(vector: [1.0, 0.0, 0.0])
+///   Sphere(radius: 1.5)
+/// ```
+pub fn describe(primitive: &dyn Primitive) -> String {
+    primitive.description().to_string()
+}
+
+impl fmt::Debug for dyn Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.description())
+    }
+}
+
+/// A value that can be shown as a parameter of a [`Description`].
+pub trait Parameter {
+    fn format(&self) -> String;
+}
+
+fn list(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(", "))
+}
+
+impl Parameter for f32 {
+    fn format(&self) -> String {
+        // Debug keeps the shortest representation that reads back the same. Adding zero turns
+        // -0.0, e.g. from inverting matrices, into 0.0.
+        format!("{:?}", self + 0.)
+    }
+}
+
+impl Parameter for i32 {
+    fn format(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Parameter for &str {
+    fn format(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl Parameter for glm::Vec3 {
+    fn format(&self) -> String {
+        list(self.iter().map(Parameter::format))
+    }
+}
+
+impl Parameter for glm::I32Vec3 {
+    fn format(&self) -> String {
+        list(self.iter().map(Parameter::format))
+    }
+}
+
+/// Matrices are written row by row.
+impl Parameter for glm::Mat4 {
+    fn format(&self) -> String {
+        list(
+            self.row_iter()
+                .map(|row| list(row.iter().map(Parameter::format))),
+        )
+    }
+}
+
+impl<T: Parameter> Parameter for &[T] {
+    fn format(&self) -> String {
+        list(self.iter().map(Parameter::format))
+    }
+}
+
+/// Nested values without children, like the min function of a boolean.
+impl Parameter for Description {
+    fn format(&self) -> String {
+        self.to_string()
+    }
+}
@@ -0,0 +1,121 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// A closed range of values, used to bound distances over whole regions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Interval {
+    pub fn new(min: f32, max: f32) -> Interval {
+        Interval { min, max }
+    }
+    pub fn point(x: f32) -> Interval {
+        Interval { min: x, max: x }
+    }
+    /// The interval of all values.
+    pub fn infinite() -> Interval {
+        Interval::new(f32::NEG_INFINITY, f32::INFINITY)
+    }
+    pub fn contains(&self, x: f32) -> bool {
+        self.min <= x && x <= self.max
+    }
+    /// The smallest interval containing both.
+    pub fn hull(&self, other: &Interval) -> Interval {
+        Interval::new(self.min.min(other.min), self.max.max(other.max))
+    }
+    pub fn min(&self, other: &Interval) -> Interval {
+        Interval::new(self.min.min(other.min), self.max.min(other.max))
+    }
+    pub fn max(&self, other: &Interval) -> Interval {
+        Interval::new(self.min.max(other.min), self.max.max(other.max))
+    }
+    pub fn abs(&self) -> Interval {
+        if self.min >= 0. {
+            *self
+        } else if self.max <= 0. {
+            -*self
+        } else {
+            Interval::new(0., self.max.max(-self.min))
+        }
+    }
+    pub fn square(&self) -> Interval {
+        let a = self.abs();
+        Interval::new(a.min * a.min, a.max * a.max)
+    }
+    /// Square root, treating negative values as zero.
+    pub fn sqrt(&self) -> Interval {
+        Interval::new(self.min.max(0.).sqrt(), self.max.max(0.).sqrt())
+    }
+    pub fn sin(&self) -> Interval {
+        (*self - Interval::point(FRAC_PI_2)).cos()
+    }
+    pub fn cos(&self) -> Interval {
+        if self.max - self.min >= 2. * PI {
+            return Interval::new(-1., 1.);
+        }
+        let mut result = Interval::point(self.min.cos()).hull(&Interval::point(self.max.cos()));
+        // Include the extrema at multiples of pi within the interval.
+        let mut k = (self.min / PI).ceil();
+        while k * PI <= self.max {
+            let extremum = if (k as i32).rem_euclid(2) == 0 {
+                1.
+            } else {
+                -1.
+            };
+            result = result.hull(&Interval::point(extremum));
+            k += 1.;
+        }
+        result
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+    fn add(self, other: Interval) -> Interval {
+        Interval::new(self.min + other.min, self.max + other.max)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+    fn sub(self, other: Interval) -> Interval {
+        Interval::new(self.min - other.max, self.max - other.min)
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Interval {
+        Interval::new(-self.max, -self.min)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+    fn mul(self, other: Interval) -> Interval {
+        let products = [
+            self.min * other.min,
+            self.min * other.max,
+            self.max * other.min,
+            self.max * other.max,
+        ];
+        Interval::new(
+            products.iter().copied().fold(f32::INFINITY, f32::min),
+            products.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        )
+    }
+}
+
+impl Mul<f32> for Interval {
+    type Output = Interval;
+    fn mul(self, s: f32) -> Interval {
+        if s >= 0. {
+            Interval::new(self.min * s, self.max * s)
+        } else {
+            Interval::new(self.max * s, self.min * s)
+        }
+    }
+}
@@ -1,17 +1,90 @@
+// Constructors hand out boxed trait objects so they can be composed directly.
+#![allow(clippy::new_ret_no_self)]
+
+use crate::ir::{self, Expr, Type, Value, Var};
 use anyhow::Result;
+use std::any::Any;
 
-pub trait Primitive: PrimitiveClone {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String>;
-    fn eval(&self, p: glm::Vec3) -> f32;
+/// Serializes as a map from the name of the type to its fields, see [`to_json`] and [`to_ron`].
+#[typetag::serde]
+pub trait Primitive: PrimitiveClone + Any {
+    /// Builds the signed distance of `p` to the surface, which is negative inside.
+    fn sdf(&self, p: &Expr) -> Expr;
+    /// Returns GLSL code computing the distance of the point `p`.
+    /// Helper functions get added to `shared_code`.
+    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
+        let x = Var::new("p", Type::Vec3);
+        Ok(ir::glsl::expression(
+            &self.sdf(&x.expr()),
+            &[(x, p)],
+            shared_code,
+        ))
+    }
+    /// Returns WGSL code computing the distance of the point `p`.
+    /// Helper functions get added to `shared_code`.
+    fn wgsl_expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
+        let x = Var::new("p", Type::Vec3);
+        Ok(ir::wgsl::expression(
+            &self.sdf(&x.expr()),
+            &[(x, p)],
+            shared_code,
+        ))
+    }
+    /// Returns the distance at `p`. Evaluating many points is faster with [`Compiled`].
+    fn eval(&self, p: glm::Vec3) -> f32 {
+        ir::cpu::eval(&self.sdf(&p.into())).float()
+    }
+    /// Returns the gradient of `eval` at `p`, which is the outward normal on the surface.
+    /// Estimated by central differences with a step of `epsilon` unless computed analytically.
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        central_difference(|p| self.eval(p), p, epsilon)
+    }
+    /// Returns a range containing all values of `eval` within `region`.
+    /// Unless computed by interval arithmetic, this relies on `eval` being a distance bound,
+    /// i.e. on it not changing faster than the distance to the evaluated point.
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        distance_bound_interval(self.eval(region.center()), region)
+    }
+    /// Returns a box containing the whole object.
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::infinite()
+    }
+    /// Returns an equivalent tree that is cheaper to evaluate, e.g. with chains of
+    /// transformations folded into one node and nested unions flattened.
+    fn simplify(&self) -> Box<dyn Primitive> {
+        self.clone_box()
+    }
+    /// Whether `eval` is the exact signed distance rather than only a bound on it. Only exact
+    /// distances are never smaller than the distance to `bounds`.
+    fn is_exact(&self) -> bool {
+        false
+    }
+    /// Returns the name and constructor parameters of this node, with its children, see
+    /// [`describe`].
+    fn description(&self) -> Description;
+    /// Checks the fields the way the constructor does, including those of all children.
+    /// Deserialized trees skip the constructors, so they get checked by this instead.
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
     fn translate(&self, vector: glm::Vec3) -> Box<dyn Primitive> {
         Translate::new(self.clone_box(), vector)
     }
     fn rotate_euler(&self, r: f32, p: f32, y: f32) -> Box<dyn Primitive> {
         Rotate::from_euler(self.clone_box(), r, p, y)
     }
+    fn rotate_axis(&self, axis: glm::Vec3, angle: f32) -> Result<Box<dyn Primitive>> {
+        Ok(Rotate::from_axis_angle(self.clone_box(), axis, angle)?)
+    }
+    fn align(&self, from: glm::Vec3, to: glm::Vec3) -> Result<Box<dyn Primitive>> {
+        Ok(Rotate::align(self.clone_box(), from, to)?)
+    }
     fn scale(&self, scale: glm::Vec3) -> Box<dyn Primitive> {
         Scale::new(self.clone_box(), scale)
     }
+    fn transform(&self, pose: glm::Mat4) -> Result<Box<dyn Primitive>> {
+        Ok(Transform::new(self.clone_box(), pose)?)
+    }
 }
 
 pub trait PrimitiveClone {
@@ -35,25 +108,53 @@ impl Clone for Box<dyn Primitive> {
     }
 }
 
-fn shader_vec3(v: &glm::Vec3) -> String {
-    format!("vec3({:.8}, {:.8}, {:.8})", v[0], v[1], v[2])
+/// The distance of a primitive, built and compiled once for a variable point. Unlike
+/// [`Primitive::eval`], which builds the distance again for every point, evaluating it only runs
+/// the compiled instructions, so the meshers use it to sample many points.
+pub struct Compiled {
+    program: ir::cpu::Program,
 }
 
-fn shader_mat3(m: &glm::Mat3x3) -> String {
-    let m = m.as_slice();
-    format!(
-        "mat3({:.8}, {:.8}, {:.8},
-                  {:.8}, {:.8}, {:.8},
-                  {:.8}, {:.8}, {:.8})",
-        m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8]
-    )
+impl Compiled {
+    pub fn new(primitive: &dyn Primitive) -> Compiled {
+        let point = Var::new("p", Type::Vec3);
+        Compiled {
+            program: ir::cpu::Program::new(&primitive.sdf(&point.expr()), &[point]),
+        }
+    }
+    pub fn eval(&self, p: glm::Vec3) -> f32 {
+        self.program.eval(&[Value::Vec3(p)]).float()
+    }
+}
+
+fn central_difference(f: impl Fn(glm::Vec3) -> f32, p: glm::Vec3, e: f32) -> glm::Vec3 {
+    glm::vec3(
+        f(p + glm::vec3(e, 0., 0.)) - f(p - glm::vec3(e, 0., 0.)),
+        f(p + glm::vec3(0., e, 0.)) - f(p - glm::vec3(0., e, 0.)),
+        f(p + glm::vec3(0., 0., e)) - f(p - glm::vec3(0., 0., e)),
+    ) / (2. * e)
 }
 
+/// Returns the range a distance bound of `d` at the center can take within `region`.
+fn distance_bound_interval(d: f32, region: &BoundingBox) -> Interval {
+    if !region.is_finite() {
+        return Interval::infinite();
+    }
+    let r = region.size().norm() * 0.5;
+    Interval::new(d - r, d + r)
+}
+
+mod bounding_box;
+pub use bounding_box::BoundingBox;
+
+mod interval;
+pub use interval::Interval;
+
 mod plane;
 pub use plane::Plane;
 
 mod sphere;
-pub use sphere::Sphere;
+pub use sphere::{Ellipsoid, Sphere};
 
 mod torus;
 pub use torus::Torus;
@@ -64,6 +165,14 @@ pub use boxes::{ExactBox, RoundBox};
 mod cylinder;
 pub use cylinder::{Capsule, Cylinder, RoundedCylinder};
 
+mod cone;
+pub use cone::{CappedCone, Cone, RoundCone};
+
+mod polyhedra;
+pub use polyhedra::{
+    ConvexPolyhedron, Dodecahedron, HexPrism, Icosahedron, Octahedron, Tetrahedron, TriPrism,
+};
+
 mod boolean;
 pub use boolean::{
     Boolean, MinChamfer, MinCubicPolynomial, MinExponential, MinFunction, MinPolynomial, MinRoot,
@@ -71,10 +180,19 @@ pub use boolean::{
 };
 
 mod transformations;
-pub use transformations::{Rotate, Scale, Translate};
+pub use transformations::{Rotate, Scale, Transform, Translate};
 
 mod repeat;
 pub use repeat::Repeat;
 
+mod part;
+pub use part::{split_parts, Part};
+
 mod warp;
 pub use warp::{Bend, Twist};
+
+mod serialization;
+pub use serialization::{from_json, from_ron, to_json, to_ron};
+
+mod describe;
+pub use describe::{describe, Description, Parameter};
@@ -0,0 +1,122 @@
+use super::{Boolean, BoundingBox, Description, Interval, Primitive};
+use crate::ir::Expr;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+
+/// Gives a primitive a name and an optional base color, so it can be exported as its own object.
+/// Parts are transparent for rendering and evaluation.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Part {
+    primitive: Box<dyn Primitive>,
+    name: String,
+    color: Option<glm::Vec3>,
+}
+
+impl Part {
+    pub fn new(
+        primitive: Box<dyn Primitive>,
+        name: &str,
+        color: Option<glm::Vec3>,
+    ) -> Result<Box<Part>> {
+        let part = Part {
+            primitive,
+            name: name.to_string(),
+            color,
+        };
+        part.check()?;
+        Ok(Box::new(part))
+    }
+    fn check(&self) -> Result<()> {
+        if self.name.is_empty() {
+            bail!("part name must not be empty.");
+        }
+        if let Some(color) = self.color
+            && (color.min() < 0. || color.max() > 1.)
+        {
+            bail!("color components must be within [0, 1] (was {}).", color);
+        }
+        Ok(())
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn color(&self) -> Option<glm::Vec3> {
+        self.color
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Part {
+    fn sdf(&self, p: &Expr) -> Expr {
+        self.primitive.sdf(p)
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        self.primitive.gradient(p, epsilon)
+    }
+    fn is_exact(&self) -> bool {
+        self.primitive.is_exact()
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        self.primitive.eval_interval(region)
+    }
+    fn bounds(&self) -> BoundingBox {
+        self.primitive.bounds()
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        Box::new(Part {
+            primitive: self.primitive.simplify(),
+            name: self.name.clone(),
+            color: self.color,
+        })
+    }
+    fn description(&self) -> Description {
+        let description = Description::new("Part").parameter("name", self.name.as_str());
+        match self.color {
+            Some(color) => description.parameter("color", color),
+            None => description,
+        }
+        .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()?;
+        self.check()
+    }
+}
+
+/// Name of the part that collects everything not explicitly named.
+const UNNAMED_PART: &str = "sdfer";
+
+/// Splits an object into its top-level parts.
+/// A part is either the object itself or a named child of a top-level union.
+/// Unnamed children of such a union are collected into one additional part.
+pub fn split_parts(p: &dyn Primitive) -> Result<Vec<Box<Part>>> {
+    let any = p as &dyn Any;
+    if let Some(part) = any.downcast_ref::<Part>() {
+        return Ok(vec![Box::new(part.clone())]);
+    }
+    let children = match any
+        .downcast_ref::<Boolean>()
+        .and_then(|b| b.union_children())
+    {
+        Some(children) => children,
+        None => return Ok(vec![Part::new(p.clone_box(), UNNAMED_PART, None)?]),
+    };
+    let mut parts = Vec::new();
+    let mut unnamed = Vec::new();
+    for child in children {
+        match (&**child as &dyn Any).downcast_ref::<Part>() {
+            Some(part) => parts.push(Box::new(part.clone())),
+            None => unnamed.push(child.clone()),
+        }
+    }
+    if parts.is_empty() {
+        return Ok(vec![Part::new(p.clone_box(), UNNAMED_PART, None)?]);
+    }
+    match unnamed.len() {
+        0 => {}
+        1 => parts.push(Part::new(unnamed.remove(0), UNNAMED_PART, None)?),
+        _ => parts.push(Part::new(Boolean::new_union(unnamed)?, UNNAMED_PART, None)?),
+    }
+    Ok(parts)
+}
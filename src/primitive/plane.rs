@@ -1,7 +1,9 @@
-use super::{shader_vec3, Primitive};
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::Expr;
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Plane {
     normal: glm::Vec3,
     d: f32,
@@ -16,16 +18,45 @@ impl Plane {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Plane {
-    fn expression(&self, p: &str, _shared_code: &mut Vec<String>) -> Result<String> {
-        Ok(format!(
-            "dot({}, {}) + {:.8}",
-            p,
-            shader_vec3(&self.normal),
-            self.d
-        ))
-    }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        p.dot(&self.normal) + self.d
+    fn sdf(&self, p: &Expr) -> Expr {
+        p.dot(self.normal) + self.d
+    }
+    fn gradient(&self, _p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        self.normal
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        (0..3).fold(Interval::point(self.d), |d, i| {
+            d + region.axis(i) * self.normal[i]
+        })
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        // Only planes facing along an axis bound anything.
+        let mut bounds = BoundingBox::infinite();
+        for i in 0..3 {
+            if self.normal[i].abs() == 1. {
+                if self.normal[i] > 0. {
+                    bounds.max[i] = -self.d;
+                } else {
+                    bounds.min[i] = self.d;
+                }
+            }
+        }
+        bounds
+    }
+    fn description(&self) -> Description {
+        Description::new("Plane")
+            .parameter("normal", self.normal)
+            .parameter("d", self.d)
+    }
+    fn validate(&self) -> Result<()> {
+        if (self.normal.norm() - 1.).abs() > 1e-5 {
+            bail!("Normal must have unit length (was {}).", self.normal);
+        }
+        Ok(())
     }
 }
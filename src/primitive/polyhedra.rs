@@ -0,0 +1,517 @@
+use super::{BoundingBox, Description, Primitive};
+use crate::ir::{fold, select, vec2, vec3, Expr, Function, Type, Value};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+/// The golden ratio.
+const PHI: f32 = 1.618034;
+const SQRT_3: f32 = 1.7320508;
+
+fn check_radius(radius: f32) -> Result<()> {
+    if radius <= 0. {
+        bail!("radius should be positive (was {}).", radius);
+    }
+    Ok(())
+}
+
+fn check_prism(radius: f32, height: f32) -> Result<()> {
+    check_radius(radius)?;
+    if height <= 0. {
+        bail!("height should be positive (was {}).", height);
+    }
+    Ok(())
+}
+
+/// A regular tetrahedron whose faces are `radius` away from the origin, with a vertex pointing
+/// along (-1, -1, -1).
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Tetrahedron {
+    radius: f32,
+}
+
+impl Tetrahedron {
+    pub fn new(radius: f32) -> Result<Box<dyn Primitive>> {
+        check_radius(radius)?;
+        Ok(Box::new(Tetrahedron { radius }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Tetrahedron {
+    fn sdf(&self, p: &Expr) -> Expr {
+        let normals = [
+            glm::vec3(1., 1., 1.),
+            glm::vec3(1., -1., -1.),
+            glm::vec3(-1., 1., -1.),
+            glm::vec3(-1., -1., 1.),
+        ];
+        p.bind("p", |p| planes(p, &normals, self.radius))
+    }
+    fn bounds(&self) -> BoundingBox {
+        symmetric_bounds(self.radius * SQRT_3)
+    }
+    fn description(&self) -> Description {
+        Description::new("Tetrahedron").parameter("radius", self.radius)
+    }
+    fn validate(&self) -> Result<()> {
+        check_radius(self.radius)
+    }
+}
+
+/// A regular octahedron whose faces are `radius` away from the origin, with its vertices on
+/// the axes.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Octahedron {
+    radius: f32,
+}
+
+impl Octahedron {
+    pub fn new(radius: f32) -> Result<Box<dyn Primitive>> {
+        check_radius(radius)?;
+        Ok(Box::new(Octahedron { radius }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Octahedron {
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static OCTAHEDRON: Rc<Function> = Function::new(
+                "Octahedron",
+                [("p", Type::Vec3), ("s", Type::Float)],
+                |[p, s]| {
+                    p.abs().bind("a", |a| {
+                        (a.x() + a.y() + a.z() - &s).bind("m", |m| {
+                            // Rotates the coordinates such that the closest vertex or edge is
+                            // found the same way for every face.
+                            let q = select(
+                                (a.x() * 3.).lt(m),
+                                a,
+                                select(
+                                    (a.y() * 3.).lt(m),
+                                    vec3(a.y(), a.z(), a.x()),
+                                    vec3(a.z(), a.x(), a.y()),
+                                ),
+                            );
+                            q.bind("q", |q| {
+                                ((q.z() - q.y() + &s) * 0.5).clamp(0., &s).bind("k", |k| {
+                                    let edge = vec3(q.x(), q.y() - &s + k, q.z() - k).length();
+                                    // Points closest to the inside of a face.
+                                    let face = m * (1. / SQRT_3);
+                                    select((a.x().min(a.y()).min(a.z()) * 3.).lt(m), edge, face)
+                                })
+                            })
+                        })
+                    })
+                },
+            );
+        }
+        let octahedron = OCTAHEDRON.with(Rc::clone);
+        octahedron.call([p.clone(), (self.radius * SQRT_3).into()])
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        symmetric_bounds(self.radius * SQRT_3)
+    }
+    fn description(&self) -> Description {
+        Description::new("Octahedron").parameter("radius", self.radius)
+    }
+    fn validate(&self) -> Result<()> {
+        check_radius(self.radius)
+    }
+}
+
+/// A regular dodecahedron whose faces are `radius` away from the origin.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Dodecahedron {
+    radius: f32,
+}
+
+impl Dodecahedron {
+    pub fn new(radius: f32) -> Result<Box<dyn Primitive>> {
+        check_radius(radius)?;
+        Ok(Box::new(Dodecahedron { radius }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Dodecahedron {
+    fn sdf(&self, p: &Expr) -> Expr {
+        // Together with their mirror images, these are the normals of all faces.
+        let normals = [
+            glm::vec3(0., PHI, 1.),
+            glm::vec3(1., 0., PHI),
+            glm::vec3(PHI, 1., 0.),
+        ];
+        p.abs().bind("a", |a| planes(a, &normals, self.radius))
+    }
+    fn bounds(&self) -> BoundingBox {
+        symmetric_bounds(self.radius * (1. + PHI * PHI).sqrt() / PHI)
+    }
+    fn description(&self) -> Description {
+        Description::new("Dodecahedron").parameter("radius", self.radius)
+    }
+    fn validate(&self) -> Result<()> {
+        check_radius(self.radius)
+    }
+}
+
+/// A regular icosahedron whose faces are `radius` away from the origin.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Icosahedron {
+    radius: f32,
+}
+
+impl Icosahedron {
+    pub fn new(radius: f32) -> Result<Box<dyn Primitive>> {
+        check_radius(radius)?;
+        Ok(Box::new(Icosahedron { radius }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Icosahedron {
+    fn sdf(&self, p: &Expr) -> Expr {
+        // Together with their mirror images, these are the normals of all faces.
+        let normals = [
+            glm::vec3(1., 1., 1.),
+            glm::vec3(1. / PHI, 0., PHI),
+            glm::vec3(PHI, 1. / PHI, 0.),
+            glm::vec3(0., PHI, 1. / PHI),
+        ];
+        p.abs().bind("a", |a| planes(a, &normals, self.radius))
+    }
+    fn bounds(&self) -> BoundingBox {
+        symmetric_bounds(self.radius * SQRT_3 / PHI)
+    }
+    fn description(&self) -> Description {
+        Description::new("Icosahedron").parameter("radius", self.radius)
+    }
+    fn validate(&self) -> Result<()> {
+        check_radius(self.radius)
+    }
+}
+
+/// A prism along the z axis with a regular hexagon as cross section, whose sides are `radius`
+/// away from the axis and perpendicular to y for two of them.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HexPrism {
+    radius: f32,
+    height: f32,
+}
+
+impl HexPrism {
+    pub fn new(radius: f32, height: f32) -> Result<Box<dyn Primitive>> {
+        check_prism(radius, height)?;
+        Ok(Box::new(HexPrism { radius, height }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for HexPrism {
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static HEXAGON: Rc<Function> = Function::new(
+                "Hexagon",
+                [("p", Type::Vec2), ("r", Type::Float)],
+                |[p, r]| {
+                    let k = glm::vec2(-0.5 * SQRT_3, 0.5);
+                    p.abs().bind("a", |a| {
+                        // Mirrors at the diagonal side, so that the closest side is the top one.
+                        (a - a.dot(k).min(0.) * 2. * k).bind("q", |q| {
+                            let x = q.x().clamp(&r * (-1. / SQRT_3), &r * (1. / SQRT_3));
+                            (q - vec2(x, &r)).length() * (q.y() - &r).sign()
+                        })
+                    })
+                },
+            );
+        }
+        let hexagon = HEXAGON.with(Rc::clone);
+        p.bind("p", |p| {
+            extrude(&hexagon.call([p.xy(), self.radius.into()]), p, self.height)
+        })
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        let r = self.radius;
+        let r = glm::vec3(r * 2. / SQRT_3, r, self.height * 0.5);
+        BoundingBox::new(-r, r)
+    }
+    fn description(&self) -> Description {
+        Description::new("HexPrism")
+            .parameter("radius", self.radius)
+            .parameter("height", self.height)
+    }
+    fn validate(&self) -> Result<()> {
+        check_prism(self.radius, self.height)
+    }
+}
+
+/// A prism along the z axis with an equilateral triangle as cross section, whose sides are
+/// `radius` away from the axis and which points towards y.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TriPrism {
+    radius: f32,
+    height: f32,
+}
+
+impl TriPrism {
+    pub fn new(radius: f32, height: f32) -> Result<Box<dyn Primitive>> {
+        check_prism(radius, height)?;
+        Ok(Box::new(TriPrism { radius, height }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for TriPrism {
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static TRIANGLE: Rc<Function> = Function::new(
+                "Triangle",
+                [("p", Type::Vec2), ("r", Type::Float)],
+                |[p, r]| {
+                    // Half the length of a side.
+                    (&r * SQRT_3).bind("s", |s| {
+                        vec2(p.x().abs() - s, p.y() + &r).bind("a", |a| {
+                            // Mirrors at the side through the apex, so that the closest side is
+                            // the bottom one.
+                            let mirrored =
+                                vec2(a.x() - a.y() * SQRT_3, a.x() * -SQRT_3 - a.y()) * 0.5;
+                            let q = select((a.x() + a.y() * SQRT_3).gt(0.), mirrored, a);
+                            q.bind("q", |q| {
+                                let d = vec2(q.x() - q.x().clamp(s * -2., 0.), q.y());
+                                -(d.length() * q.y().sign())
+                            })
+                        })
+                    })
+                },
+            );
+        }
+        let triangle = TRIANGLE.with(Rc::clone);
+        p.bind("p", |p| {
+            extrude(&triangle.call([p.xy(), self.radius.into()]), p, self.height)
+        })
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        let r = self.radius;
+        BoundingBox::new(
+            glm::vec3(-r * SQRT_3, -r, -self.height * 0.5),
+            glm::vec3(r * SQRT_3, 2. * r, self.height * 0.5),
+        )
+    }
+    fn description(&self) -> Description {
+        Description::new("TriPrism")
+            .parameter("radius", self.radius)
+            .parameter("height", self.height)
+    }
+    fn validate(&self) -> Result<()> {
+        check_prism(self.radius, self.height)
+    }
+}
+
+/// The convex hull of points, or the intersection of half spaces. The distance is the largest
+/// distance to the planes of the faces, which is exact inside and a bound outside. Shaders loop
+/// over the faces, so the code stays small for many of them.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ConvexPolyhedron {
+    /// The outward unit normals of the faces.
+    normals: Vec<glm::Vec3>,
+    /// The distances of the faces from the origin, along their normals.
+    offsets: Vec<f32>,
+    vertices: Vec<glm::Vec3>,
+}
+
+impl ConvexPolyhedron {
+    /// The convex hull of `vertices`, which must not all lie in a plane.
+    pub fn new(vertices: Vec<glm::Vec3>) -> Result<Box<dyn Primitive>> {
+        let epsilon = tolerance(vertices.iter().map(|v| v.amax()));
+        let mut normals = Vec::<glm::Vec3>::new();
+        let mut offsets = Vec::<f32>::new();
+        for (i, a) in vertices.iter().enumerate() {
+            for (j, b) in vertices.iter().enumerate().skip(i + 1) {
+                for c in vertices.iter().skip(j + 1) {
+                    let Some(normal) = (b - a).cross(&(c - a)).try_normalize(epsilon) else {
+                        continue;
+                    };
+                    let offset = normal.dot(a);
+                    let heights = vertices.iter().map(|v| normal.dot(v) - offset);
+                    // Faces have all vertices on one side.
+                    let (normal, offset) = if heights.clone().all(|h| h <= epsilon) {
+                        (normal, offset)
+                    } else if heights.clone().all(|h| h >= -epsilon) {
+                        (-normal, -offset)
+                    } else {
+                        continue;
+                    };
+                    let known = normals
+                        .iter()
+                        .zip(&offsets)
+                        .any(|(n, d)| n.dot(&normal) > 1. - 1e-6 && (d - offset).abs() <= epsilon);
+                    if !known {
+                        normals.push(normal);
+                        offsets.push(offset);
+                    }
+                }
+            }
+        }
+        if normals.len() < 4 {
+            bail!("ConvexPolyhedron: the vertices must not all lie in a plane.");
+        }
+        ConvexPolyhedron::with_vertices(normals, offsets, vertices, epsilon)
+    }
+
+    /// The intersection of the half spaces `dot(p, normal) <= offset`, where the normals get
+    /// normalized first. So each offset is the distance of a face from the origin.
+    pub fn from_planes(normals: Vec<glm::Vec3>, offsets: Vec<f32>) -> Result<Box<dyn Primitive>> {
+        if normals.len() != offsets.len() {
+            bail!(
+                "ConvexPolyhedron: expected an offset for each of the {} normals (got {}).",
+                normals.len(),
+                offsets.len()
+            );
+        }
+        let normals = normals
+            .iter()
+            .map(|n| n.try_normalize(0.))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("ConvexPolyhedron: normals must be != 0."))?;
+        for (i, a) in normals.iter().enumerate() {
+            for b in normals.iter().skip(i + 1) {
+                // Directions along the edge where the planes meet must leave some half space.
+                let edge = a.cross(b);
+                let leaves = |u: glm::Vec3| normals.iter().any(|n| n.dot(&u) > 1e-6);
+                if edge.norm() > 1e-6 && !(leaves(edge) && leaves(-edge)) {
+                    bail!("ConvexPolyhedron: the planes do not enclose a bounded solid.");
+                }
+            }
+        }
+        let epsilon = tolerance(offsets.iter().map(|d| d.abs()));
+        // The corners are where three planes meet within all half spaces.
+        let mut vertices = Vec::<glm::Vec3>::new();
+        for (i, a) in normals.iter().enumerate() {
+            for (j, b) in normals.iter().enumerate().skip(i + 1) {
+                for (k, c) in normals.iter().enumerate().skip(j + 1) {
+                    let Some(inverse) =
+                        glm::Mat3::from_rows(&[a.transpose(), b.transpose(), c.transpose()])
+                            .try_inverse()
+                    else {
+                        continue;
+                    };
+                    let v = inverse * glm::vec3(offsets[i], offsets[j], offsets[k]);
+                    let inside = normals
+                        .iter()
+                        .zip(&offsets)
+                        .all(|(n, d)| n.dot(&v) - d <= epsilon);
+                    if inside && !vertices.iter().any(|w| (w - v).norm() <= epsilon) {
+                        vertices.push(v);
+                    }
+                }
+            }
+        }
+        if vertices.len() < 4 {
+            bail!("ConvexPolyhedron: the planes do not enclose a solid.");
+        }
+        ConvexPolyhedron::with_vertices(normals, offsets, vertices, epsilon)
+    }
+
+    /// Drops the faces through less than three of the vertices, which are redundant, and the
+    /// vertices on less than three of the faces, which are no corners.
+    fn with_vertices(
+        normals: Vec<glm::Vec3>,
+        offsets: Vec<f32>,
+        vertices: Vec<glm::Vec3>,
+        epsilon: f32,
+    ) -> Result<Box<dyn Primitive>> {
+        let touches = |n: &glm::Vec3, d: f32, v: &glm::Vec3| (n.dot(v) - d).abs() <= epsilon;
+        let (normals, offsets): (Vec<_>, Vec<_>) = normals
+            .into_iter()
+            .zip(offsets)
+            .filter(|(n, d)| vertices.iter().filter(|v| touches(n, *d, v)).count() >= 3)
+            .unzip();
+        let vertices = vertices
+            .into_iter()
+            .filter(|v| {
+                let faces = normals.iter().zip(&offsets);
+                faces.filter(|(n, d)| touches(n, **d, v)).count() >= 3
+            })
+            .collect();
+        Ok(Box::new(ConvexPolyhedron {
+            normals,
+            offsets,
+            vertices,
+        }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for ConvexPolyhedron {
+    fn sdf(&self, p: &Expr) -> Expr {
+        let normals = self.normals.iter().map(|n| Value::Vec3(*n)).collect();
+        let offsets = self.offsets.iter().map(|d| Value::Float(*d)).collect();
+        p.bind("p", |p| {
+            let first = p.dot(self.normals[0]) - self.offsets[0];
+            let rest = [normals, offsets].map(|values: Vec<Value>| values[1..].to_vec());
+            fold(first, rest, |distance, [normal, offset]| {
+                distance.max(p.dot(normal) - offset)
+            })
+        })
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        let distance = |i: &usize| self.normals[*i].dot(&p) - self.offsets[*i];
+        let closest = (0..self.normals.len())
+            .max_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap();
+        self.normals[closest]
+    }
+    fn bounds(&self) -> BoundingBox {
+        self.vertices.iter().fold(
+            BoundingBox::new(self.vertices[0], self.vertices[0]),
+            |bounds, v| bounds.union(&BoundingBox::new(*v, *v)),
+        )
+    }
+    fn description(&self) -> Description {
+        Description::new("ConvexPolyhedron").parameter("vertices", &self.vertices[..])
+    }
+    fn validate(&self) -> Result<()> {
+        if self.vertices.is_empty() {
+            bail!("ConvexPolyhedron: the vertices must not be empty.");
+        }
+        ConvexPolyhedron::from_planes(self.normals.clone(), self.offsets.clone()).map(|_| ())
+    }
+}
+
+/// Returns the distance below which points count as the same, relative to the size of the
+/// polyhedron.
+fn tolerance(sizes: impl Iterator<Item = f32>) -> f32 {
+    sizes.fold(1., f32::max) * 1e-5
+}
+
+/// Builds the largest distance of `p` to the planes with the given normals and `radius` away
+/// from the origin. This is exact inside the enclosed polyhedron and a bound outside.
+fn planes(p: &Expr, normals: &[glm::Vec3], radius: f32) -> Expr {
+    normals
+        .iter()
+        .map(|n| p.dot(n.normalize()))
+        .reduce(|a, b| a.max(b))
+        .unwrap()
+        - radius
+}
+
+/// Builds the distance to the prism of `height` along z, given the distance `d` to its cross
+/// section.
+fn extrude(d: &Expr, p: &Expr, height: f32) -> Expr {
+    vec2(d, p.z().abs() - height * 0.5).bind("w", |w| w.x().max(w.y()).min(0.) + w.max(0.).length())
+}
+
+fn symmetric_bounds(extent: f32) -> BoundingBox {
+    let e = glm::vec3(extent, extent, extent);
+    BoundingBox::new(-e, e)
+}
@@ -1,7 +1,9 @@
-use super::{shader_vec3, Primitive};
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::Expr;
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Repeat {
     primitive: Box<dyn Primitive>,
     bounds: glm::Vec3,
@@ -16,22 +18,39 @@ impl Repeat {
         repeats_min: glm::I32Vec3,
         repeats_max: glm::I32Vec3,
     ) -> Result<Box<Repeat>> {
-        if bounds.min() <= 0.0 {
-            bail!("bound must be larger 0 (was {}).", bounds);
+        let repeat = Repeat {
+            primitive,
+            bounds,
+            repeats_min: repeats_min.cast::<f32>(),
+            repeats_max: repeats_max.cast::<f32>(),
+        };
+        repeat.check()?;
+        Ok(Box::new(repeat))
+    }
+    fn check(&self) -> Result<()> {
+        if self.bounds.min() <= 0.0 {
+            bail!("bound must be larger 0 (was {}).", self.bounds);
         }
-        if (repeats_max - repeats_min).min() < 0 {
+        if (self.repeats_max - self.repeats_min).min() < 0. {
             bail!(
                 "repeats range must non-negative (was {:?} - {:?}).",
-                repeats_min,
-                repeats_max
+                self.repeats_min,
+                self.repeats_max
             );
         }
-        Ok(Box::new(Repeat {
-            primitive,
-            bounds,
-            repeats_min: repeats_min.cast::<f32>(),
-            repeats_max: repeats_max.cast::<f32>(),
-        }))
+        Ok(())
+    }
+}
+
+impl Repeat {
+    /// Returns the repetition closest to `p`.
+    fn repetition(&self, p: glm::Vec3) -> glm::Vec3 {
+        let rp = v3_round(p.component_div(&self.bounds));
+        v3_clamp(rp, self.repeats_min, self.repeats_max)
+    }
+    /// Moves `p` into the cell of the closest repetition.
+    fn local(&self, p: glm::Vec3) -> glm::Vec3 {
+        p - self.bounds.component_mul(&self.repetition(p))
     }
 }
 
@@ -47,23 +66,50 @@ fn v3_clamp(v: glm::Vec3, min: glm::Vec3, max: glm::Vec3) -> glm::Vec3 {
     )
 }
 
+#[typetag::serde]
 impl Primitive for Repeat {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        self.primitive.expression(
-            &format!(
-                "{p} - {bounds} * clamp(round({p} / {bounds}), {rep_min}, {rep_max})",
-                p = p,
-                bounds = shader_vec3(&self.bounds),
-                rep_min = shader_vec3(&(self.repeats_min)),
-                rep_max = shader_vec3(&(self.repeats_max))
-            ),
-            shared_code,
+    fn sdf(&self, p: &Expr) -> Expr {
+        p.bind("p", |p| {
+            let repetition = (p / self.bounds)
+                .round()
+                .clamp(self.repeats_min, self.repeats_max);
+            self.primitive.sdf(&(p - repetition * self.bounds))
+        })
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        self.primitive.gradient(self.local(p), epsilon)
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        // The repetition only grows along each axis, so the farthest ones bound the offsets.
+        let first = self.bounds.component_mul(&self.repetition(region.min));
+        let last = self.bounds.component_mul(&self.repetition(region.max));
+        self.primitive
+            .eval_interval(&BoundingBox::new(region.min - last, region.max - first))
+    }
+    fn bounds(&self) -> BoundingBox {
+        let bounds = self.primitive.bounds();
+        BoundingBox::new(
+            bounds.min + self.bounds.component_mul(&self.repeats_min),
+            bounds.max + self.bounds.component_mul(&self.repeats_max),
         )
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        let rp = v3_round(p).component_div(&self.bounds);
-        let rp = v3_clamp(rp, self.repeats_min, self.repeats_max);
-        let p = p - self.bounds.component_mul(&rp);
-        self.primitive.eval(p)
+    fn simplify(&self) -> Box<dyn Primitive> {
+        Box::new(Repeat {
+            primitive: self.primitive.simplify(),
+            bounds: self.bounds,
+            repeats_min: self.repeats_min,
+            repeats_max: self.repeats_max,
+        })
+    }
+    fn description(&self) -> Description {
+        Description::new("Repeat")
+            .parameter("bounds", self.bounds)
+            .parameter("repeats_min", self.repeats_min.map(|r| r as i32))
+            .parameter("repeats_max", self.repeats_max.map(|r| r as i32))
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()?;
+        self.check()
     }
 }
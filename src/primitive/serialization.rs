@@ -0,0 +1,29 @@
+//! Stores primitive trees as text, so they can be meshed or shown without running the script.
+
+use super::Primitive;
+use anyhow::Result;
+
+pub fn to_json(primitive: &dyn Primitive) -> Result<String> {
+    Ok(serde_json::to_string_pretty(primitive)?)
+}
+
+/// Loads a primitive tree, which has to pass the checks of the constructors.
+pub fn from_json(json: &str) -> Result<Box<dyn Primitive>> {
+    let primitive: Box<dyn Primitive> = serde_json::from_str(json)?;
+    primitive.validate()?;
+    Ok(primitive)
+}
+
+pub fn to_ron(primitive: &dyn Primitive) -> Result<String> {
+    Ok(ron::ser::to_string_pretty(
+        primitive,
+        ron::ser::PrettyConfig::default(),
+    )?)
+}
+
+/// Loads a primitive tree, which has to pass the checks of the constructors.
+pub fn from_ron(ron: &str) -> Result<Box<dyn Primitive>> {
+    let primitive: Box<dyn Primitive> = ron::from_str(ron)?;
+    primitive.validate()?;
+    Ok(primitive)
+}
@@ -1,7 +1,10 @@
-use super::Primitive;
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::{Expr, Function, Type};
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Sphere {
     radius: f32,
 }
@@ -15,11 +18,112 @@ impl Sphere {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Sphere {
-    fn expression(&self, p: &str, _shared_code: &mut Vec<String>) -> Result<String> {
-        Ok(format!("length({}) - {:.8}", p, self.radius))
+    fn sdf(&self, p: &Expr) -> Expr {
+        p.length() - self.radius
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        p.norm() - self.radius
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        p.try_normalize(0.).unwrap_or_default()
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        region.norm() - Interval::point(self.radius)
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        let r = glm::vec3(self.radius, self.radius, self.radius);
+        BoundingBox::new(-r, r)
+    }
+    fn description(&self) -> Description {
+        Description::new("Sphere").parameter("radius", self.radius)
+    }
+    fn validate(&self) -> Result<()> {
+        Sphere::new(self.radius).map(|_| ())
+    }
+}
+
+/// An ellipsoid with the given semi-axes along x, y and z.
+///
+/// The exact distance has no closed form, so the distance is the largest of three bounds: the
+/// distance to the bounding box, to the bounding sphere, and that of a unit sphere scaled to the
+/// ellipsoid and shrunk by the smallest semi-axis. Each neither overestimates the distance nor
+/// changes faster than it, so neither does their maximum.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Ellipsoid {
+    radii: glm::Vec3,
+}
+
+impl Ellipsoid {
+    pub fn new(radii: glm::Vec3) -> Result<Box<dyn Primitive>> {
+        if radii.min() <= 0. {
+            bail!("all radii should be positive (was {}).", radii);
+        }
+        Ok(Box::new(Ellipsoid { radii }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Ellipsoid {
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static ELLIPSOID: Rc<Function> = Function::new(
+                "Ellipsoid",
+                [
+                    ("p", Type::Vec3),
+                    ("r", Type::Vec3),
+                    ("rmin", Type::Float),
+                    ("rmax", Type::Float),
+                ],
+                |[p, r, rmin, rmax]| {
+                    let scaled = ((&p / &r).length() - 1.) * rmin;
+                    let sphere = p.length() - rmax;
+                    (p.abs() - r).bind("q", |q| {
+                        let cube = q.x().max(q.y()).max(q.z()).min(0.) + q.max(0.).length();
+                        cube.max(scaled).max(sphere)
+                    })
+                },
+            );
+        }
+        let ellipsoid = ELLIPSOID.with(Rc::clone);
+        ellipsoid.call([
+            p.clone(),
+            self.radii.into(),
+            self.radii.min().into(),
+            self.radii.max().into(),
+        ])
+    }
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        // The gradient of whichever of the three bounds is the largest.
+        let r = self.radii;
+        let scaled = p.component_div(&r);
+        let scaled_distance = (scaled.norm() - 1.) * r.min();
+        let sphere_distance = p.norm() - r.max();
+        let q = p.abs() - r;
+        let cube_distance = q.max().min(0.) + q.sup(&glm::Vec3::zeros()).norm();
+        if cube_distance >= scaled_distance.max(sphere_distance) {
+            let g = if q.max() > 0. {
+                q.sup(&glm::Vec3::zeros()).normalize()
+            } else {
+                // Inside, towards the closest face.
+                glm::Vec3::ith(q.imax(), 1.)
+            };
+            // q is mirrored into the positive octant.
+            g.zip_map(&p, |g, p| if p < 0. { -g } else { g })
+        } else if sphere_distance >= scaled_distance {
+            p.try_normalize(0.).unwrap_or_default()
+        } else {
+            scaled.component_div(&r) * (r.min() / scaled.norm())
+        }
+    }
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::new(-self.radii, self.radii)
+    }
+    fn description(&self) -> Description {
+        Description::new("Ellipsoid").parameter("radii", self.radii)
+    }
+    fn validate(&self) -> Result<()> {
+        Ellipsoid::new(self.radii).map(|_| ())
     }
 }
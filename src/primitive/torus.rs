@@ -1,8 +1,11 @@
-use super::Primitive;
+use super::{distance_bound_interval, BoundingBox, Description, Interval, Primitive};
+use crate::ir::{select, vec2, vec3, Expr, Function, Type};
 use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
+use std::rc::Rc;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Torus {
     inner: f32,
     outer: f32,
@@ -38,67 +41,106 @@ impl Torus {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Torus {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
+    fn sdf(&self, p: &Expr) -> Expr {
+        let ra = (self.inner + self.outer) / 2.0;
+        let rb = (self.outer - self.inner) / 2.0;
         match self.cap_angle {
-            None => {
-                shared_code.push(
-                    r#"
-float Torus(vec3 p, vec2 t) {
-    vec2 q = vec2(length(p.xy) - t.x, p.z);
-    return length(q) - t.y;
-}"#
-                    .to_string(),
-                );
-                Ok(format!(
-                    "Torus({}, vec2({:.8}, {:.8}))",
-                    p,
-                    (self.inner + self.outer) / 2.0,
-                    (self.outer - self.inner) / 2.0
-                ))
-            }
+            None => p.bind("p", |p| vec2(p.xy().length() - ra, p.z()).length() - rb),
             Some(a) => {
-                shared_code.push(
-                    r#"
-float CappedTorus(vec3 p, float ra, float rb, vec2 an) {
-    p.x = abs(p.x);
-    float k = (an.y * p.x > an.x * p.y) ? dot(p.xy, an) : length(p.xy);
-    return sqrt( dot(p, p) + ra * ra - 2.0 * ra * k) - rb;
-}
-"#
-                    .to_string(),
-                );
-                Ok(format!(
-                    "CappedTorus({}, {:.8}, {:.8}, vec2({:.8}, {:.8}))",
-                    p,
-                    (self.inner + self.outer) / 2.0,
-                    (self.outer - self.inner) / 2.0,
-                    a.sin(),
-                    a.cos()
-                ))
+                thread_local! {
+                    static CAPPED_TORUS: Rc<Function> = Function::new(
+                        "CappedTorus",
+                        [
+                            ("p", Type::Vec3),
+                            ("ra", Type::Float),
+                            ("rb", Type::Float),
+                            ("an", Type::Vec2),
+                        ],
+                        |[p, ra, rb, an]| {
+                            vec3(p.x().abs(), p.y(), p.z()).bind("q", |q| {
+                                let k = select(
+                                    (an.y() * q.x()).gt(an.x() * q.y()),
+                                    q.xy().dot(&an),
+                                    q.xy().length(),
+                                );
+                                (q.dot(q) + &ra * &ra - 2. * &ra * k).sqrt() - rb
+                            })
+                        },
+                    );
+                }
+                let capped_torus = CAPPED_TORUS.with(Rc::clone);
+                capped_torus.call([
+                    p.clone(),
+                    ra.into(),
+                    rb.into(),
+                    glm::vec2(a.sin(), a.cos()).into(),
+                ])
             }
         }
     }
-    fn eval(&self, mut p: glm::Vec3) -> f32 {
-        match self.cap_angle {
+    fn gradient(&self, p: glm::Vec3, _epsilon: f32) -> glm::Vec3 {
+        let ra = (self.inner + self.outer) / 2.0;
+        let g = match self.cap_angle {
             None => {
-                let t0 = (self.inner + self.outer) / 2.0;
-                let t1 = (self.outer - self.inner) / 2.0;
-                let q = glm::vec2(p.rows(0, 2).norm() - t0, p[2]);
-                q.norm() - t1
+                // Away from the closest point on the center circle.
+                let xy = glm::vec2(p[0], p[1]);
+                let radial = xy.try_normalize(0.).unwrap_or_default();
+                let q = glm::vec2(xy.norm() - ra, p[2]);
+                glm::vec3(radial[0] * q[0], radial[1] * q[0], q[1])
             }
             Some(a) => {
-                let ra = (self.inner + self.outer) / 2.0;
-                let rb = (self.outer - self.inner) / 2.0;
                 let an = glm::vec2(a.sin(), a.cos());
-                p[0] = p[0].abs();
-                let k = if an[1] * p[0] > an[0] * p[1] {
-                    p.rows(0, 2).dot(&an)
+                let x = p[0].abs();
+                // Away from the closest point on the capped center arc.
+                let closest = if an[1] * x > an[0] * p[1] {
+                    an * ra
                 } else {
-                    p.rows(0, 2).norm()
+                    glm::vec2(x, p[1]).try_normalize(0.).unwrap_or_default() * ra
                 };
-                (p.norm_squared() + ra * ra - 2.0 * ra * k).sqrt() - rb
+                glm::vec3(x - closest[0], p[1] - closest[1], p[2])
             }
+        };
+        let g = g.try_normalize(0.).unwrap_or_default();
+        match self.cap_angle {
+            None => g,
+            Some(_) => glm::vec3(g[0] * 1_f32.copysign(p[0]), g[1], g[2]),
         }
     }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        let ra = (self.inner + self.outer) / 2.0;
+        let rb = (self.outer - self.inner) / 2.0;
+        let q_xy = region.norm_xy() - Interval::point(ra);
+        let full = (q_xy.square() + region.axis(2).square()).sqrt() - Interval::point(rb);
+        match self.cap_angle {
+            None => full,
+            Some(_) => {
+                // The capped torus is never closer than the full one.
+                let capped = distance_bound_interval(self.eval(region.center()), region);
+                Interval::new(capped.min.max(full.min), capped.max)
+            }
+        }
+    }
+    fn is_exact(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> BoundingBox {
+        // The capped torus is contained in the full one.
+        let tube = (self.outer - self.inner) / 2.0;
+        let r = glm::vec3(self.outer, self.outer, tube);
+        BoundingBox::new(-r, r)
+    }
+    fn description(&self) -> Description {
+        let description = Description::new("Torus")
+            .parameter("inner", self.inner)
+            .parameter("outer", self.outer);
+        match self.cap_angle {
+            Some(cap_angle) => description.parameter("cap_angle", cap_angle),
+            None => description,
+        }
+    }
+    fn validate(&self) -> Result<()> {
+        Torus::new_impl(self.inner, self.outer, self.cap_angle).map(|_| ())
+    }
 }
@@ -1,7 +1,14 @@
-use super::{shader_mat3, shader_vec3, Primitive};
-use anyhow::Result;
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::Expr;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::f32::consts::PI;
 
-#[derive(Clone)]
+/// Tolerance for recognizing identities when composing transformations.
+const EPSILON: f32 = 1e-6;
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Translate {
     primitive: Box<dyn Primitive>,
     vector: glm::Vec3,
@@ -13,25 +20,46 @@ impl Translate {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Translate {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        self.primitive.expression(
-            &format!("({}) - {}", p, shader_vec3(&self.vector)),
-            shared_code,
-        )
+    fn sdf(&self, p: &Expr) -> Expr {
+        self.primitive.sdf(&(p - self.vector))
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        self.primitive.gradient(p - self.vector, epsilon)
+    }
+    fn is_exact(&self) -> bool {
+        self.primitive.is_exact()
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        self.primitive
+            .eval_interval(&region.translate(&-self.vector))
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        self.primitive.eval(p - self.vector)
+    fn bounds(&self) -> BoundingBox {
+        self.primitive.bounds().translate(&self.vector)
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        simplify_chain(self)
+    }
+    fn description(&self) -> Description {
+        Description::new("Translate")
+            .parameter("vector", self.vector)
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Rotate {
     primitive: Box<dyn Primitive>,
     matrix: glm::Mat3x3,
 }
 
 impl Rotate {
+    /// Evaluates the child at `Rx(p) * Ry(y) * Rz(r) * point`, which turns the object by the
+    /// inverse of that rotation. [`Rotate::from_axis_angle`] is easier to reason about.
     pub fn from_euler(primitive: Box<dyn Primitive>, r: f32, p: f32, y: f32) -> Box<Rotate> {
         let mat = glm::identity::<f32, 4>();
         let mat = glm::rotate_x(&mat, p);
@@ -42,21 +70,86 @@ impl Rotate {
             matrix: mat.fixed_view::<3, 3>(0, 0).into(),
         })
     }
+    /// Turns the object by `angle` radians around `axis`, counterclockwise when looking
+    /// against the axis.
+    pub fn from_axis_angle(
+        primitive: Box<dyn Primitive>,
+        axis: glm::Vec3,
+        angle: f32,
+    ) -> Result<Box<Rotate>> {
+        if axis.norm() == 0. {
+            bail!("rotate_axis: the axis must not be zero.");
+        }
+        Rotate::from_quaternion(primitive, glm::quat_angle_axis(angle, &axis))
+    }
+    /// Turns the object by the rotation `q`, which gets normalized.
+    pub fn from_quaternion(primitive: Box<dyn Primitive>, q: glm::Quat) -> Result<Box<Rotate>> {
+        if q.norm() == 0. {
+            bail!("rotate: the quaternion must not be zero.");
+        }
+        // The child is evaluated at the inversely rotated point.
+        let matrix = glm::quat_to_mat3(&q.normalize()).transpose();
+        Ok(Box::new(Rotate { primitive, matrix }))
+    }
+    /// Turns the object by the smallest rotation taking the direction `from` onto `to`.
+    pub fn align(
+        primitive: Box<dyn Primitive>,
+        from: glm::Vec3,
+        to: glm::Vec3,
+    ) -> Result<Box<Rotate>> {
+        if from.norm() == 0. || to.norm() == 0. {
+            bail!("align: the directions must not be zero.");
+        }
+        let (from, to) = (from.normalize(), to.normalize());
+        if from.cross(&to).norm() < EPSILON && from.dot(&to) < 0. {
+            // Opposite directions, any axis perpendicular to them works.
+            let other = if from[0].abs() < 0.9 {
+                glm::vec3(1., 0., 0.)
+            } else {
+                glm::vec3(0., 1., 0.)
+            };
+            return Rotate::from_axis_angle(primitive, from.cross(&other), PI);
+        }
+        Rotate::from_quaternion(primitive, glm::quat_rotation(&from, &to))
+    }
 }
 
+#[typetag::serde]
 impl Primitive for Rotate {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        self.primitive.expression(
-            &format!("{} * ({})", shader_mat3(&self.matrix), p),
-            shared_code,
-        )
+    fn sdf(&self, p: &Expr) -> Expr {
+        self.primitive.sdf(&(Expr::from(self.matrix) * p))
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        self.matrix.transpose() * self.primitive.gradient(self.matrix * p, epsilon)
+    }
+    fn is_exact(&self) -> bool {
+        self.primitive.is_exact()
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        self.primitive
+            .eval_interval(&region.transform(&self.matrix))
+    }
+    fn bounds(&self) -> BoundingBox {
+        // The child is evaluated at matrix * p, so it gets rotated by the inverse.
+        self.primitive.bounds().transform(&self.matrix.transpose())
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        simplify_chain(self)
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        self.primitive.eval(self.matrix * p)
+    fn description(&self) -> Description {
+        // The child is evaluated at the inversely rotated point.
+        let q = glm::mat3_to_quat(&self.matrix.transpose());
+        Description::new("Rotate")
+            .parameter("axis", glm::quat_axis(&q))
+            .parameter("angle", glm::quat_angle(&q))
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Scale {
     primitive: Box<dyn Primitive>,
     scale: glm::Vec3,
@@ -68,19 +161,249 @@ impl Scale {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Scale {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        let d = self.primitive.expression(
-            &format!(
-                "({}) * {}",
-                p,
-                shader_vec3(&(glm::vec3(1., 1., 1.).component_div(&self.scale)))
-            ),
-            shared_code,
-        )?;
-        Ok(format!("({}) * {:.8}", d, self.scale.abs().min()))
-    }
-    fn eval(&self, p: glm::Vec3) -> f32 {
-        self.primitive.eval(p.component_mul(&self.scale))
+    fn sdf(&self, p: &Expr) -> Expr {
+        let inverse = glm::vec3(1., 1., 1.).component_div(&self.scale);
+        self.primitive.sdf(&(p * inverse)) * self.scale.abs().min()
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        self.primitive
+            .gradient(p.component_div(&self.scale), epsilon)
+            .component_div(&self.scale)
+            * self.scale.abs().min()
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        let inverse = glm::vec3(1., 1., 1.).component_div(&self.scale);
+        self.primitive.eval_interval(&region.scale(&inverse)) * self.scale.abs().min()
+    }
+    fn is_exact(&self) -> bool {
+        // Uneven scales stretch distances differently along each axis.
+        self.scale.abs().min() == self.scale.abs().max() && self.primitive.is_exact()
+    }
+    fn bounds(&self) -> BoundingBox {
+        self.primitive.bounds().scale(&self.scale)
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        simplify_chain(self)
+    }
+    fn description(&self) -> Description {
+        Description::new("Scale")
+            .parameter("scale", self.scale)
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()
+    }
+}
+
+/// Evaluates the child at `matrix * p + offset` and multiplies the distance by `factor`. Chains
+/// of translations, rotations and scales compose into one such node.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Transform {
+    primitive: Box<dyn Primitive>,
+    matrix: glm::Mat3x3,
+    offset: glm::Vec3,
+    factor: f32,
+}
+
+impl Transform {
+    /// Places `primitive` by `pose`, an affine map from the coordinates of the primitive to
+    /// world coordinates. The map may shear or scale unevenly, so the last row has to be
+    /// (0, 0, 0, 1) and the rest must be invertible.
+    pub fn new(primitive: Box<dyn Primitive>, pose: glm::Mat4) -> Result<Box<Transform>> {
+        if pose.row(3) != glm::vec4(0., 0., 0., 1.).transpose() {
+            let r = pose.row(3);
+            bail!(
+                "transform: the last row must be (0, 0, 0, 1) (was ({}, {}, {}, {})).",
+                r[0],
+                r[1],
+                r[2],
+                r[3]
+            );
+        }
+        let linear: glm::Mat3x3 = pose.fixed_view::<3, 3>(0, 0).into();
+        let translation: glm::Vec3 = pose.fixed_view::<3, 1>(0, 3).into();
+        let Some(matrix) = linear.try_inverse() else {
+            bail!("transform: the matrix must be invertible.");
+        };
+        // The map shrinks distances at most by its smallest singular value.
+        let factor = linear.svd(false, false).singular_values.min();
+        Ok(Box::new(Transform {
+            primitive,
+            matrix,
+            offset: matrix * -translation,
+            factor,
+        }))
+    }
+}
+
+#[typetag::serde]
+impl Primitive for Transform {
+    fn sdf(&self, p: &Expr) -> Expr {
+        let mut q = Expr::from(self.matrix) * p;
+        if self.offset != glm::Vec3::zeros() {
+            q = q + self.offset;
+        }
+        let d = self.primitive.sdf(&q);
+        if self.factor == 1. {
+            d
+        } else {
+            d * self.factor
+        }
+    }
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
+        self.matrix.transpose()
+            * self
+                .primitive
+                .gradient(self.matrix * p + self.offset, epsilon)
+            * self.factor
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        let region = region.transform(&self.matrix).translate(&self.offset);
+        self.primitive.eval_interval(&region) * self.factor
+    }
+    fn is_exact(&self) -> bool {
+        // Only maps scaling evenly in all directions keep distances exact, and only if the
+        // distances of the child get scaled back by the same amount.
+        let g = self.matrix.transpose() * self.matrix;
+        let even = (g - glm::Mat3x3::identity() * g[(0, 0)]).amax() <= EPSILON * g[(0, 0)];
+        let rescaled = (self.factor - 1. / g[(0, 0)].sqrt()).abs() < EPSILON;
+        even && rescaled && self.primitive.is_exact()
+    }
+    fn bounds(&self) -> BoundingBox {
+        match self.matrix.try_inverse() {
+            Some(inverse) => self
+                .primitive
+                .bounds()
+                .translate(&-self.offset)
+                .transform(&inverse),
+            None => BoundingBox::infinite(),
+        }
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        simplify_chain(self)
+    }
+    fn description(&self) -> Description {
+        // Undoes the inversion of the constructor.
+        let linear = self.matrix.try_inverse().unwrap_or_else(glm::Mat3x3::zeros);
+        let mut pose = glm::translation(&(linear * -self.offset));
+        pose.fixed_view_mut::<3, 3>(0, 0).copy_from(&linear);
+        Description::new("Transform")
+            .parameter("pose", pose)
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()
+    }
+}
+
+/// What a transformation does to the evaluated point and the distance.
+#[derive(Clone, Copy)]
+struct Affine {
+    matrix: glm::Mat3x3,
+    offset: glm::Vec3,
+    factor: f32,
+}
+
+impl Affine {
+    /// Returns the transformation of `p` and its child, if `p` is a transformation.
+    fn of(p: &dyn Primitive) -> Option<(Affine, &dyn Primitive)> {
+        let any = p as &dyn Any;
+        let identity = glm::Mat3x3::identity();
+        if let Some(t) = any.downcast_ref::<Translate>() {
+            let affine = Affine {
+                matrix: identity,
+                offset: -t.vector,
+                factor: 1.,
+            };
+            return Some((affine, &*t.primitive));
+        }
+        if let Some(r) = any.downcast_ref::<Rotate>() {
+            let affine = Affine {
+                matrix: r.matrix,
+                offset: glm::Vec3::zeros(),
+                factor: 1.,
+            };
+            return Some((affine, &*r.primitive));
+        }
+        if let Some(s) = any.downcast_ref::<Scale>() {
+            let inverse = glm::vec3(1., 1., 1.).component_div(&s.scale);
+            let affine = Affine {
+                matrix: glm::Mat3x3::from_diagonal(&inverse),
+                offset: glm::Vec3::zeros(),
+                factor: s.scale.abs().min(),
+            };
+            return Some((affine, &*s.primitive));
+        }
+        let t = any.downcast_ref::<Transform>()?;
+        let affine = Affine {
+            matrix: t.matrix,
+            offset: t.offset,
+            factor: t.factor,
+        };
+        Some((affine, &*t.primitive))
+    }
+
+    /// Returns the transformation applying `self` and then `inner`, which belongs to the child.
+    fn then(&self, inner: &Affine) -> Affine {
+        Affine {
+            matrix: inner.matrix * self.matrix,
+            offset: inner.matrix * self.offset + inner.offset,
+            factor: self.factor * inner.factor,
+        }
+    }
+
+    /// Returns the simplest node applying the transformation to `primitive`.
+    fn apply(&self, primitive: Box<dyn Primitive>) -> Box<dyn Primitive> {
+        let is_identity = |m: glm::Mat3x3| (m - glm::Mat3x3::identity()).abs().max() < EPSILON;
+        let no_offset = self.offset.abs().max() < EPSILON;
+        let unit = (self.factor - 1.).abs() < EPSILON;
+        if is_identity(self.matrix) && unit {
+            return if no_offset {
+                primitive
+            } else {
+                Translate::new(primitive, -self.offset)
+            };
+        }
+        // Orthogonal matrices keep distances.
+        if no_offset && unit && is_identity(self.matrix.transpose() * self.matrix) {
+            return Box::new(Rotate {
+                primitive,
+                matrix: self.matrix,
+            });
+        }
+        let diagonal = self.matrix.diagonal();
+        let scale = glm::vec3(1., 1., 1.).component_div(&diagonal);
+        if no_offset
+            && (self.matrix - glm::Mat3x3::from_diagonal(&diagonal))
+                .abs()
+                .max()
+                < EPSILON
+            && (self.factor - scale.abs().min()).abs() < EPSILON
+        {
+            return Scale::new(primitive, scale);
+        }
+        Box::new(Transform {
+            primitive,
+            matrix: self.matrix,
+            offset: self.offset,
+            factor: self.factor,
+        })
+    }
+}
+
+/// Composes the transformation `p` with the ones directly below it.
+fn simplify_chain(p: &dyn Primitive) -> Box<dyn Primitive> {
+    let (mut affine, mut child) = Affine::of(p).expect("simplify_chain called on a transformation");
+    while let Some((inner, grandchild)) = Affine::of(child) {
+        affine = affine.then(&inner);
+        child = grandchild;
+    }
+    // Simplifying the child may reveal another transformation, like one below a double negation.
+    let child = child.simplify();
+    match Affine::of(&*child) {
+        Some((inner, grandchild)) => affine.then(&inner).apply(grandchild.clone_box()),
+        None => affine.apply(child),
     }
 }
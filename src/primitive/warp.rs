@@ -1,8 +1,11 @@
-use super::Primitive;
+use super::{BoundingBox, Description, Interval, Primitive};
+use crate::ir::{mat2, vec3, Expr, Function, Type};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
+use std::rc::Rc;
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Twist {
     primitive: Box<dyn Primitive>,
     height_per_rotation: f32,
@@ -17,37 +20,87 @@ impl Twist {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Twist {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        shared_code.push(
-            r#"
-vec3 TwistXY(vec3 p, float rad_per_h) {
-    float a = p.z * rad_per_h;
-    float sin_a = sin(a);
-    float cos_a = cos(a);
-    mat2 rmat = mat2(cos_a, -sin_a, sin_a, cos_a);
-    return vec3(rmat * p.xy, p.z);
-}
-"#
-            .to_string(),
-        );
-        self.primitive.expression(
-            &format!("TwistXY({}, {:.8})", p, 2. * PI / self.height_per_rotation),
-            shared_code,
-        )
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static TWIST: Rc<Function> = Function::new(
+                "TwistXY",
+                [("p", Type::Vec3), ("rad_per_h", Type::Float)],
+                |[p, rad_per_h]| {
+                    (p.z() * rad_per_h).bind("a", |a| {
+                        a.sin().bind("sin_a", |sin_a| {
+                            a.cos().bind("cos_a", |cos_a| {
+                                let rmat = mat2(cos_a, -sin_a, sin_a, cos_a);
+                                (rmat * p.xy()).bind("q", |q| vec3(q.x(), q.y(), p.z()))
+                            })
+                        })
+                    })
+                },
+            );
+        }
+        let twist = TWIST.with(Rc::clone);
+        let rad_per_h = 2. * PI / self.height_per_rotation;
+        self.primitive
+            .sdf(&twist.call([p.clone(), rad_per_h.into()]))
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
         let rad_per_h = 2. * PI / self.height_per_rotation;
         let a = p[2] * rad_per_h;
         let sin_a = a.sin();
         let cos_a = a.cos();
-        let rmat = glm::mat2x2(cos_a, -sin_a, sin_a, cos_a);
+        let rmat = glm::mat2x2(cos_a, sin_a, -sin_a, cos_a);
         let r_xy = rmat * p.rows(0, 2);
-        self.primitive.eval(glm::vec3(r_xy[0], r_xy[1], p[2]))
+        let g = self
+            .primitive
+            .gradient(glm::vec3(r_xy[0], r_xy[1], p[2]), epsilon);
+        let g_xy = g.rows(0, 2);
+        // Chain rule: the rotation also changes with z.
+        let drmat = glm::mat2x2(-sin_a, cos_a, -cos_a, -sin_a) * rad_per_h;
+        let g_xy_p = rmat.transpose() * g_xy;
+        glm::vec3(
+            g_xy_p[0],
+            g_xy_p[1],
+            g[2] + (drmat * p.rows(0, 2)).dot(&g_xy),
+        )
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
+        let a = region.axis(2) * (2. * PI / self.height_per_rotation);
+        let (sin_a, cos_a) = (a.sin(), a.cos());
+        let (x, y) = (region.axis(0), region.axis(1));
+        // Rotating never moves points farther from the z axis.
+        let r = region.radius_xy();
+        let clamp = |i: Interval| Interval::new(i.min.max(-r), i.max.min(r));
+        let q_x = clamp(cos_a * x + sin_a * y);
+        let q_y = clamp(cos_a * y - sin_a * x);
+        self.primitive.eval_interval(&BoundingBox::new(
+            glm::vec3(q_x.min, q_y.min, region.min[2]),
+            glm::vec3(q_x.max, q_y.max, region.max[2]),
+        ))
+    }
+    fn bounds(&self) -> BoundingBox {
+        // Twisting only rotates around the z axis.
+        let bounds = self.primitive.bounds();
+        let r = bounds.radius_xy();
+        BoundingBox::new(
+            glm::vec3(-r, -r, bounds.min[2]),
+            glm::vec3(r, r, bounds.max[2]),
+        )
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        Twist::new(self.primitive.simplify(), self.height_per_rotation)
+    }
+    fn description(&self) -> Description {
+        Description::new("Twist")
+            .parameter("height_per_rotation", self.height_per_rotation)
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Bend {
     primitive: Box<dyn Primitive>,
     distance_for_full_circle: f32,
@@ -62,31 +115,81 @@ impl Bend {
     }
 }
 
+#[typetag::serde]
 impl Primitive for Bend {
-    fn expression(&self, p: &str, shared_code: &mut Vec<String>) -> Result<String> {
-        shared_code.push(
-            r#"
-vec3 BendAroundZ(vec3 p, float y_scale) {
-    float a = atan(p.x, p.y);
-    float r = length(p.xy);
-    return vec3(a * y_scale, r, p.z);
-}
-"#
-            .to_string(),
-        );
-        self.primitive.expression(
-            &format!(
-                "BendAroundZ({}, {:.8})",
-                p,
-                self.distance_for_full_circle * 0.5 / PI
-            ),
-            shared_code,
-        )
+    fn sdf(&self, p: &Expr) -> Expr {
+        thread_local! {
+            static BEND: Rc<Function> = Function::new(
+                "BendAroundZ",
+                [("p", Type::Vec3), ("y_scale", Type::Float)],
+                |[p, y_scale]| vec3(p.x().atan2(p.y()) * y_scale, p.xy().length(), p.z()),
+            );
+        }
+        let bend = BEND.with(Rc::clone);
+        let y_scale = self.distance_for_full_circle * 0.5 / PI;
+        self.primitive.sdf(&bend.call([p.clone(), y_scale.into()]))
     }
-    fn eval(&self, p: glm::Vec3) -> f32 {
+    fn gradient(&self, p: glm::Vec3, epsilon: f32) -> glm::Vec3 {
         let a = p[0].atan2(p[1]);
-        let r = p.rows(0, 2).norm();
+        let r2 = p[0] * p[0] + p[1] * p[1];
+        let r = r2.sqrt();
+        if r == 0. {
+            return glm::Vec3::zeros();
+        }
+        let y_scale = self.distance_for_full_circle * 0.5 / PI;
+        let g = self
+            .primitive
+            .gradient(glm::vec3(a * y_scale, r, p[2]), epsilon);
+        // Chain rule through the angle and the distance to the z axis.
+        glm::vec3(
+            g[0] * y_scale * p[1] / r2 + g[1] * p[0] / r,
+            -g[0] * y_scale * p[0] / r2 + g[1] * p[1] / r,
+            g[2],
+        )
+    }
+    fn eval_interval(&self, region: &BoundingBox) -> Interval {
         let y_scale = self.distance_for_full_circle * 0.5 / PI;
-        self.primitive.eval(glm::vec3(a * y_scale, r, p[2]))
+        let (x, y) = (region.axis(0), region.axis(1));
+        // atan2 jumps from pi to -pi along the negative y axis.
+        let a = if x.contains(0.) && y.min < 0. {
+            Interval::new(-PI, PI)
+        } else {
+            [
+                (x.min, y.min),
+                (x.min, y.max),
+                (x.max, y.min),
+                (x.max, y.max),
+            ]
+            .iter()
+            .map(|(x, y)| Interval::point(x.atan2(*y)))
+            .reduce(|a, b| a.hull(&b))
+            .unwrap()
+        };
+        let a = a * y_scale;
+        let r = region.norm_xy();
+        self.primitive.eval_interval(&BoundingBox::new(
+            glm::vec3(a.min, r.min, region.min[2]),
+            glm::vec3(a.max, r.max, region.max[2]),
+        ))
+    }
+    fn bounds(&self) -> BoundingBox {
+        // The y coordinate of the child becomes the distance to the z axis.
+        let bounds = self.primitive.bounds();
+        let r = bounds.max[1].max(0.);
+        BoundingBox::new(
+            glm::vec3(-r, -r, bounds.min[2]),
+            glm::vec3(r, r, bounds.max[2]),
+        )
+    }
+    fn simplify(&self) -> Box<dyn Primitive> {
+        Bend::new(self.primitive.simplify(), self.distance_for_full_circle)
+    }
+    fn description(&self) -> Description {
+        Description::new("Bend")
+            .parameter("distance_for_full_circle", self.distance_for_full_circle)
+            .child(self.primitive.description())
+    }
+    fn validate(&self) -> Result<()> {
+        self.primitive.validate()
     }
 }
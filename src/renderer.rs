@@ -1,19 +1,79 @@
-use super::Primitive;
+use crate::ir::{self, Type, Uniforms, Var};
+use crate::primitive::Primitive;
 use anyhow::Result;
 use array_tool::vec::Uniq;
 
+/// Name of the uniform array holding the constants of parametric shaders.
+pub const PARAMETERS: &str = "iParameters";
+
+/// Number of floats parametric shaders read from uniforms. WebGL 2 guarantees 224 uniform
+/// vectors in fragment shaders, of which the parameters take at most 192.
+const MAX_PARAMETERS: usize = 768;
+
+/// A renderer shader that reads the constants of the object from the uniform array
+/// [`PARAMETERS`]. Objects that only differ in their constants share the code.
+pub struct ParametricShader {
+    pub code: String,
+    /// Values of the uniform array, given matrix by matrix and column by column.
+    pub parameters: Vec<f32>,
+}
+
 pub fn generate_renderer_shader(obj: &dyn Primitive) -> Result<String> {
-    let main_renderer = include_str!("renderer.glsl");
     let mut static_code = Vec::new();
+    let map = obj.simplify().expression("p", &mut static_code)?;
+    Ok(renderer_shader(&static_code, "", &map))
+}
+
+pub fn generate_parametric_renderer_shader(obj: &dyn Primitive) -> Result<ParametricShader> {
+    let mut static_code = Vec::new();
+    let mut uniforms = Uniforms::new(PARAMETERS, MAX_PARAMETERS);
+    let p = Var::new("p", Type::Vec3);
+    let map = ir::glsl::expression_with_uniforms(
+        &obj.simplify().sdf(&p.expr()),
+        &[(p, "p")],
+        &mut static_code,
+        &mut uniforms,
+    );
+    Ok(ParametricShader {
+        code: renderer_shader(&static_code, &ir::glsl::declare_uniforms(&uniforms), &map),
+        parameters: uniforms.values(),
+    })
+}
+
+fn renderer_shader(static_code: &[String], uniforms: &str, map: &str) -> String {
+    let main_renderer = include_str!("renderer.glsl");
     let map = format!(
         "
 uniform mat4 iWorldTransform;
+{}
 
 float map(in vec3 p) {{
     p = (vec4(p, 1) * iWorldTransform).xyz;
     return {};
 }}",
-        obj.expression("p", &mut static_code)?
+        uniforms, map
+    );
+    let static_code = static_code
+        .to_vec()
+        .unique()
+        .iter()
+        .fold(String::new(), |mut sum, i| {
+            sum.push_str(i);
+            sum
+        });
+    format!("{}\n{}\n{}", static_code, map, main_renderer)
+}
+
+/// Returns a WGSL module defining `fn map(p: vec3f) -> f32`, the distance to `obj`,
+/// for WebGPU render or compute pipelines.
+pub fn generate_wgsl_map(obj: &dyn Primitive) -> Result<String> {
+    let mut static_code = Vec::new();
+    let map = format!(
+        "
+fn map(p: vec3f) -> f32 {{
+    return {};
+}}",
+        obj.simplify().wgsl_expression("p", &mut static_code)?
     );
     let static_code = static_code
         .unique()
@@ -22,5 +82,5 @@ float map(in vec3 p) {{
             sum.push_str(i);
             sum
         });
-    Ok(format!("{}\n{}\n{}", static_code, map, main_renderer))
+    Ok(format!("{}\n{}\n", static_code, map))
 }
@@ -1,15 +1,47 @@
+use super::mesh::{MeshSettings, Mesher, Resolution};
 use super::primitive::*;
 use anyhow::{anyhow, bail, Result};
-use rhai::{Array, Dynamic, Engine, EvalAltResult};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Module};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::rc::Rc;
 
 pub trait ScriptEngine {
     fn eval(&self, script: &str) -> Result<Box<dyn Primitive>>;
+    /// Passes on the output of `print` and `debug` in scripts.
     fn on_print(&mut self, callback: impl Fn(&str) + 'static);
+    /// Returns the mesh settings requested by the last evaluated script.
+    fn mesh_settings(&self) -> MeshSettings;
+    /// Returns the parameters declared by the last evaluated script, with the values it got.
+    fn parameters(&self) -> Vec<Parameter>;
+    /// Makes later evaluations use `value` instead of the default of the parameter `name`, until
+    /// the script changes the default or range of the parameter.
+    fn set_parameter(&mut self, name: &str, value: f32);
+}
+
+/// A tunable value declared by a script like `param("wall", 2.0, 0.5, 5.0)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub value: f32,
+    pub default: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// A value given by `set_parameter`, with the default and range of the parameter at that time.
+/// Those are unknown when set before the script declared the parameter.
+struct Override {
+    value: f32,
+    declaration: Option<[f32; 3]>,
 }
 
 pub struct RhaiScriptEngine {
     engine: Engine,
+    mesh_settings: Rc<RefCell<MeshSettings>>,
+    parameters: Rc<RefCell<Vec<Parameter>>>,
+    overrides: Rc<RefCell<HashMap<String, Override>>>,
 }
 
 impl RhaiScriptEngine {
@@ -42,6 +74,55 @@ impl RhaiScriptEngine {
             .register_fn("Vector", |x: i32, y: i32, z: i32| {
                 glm::make_vec3::<i32>(&[x, y, z])
             });
+        engine
+            .register_type_with_name::<glm::Mat4>("Matrix")
+            .register_fn(
+                "Matrix",
+                |rows: Array| -> Result<glm::Mat4, Box<EvalAltResult>> {
+                    to_matrix(rows).map_err(|e| e.to_string().into())
+                },
+            )
+            .register_fn("to_string", |m: &mut glm::Mat4| {
+                let rows = (0..4)
+                    .map(|i| {
+                        let row = (0..4).map(|j| m[(i, j)].to_string()).collect::<Vec<_>>();
+                        format!("[{}]", row.join(", "))
+                    })
+                    .collect::<Vec<_>>();
+                format!("Matrix([{}])", rows.join(", "))
+            })
+            .register_fn("*", |a: glm::Mat4, b: glm::Mat4| a * b)
+            .register_fn("*", |m: glm::Mat4, v: glm::Vec3| (m * v.push(1.)).xyz())
+            .register_fn(
+                "inverse",
+                |m: &mut glm::Mat4| -> Result<glm::Mat4, Box<EvalAltResult>> {
+                    m.try_inverse()
+                        .ok_or_else(|| "inverse: the matrix is not invertible.".into())
+                },
+            );
+        let mut matrix = Module::new();
+        matrix.set_native_fn("identity", || Ok(glm::Mat4::identity()));
+        matrix.set_native_fn("translation", |v: glm::Vec3| Ok(glm::translation(&v)));
+        matrix.set_native_fn("scaling", |v: glm::Vec3| Ok(glm::scaling(&v)));
+        matrix.set_native_fn(
+            "rotation_axis_angle",
+            |axis: glm::Vec3, deg: f32| -> Result<glm::Mat4, Box<EvalAltResult>> {
+                if axis.norm() == 0. {
+                    return Err("rotation_axis_angle: the axis must not be zero.".into());
+                }
+                Ok(glm::rotation(deg * PI / 180., &axis))
+            },
+        );
+        matrix.set_native_fn(
+            "look_at",
+            |eye: glm::Vec3,
+             target: glm::Vec3,
+             up: glm::Vec3|
+             -> Result<glm::Mat4, Box<EvalAltResult>> {
+                look_at(eye, target, up).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_static_module("Matrix", matrix.into());
         engine
             .register_type_with_name::<Box<dyn Primitive>>("Primitive")
             .register_fn(
@@ -64,6 +145,33 @@ impl RhaiScriptEngine {
                 "scale",
                 |prim: Box<dyn Primitive>, x: f32, y: f32, z: f32| prim.scale(glm::vec3(x, y, z)),
             )
+            .register_fn(
+                "rotate_axis",
+                |prim: Box<dyn Primitive>,
+                 axis: glm::Vec3,
+                 deg: f32|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    prim.rotate_axis(axis, deg * PI / 180.)
+                        .map_err(|e| e.to_string().into())
+                },
+            )
+            .register_fn(
+                "align",
+                |prim: Box<dyn Primitive>,
+                 from: glm::Vec3,
+                 to: glm::Vec3|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    prim.align(from, to).map_err(|e| e.to_string().into())
+                },
+            )
+            .register_fn(
+                "transform",
+                |prim: Box<dyn Primitive>,
+                 pose: glm::Mat4|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    prim.transform(pose).map_err(|e| e.to_string().into())
+                },
+            )
             .register_fn("scale", |prim: Box<dyn Primitive>, s: f32| {
                 prim.scale(glm::vec3(s, s, s))
             })
@@ -90,7 +198,35 @@ impl RhaiScriptEngine {
                 |prim: Box<dyn Primitive>, distance_for_full_circle: f32| {
                     Bend::new(prim, distance_for_full_circle) as Box<dyn Primitive>
                 },
-            );
+            )
+            .register_fn(
+                "part",
+                |prim: Box<dyn Primitive>,
+                 name: &str|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    let p = Part::new(prim, name, None).map_err(|e| e.to_string())?;
+                    Ok(p as Box<dyn Primitive>)
+                },
+            )
+            .register_fn(
+                "part",
+                |prim: Box<dyn Primitive>,
+                 name: &str,
+                 color: glm::Vec3|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    let p = Part::new(prim, name, Some(color)).map_err(|e| e.to_string())?;
+                    Ok(p as Box<dyn Primitive>)
+                },
+            )
+            .register_fn("describe", |prim: &mut Box<dyn Primitive>| {
+                describe(&**prim)
+            })
+            .register_fn("to_string", |prim: &mut Box<dyn Primitive>| {
+                describe(&**prim)
+            })
+            .register_fn("to_debug", |prim: &mut Box<dyn Primitive>| {
+                describe(&**prim)
+            });
         engine.register_fn(
             "Plane",
             |normal: glm::Vec3, d: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
@@ -103,6 +239,12 @@ impl RhaiScriptEngine {
                 Sphere::new(r).map_err(|e| e.to_string().into())
             },
         );
+        engine.register_fn(
+            "Ellipsoid",
+            |rx: f32, ry: f32, rz: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                Ellipsoid::new(glm::vec3(rx, ry, rz)).map_err(|e| e.to_string().into())
+            },
+        );
         engine
             .register_fn(
                 "Cylinder",
@@ -138,6 +280,88 @@ impl RhaiScriptEngine {
                 Capsule::new(r, begin, end).map_err(|e| e.to_string().into())
             },
         );
+        engine.register_fn(
+            "Tetrahedron",
+            |r: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                Tetrahedron::new(r).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "Octahedron",
+            |r: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                Octahedron::new(r).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "Dodecahedron",
+            |r: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                Dodecahedron::new(r).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "Icosahedron",
+            |r: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                Icosahedron::new(r).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "HexPrism",
+            |r: f32, height: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                HexPrism::new(r, height).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "TriPrism",
+            |r: f32, height: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                TriPrism::new(r, height).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "ConvexPolyhedron",
+            |vertices: Array| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                to_vectors(vertices)
+                    .and_then(ConvexPolyhedron::new)
+                    .map_err(|e| e.to_string().into())
+            },
+        );
+        let mut convex_polyhedron = Module::new();
+        convex_polyhedron.set_native_fn(
+            "from_planes",
+            |normals: Array, offsets: Array| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                to_vectors(normals)
+                    .and_then(|normals| ConvexPolyhedron::from_planes(normals, to_floats(offsets)?))
+                    .map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_static_module("ConvexPolyhedron", convex_polyhedron.into());
+        engine.register_fn(
+            "Cone",
+            |r: f32, height: f32| -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                Cone::new(r, height).map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "CappedCone",
+            |begin_radius: f32,
+             end_radius: f32,
+             begin: glm::Vec3,
+             end: glm::Vec3|
+             -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                CappedCone::new(begin_radius, end_radius, begin, end)
+                    .map_err(|e| e.to_string().into())
+            },
+        );
+        engine.register_fn(
+            "RoundCone",
+            |begin_radius: f32,
+             end_radius: f32,
+             begin: glm::Vec3,
+             end: glm::Vec3|
+             -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                RoundCone::new(begin_radius, end_radius, begin, end)
+                    .map_err(|e| e.to_string().into())
+            },
+        );
 
         engine
             .register_type_with_name::<Box<Torus>>("Torus")
@@ -288,6 +512,33 @@ impl RhaiScriptEngine {
                 "scale",
                 |prim: &mut Box<Boolean>, x: f32, y: f32, z: f32| prim.scale(glm::vec3(x, y, z)),
             )
+            .register_fn(
+                "rotate_axis",
+                |prim: &mut Box<Boolean>,
+                 axis: glm::Vec3,
+                 deg: f32|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    prim.rotate_axis(axis, deg * PI / 180.)
+                        .map_err(|e| e.to_string().into())
+                },
+            )
+            .register_fn(
+                "align",
+                |prim: &mut Box<Boolean>,
+                 from: glm::Vec3,
+                 to: glm::Vec3|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    prim.align(from, to).map_err(|e| e.to_string().into())
+                },
+            )
+            .register_fn(
+                "transform",
+                |prim: &mut Box<Boolean>,
+                 pose: glm::Mat4|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    prim.transform(pose).map_err(|e| e.to_string().into())
+                },
+            )
             .register_fn("scale", |prim: &mut Box<Boolean>, s: f32| {
                 prim.scale(glm::vec3(s, s, s))
             })
@@ -314,9 +565,85 @@ impl RhaiScriptEngine {
                 |prim: &mut Box<Boolean>, distance_for_full_circle: f32| {
                     Bend::new(prim.clone(), distance_for_full_circle) as Box<dyn Primitive>
                 },
+            )
+            .register_fn(
+                "part",
+                |prim: &mut Box<Boolean>,
+                 name: &str|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    let p = Part::new(prim.clone(), name, None).map_err(|e| e.to_string())?;
+                    Ok(p as Box<dyn Primitive>)
+                },
+            )
+            .register_fn(
+                "part",
+                |prim: &mut Box<Boolean>,
+                 name: &str,
+                 color: glm::Vec3|
+                 -> Result<Box<dyn Primitive>, Box<EvalAltResult>> {
+                    let p =
+                        Part::new(prim.clone(), name, Some(color)).map_err(|e| e.to_string())?;
+                    Ok(p as Box<dyn Primitive>)
+                },
+            )
+            .register_fn("describe", |prim: &mut Box<Boolean>| describe(&**prim))
+            .register_fn("to_string", |prim: &mut Box<Boolean>| describe(&**prim))
+            .register_fn("to_debug", |prim: &mut Box<Boolean>| describe(&**prim));
+        let mesh_settings = Rc::new(RefCell::new(MeshSettings::default()));
+        {
+            let mesh_settings = mesh_settings.clone();
+            engine.register_fn(
+                "mesh_settings",
+                move |settings: Map| -> Result<(), Box<EvalAltResult>> {
+                    *mesh_settings.borrow_mut() =
+                        to_mesh_settings(settings).map_err(|e| e.to_string())?;
+                    Ok(())
+                },
             );
+        }
+        let parameters = Rc::new(RefCell::new(Vec::new()));
+        let overrides = Rc::new(RefCell::new(HashMap::<String, Override>::new()));
+        {
+            let parameters = parameters.clone();
+            let overrides = overrides.clone();
+            engine.register_fn(
+                "param",
+                move |name: &str,
+                      default: f32,
+                      min: f32,
+                      max: f32|
+                      -> Result<f32, Box<EvalAltResult>> {
+                    let mut overrides = overrides.borrow_mut();
+                    // Values picked for an earlier declaration may not fit the edited one.
+                    let declaration = [default, min, max];
+                    if overrides
+                        .get(name)
+                        .is_some_and(|o| o.declaration.is_some_and(|d| d != declaration))
+                    {
+                        overrides.remove(name);
+                    }
+                    let value = overrides.get(name).map_or(default, |o| o.value);
+                    let parameter =
+                        to_parameter(name, value, default, min, max, &parameters.borrow())
+                            .map_err(|e| e.to_string())?;
+                    parameters.borrow_mut().push(parameter);
+                    Ok(value)
+                },
+            );
+        }
         let engine = engine;
-        RhaiScriptEngine { engine }
+        RhaiScriptEngine {
+            engine,
+            mesh_settings,
+            parameters,
+            overrides,
+        }
+    }
+}
+
+impl Default for RhaiScriptEngine {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -337,8 +664,151 @@ fn to_primitive(p: Dynamic) -> Result<Box<dyn Primitive>> {
     bail!("Not a primitive: {}", p);
 }
 
+/// Reads a matrix given as 3 or 4 rows of 4 numbers. A missing last row is (0, 0, 0, 1).
+fn to_matrix(rows: Array) -> Result<glm::Mat4> {
+    if rows.len() != 3 && rows.len() != 4 {
+        bail!("Matrix: expected 3 or 4 rows (got {}).", rows.len());
+    }
+    let mut m = glm::Mat4::identity();
+    for (i, row) in rows.into_iter().enumerate() {
+        let row = row
+            .into_array()
+            .map_err(|t| anyhow!("Matrix: expected rows as arrays (got {}).", t))?;
+        if row.len() != 4 {
+            bail!("Matrix: expected 4 numbers per row (got {}).", row.len());
+        }
+        for (j, x) in row.into_iter().enumerate() {
+            m[(i, j)] = x
+                .as_float()
+                .or_else(|_| x.as_int().map(|x| x as f32))
+                .map_err(|t| anyhow!("Matrix: expected numbers (got {}).", t))?;
+        }
+    }
+    Ok(m)
+}
+
+fn to_vectors(values: Array) -> Result<Vec<glm::Vec3>> {
+    values
+        .into_iter()
+        .map(|v| {
+            v.try_cast::<glm::Vec3>()
+                .ok_or_else(|| anyhow!("ConvexPolyhedron: expected an array of Vectors."))
+        })
+        .collect()
+}
+
+fn to_floats(values: Array) -> Result<Vec<f32>> {
+    values
+        .into_iter()
+        .map(|x| {
+            x.as_float()
+                .or_else(|_| x.as_int().map(|x| x as f32))
+                .map_err(|t| anyhow!("ConvexPolyhedron: expected numbers (got {}).", t))
+        })
+        .collect()
+}
+
+/// Returns the pose placing an object at `eye`, with its z axis pointing at `target` and its
+/// y axis towards `up`.
+fn look_at(eye: glm::Vec3, target: glm::Vec3, up: glm::Vec3) -> Result<glm::Mat4> {
+    let z = target - eye;
+    let x = up.cross(&z);
+    if x.norm() == 0. {
+        bail!("look_at: the target must differ from the eye and not lie in the up direction.");
+    }
+    let (x, z) = (x.normalize(), z.normalize());
+    let y = z.cross(&x);
+    Ok(glm::Mat4::from_columns(&[
+        x.push(0.),
+        y.push(0.),
+        z.push(0.),
+        eye.push(1.),
+    ]))
+}
+
+/// Checks a parameter declared by a script, given the `declared` ones before it.
+fn to_parameter(
+    name: &str,
+    value: f32,
+    default: f32,
+    min: f32,
+    max: f32,
+    declared: &[Parameter],
+) -> Result<Parameter> {
+    if declared.iter().any(|p| p.name == name) {
+        bail!("param: {} is declared twice.", name);
+    }
+    if !(min <= default && default <= max) {
+        bail!(
+            "param: default {} of {} is not within {} and {}.",
+            default,
+            name,
+            min,
+            max
+        );
+    }
+    if !(min <= value && value <= max) {
+        bail!(
+            "param: {} must be within {} and {} (was {}).",
+            name,
+            min,
+            max,
+            value
+        );
+    }
+    Ok(Parameter {
+        name: name.to_string(),
+        value,
+        default,
+        min,
+        max,
+    })
+}
+
+/// Reads settings like `#{cell_size: 0.1, min: Vector(-1., -1., -1.), max: Vector(1., 1., 1.)}`.
+fn to_mesh_settings(map: Map) -> Result<MeshSettings> {
+    let mut settings = MeshSettings::default();
+    let mut min = None;
+    let mut max = None;
+    for (key, value) in map {
+        let type_name = value.type_name();
+        let wrong_type = || anyhow!("mesh_settings: unexpected type {} for {}.", type_name, key);
+        match key.as_str() {
+            "cell_size" => {
+                settings.resolution =
+                    Resolution::CellSize(value.as_float().map_err(|_| wrong_type())?)
+            }
+            "depth" => {
+                let depth = value.as_int().map_err(|_| wrong_type())?;
+                let depth = usize::try_from(depth).map_err(|_| {
+                    anyhow!("mesh_settings: depth must be at least 1 (was {}).", depth)
+                })?;
+                settings.resolution = Resolution::Depth(depth);
+            }
+            "min" => min = Some(value.try_cast::<glm::Vec3>().ok_or_else(wrong_type)?),
+            "max" => max = Some(value.try_cast::<glm::Vec3>().ok_or_else(wrong_type)?),
+            "mesher" => {
+                let name = value.into_string().map_err(|_| wrong_type())?;
+                settings.mesher = Mesher::from_name(&name)?;
+            }
+            "normal_epsilon" => {
+                settings.normal_epsilon = value.as_float().map_err(|_| wrong_type())?
+            }
+            _ => bail!("mesh_settings: unknown setting {}.", key),
+        }
+    }
+    settings.bounds = match (min, max) {
+        (Some(min), Some(max)) => Some(BoundingBox::new(min, max)),
+        (None, None) => None,
+        _ => bail!("mesh_settings: min and max must be given together."),
+    };
+    Ok(settings)
+}
+
 impl ScriptEngine for RhaiScriptEngine {
     fn eval(&self, script: &str) -> Result<Box<dyn Primitive>> {
+        *self.mesh_settings.borrow_mut() = MeshSettings::default();
+        self.parameters.borrow_mut().clear();
         let result = self
             .engine
             .eval::<Dynamic>(script)
@@ -346,6 +816,27 @@ impl ScriptEngine for RhaiScriptEngine {
         to_primitive(result)
     }
     fn on_print(&mut self, callback: impl Fn(&str) + 'static) {
-        self.engine.on_print(callback);
+        let callback = Rc::new(callback);
+        let print = callback.clone();
+        self.engine.on_print(move |s| print(s));
+        self.engine
+            .on_debug(move |s, _, position| callback(&format!("{}: {}", position, s)));
+    }
+    fn mesh_settings(&self) -> MeshSettings {
+        self.mesh_settings.borrow().clone()
+    }
+    fn parameters(&self) -> Vec<Parameter> {
+        self.parameters.borrow().clone()
+    }
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        let declaration = self
+            .parameters
+            .borrow()
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| [p.default, p.min, p.max]);
+        self.overrides
+            .borrow_mut()
+            .insert(name.to_string(), Override { value, declaration });
     }
 }
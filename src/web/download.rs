@@ -0,0 +1,26 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+/// Offers `data` to the user as a file download named `file_name`.
+pub fn download(file_name: &str, mime_type: &str, data: &[u8]) -> Result<(), JsValue> {
+    let window = web_sys::window().ok_or("No window available.")?;
+    let document = window.document().ok_or("No document available.")?;
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let anchor = document
+        .create_element("a")?
+        .dyn_into::<HtmlAnchorElement>()?;
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    // Some browsers cancel the download when the URL is revoked right away.
+    let revoke = Closure::once_into_js(move || {
+        let _ = Url::revoke_object_url(&url);
+    });
+    window.set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 1000)?;
+    Ok(())
+}
@@ -1,4 +1,3 @@
-use anyhow::{bail, Result};
 use log::info;
 use std::f32::consts::PI;
 use wasm_bindgen::prelude::*;
@@ -7,34 +6,6 @@ use web_sys::{
     WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader, WebGlUniformLocation,
 };
 
-pub struct Mesh {
-    vertices: Vec<f32>,
-    normals: Vec<f32>,
-}
-
-impl Mesh {
-    pub fn new(vertices: &[f32], normals: &[f32], indices: &[u32]) -> Result<Mesh> {
-        if indices.len() % 3 != 0 {
-            bail!("indices.len() is not divisible by 3.")
-        }
-        let vertices = indices
-            .iter()
-            .map(|i| i * 3)
-            .map(|i| [i, i + 1, i + 2])
-            .flatten()
-            .map(|i| vertices[i as usize])
-            .collect();
-        let normals = indices
-            .iter()
-            .map(|i| i * 3)
-            .map(|i| [i, i + 1, i + 2])
-            .flatten()
-            .map(|i| normals[i as usize])
-            .collect();
-        Ok(Mesh { vertices, normals })
-    }
-}
-
 pub struct MeshCanvas {
     canvas: web_sys::HtmlCanvasElement,
     gl: WebGl2RenderingContext,
@@ -49,7 +20,7 @@ pub struct MeshCanvas {
     reverse_light_direction_location: Option<WebGlUniformLocation>,
 }
 
-static VERTEX_SHADER: &'static str = r##" 
+static VERTEX_SHADER: &str = r##" 
 attribute vec4 a_position;
 attribute vec3 a_normal;
 
@@ -67,7 +38,7 @@ void main() {
 }
 "##;
 
-static FRAGMENT_SHADER: &'static str = r##"
+static FRAGMENT_SHADER: &str = r##"
 precision mediump float;
 
 // Passed in from the vertex shader.
@@ -113,11 +84,6 @@ impl MeshCanvas {
         )?;
 
         let program = link_program(&gl, &vert_shader, &frag_shader)?;
-        // let locations = get_locations(&gl, &program);
-        // let mesh = Mesh::new(_VERTICES, _NORMALS, _INDICES).map_err(|e| e.to_string())?;
-
-        // let buffers = init_buffers(&gl, &mesh)?;
-
         let position_location = gl.get_attrib_location(&program, "a_position");
         let normal_location = gl.get_attrib_location(&program, "a_normal");
         let world_view_projection_location =
@@ -408,8 +374,7 @@ fn set_geometry(gl: &WebGl2RenderingContext) {
         0, 0, 0, 0, 0, 30, 0, 150, 30, 0, 0, 0, 0, 150, 30, 0, 150, 0,
     ][..]
         .chunks(3)
-        .map(|p| [p[0] - 50, p[1] - 75, p[2] - 15])
-        .flatten()
+        .flat_map(|p| [p[0] - 50, p[1] - 75, p[2] - 15])
         .map(|x| x as f32)
         .collect::<Vec<_>>();
     let vs = geometry[..]
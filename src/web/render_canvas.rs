@@ -1,5 +1,5 @@
-use super::renderer;
-use super::Primitive;
+use crate::primitive::Primitive;
+use crate::renderer;
 use log::debug;
 use shadercanvas::ShaderCanvas;
 use std::cell::RefCell;
@@ -9,6 +9,9 @@ use wasm_bindgen::JsCast;
 
 pub struct RenderCanvas {
     shader_canvas: Rc<RefCell<ShaderCanvas>>,
+    world_transform: Rc<RefCell<glm::Mat4>>,
+    /// The code of the current shader, which gets reused for objects of the same structure.
+    shader: RefCell<String>,
 }
 
 impl RenderCanvas {
@@ -72,20 +75,40 @@ impl RenderCanvas {
                 .add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref())?;
             closure.forget();
         }
-        Ok(RenderCanvas { shader_canvas })
+        Ok(RenderCanvas {
+            shader_canvas,
+            world_transform,
+            shader: RefCell::new(String::new()),
+        })
     }
     pub fn draw(&self) {
         self.shader_canvas.borrow().draw();
     }
+    /// Renders `prim`. Objects that only differ in their constants just update the uniforms,
+    /// which keeps tweaking interactive.
     pub fn set_primtive(&self, prim: &dyn Primitive) -> Result<(), JsValue> {
-        let shader =
-            renderer::generate_renderer_shader(prim).map_err(|e| JsValue::from(e.to_string()))?;
-        debug!("setting shader:\n{}", shader);
+        let shader = renderer::generate_parametric_renderer_shader(prim)
+            .map_err(|e| JsValue::from(e.to_string()))?;
         let mut shader_canvas = self.shader_canvas.borrow_mut();
-        shader_canvas.set_shader(&shader)?;
-        // Also reset the world transform.
-        let world_transform = glm::identity::<f32, 4>();
-        shader_canvas.uniform_matrix4fv("iWorldTransform", world_transform.as_slice());
+        if *self.shader.borrow() != shader.code {
+            debug!("setting shader:\n{}", shader.code);
+            shader_canvas.set_shader(&shader.code)?;
+            *self.shader.borrow_mut() = shader.code;
+            self.reset_world_transform(&shader_canvas, prim);
+        }
+        shader_canvas.uniform_matrix4fv(renderer::PARAMETERS, &shader.parameters);
         Ok(())
     }
+    fn reset_world_transform(&self, shader_canvas: &ShaderCanvas, prim: &dyn Primitive) {
+        // Reset the world transform, centering the object if it is bounded.
+        let bounds = prim.bounds();
+        let center = if bounds.is_finite() {
+            bounds.center()
+        } else {
+            glm::vec3(0., 0., 0.)
+        };
+        let mut world_transform = self.world_transform.borrow_mut();
+        *world_transform = glm::translation(&center);
+        shader_canvas.uniform_matrix4fv("iWorldTransform", world_transform.transpose().as_slice());
+    }
 }
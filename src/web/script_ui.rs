@@ -0,0 +1,261 @@
+use super::download::download;
+use crate::export::write_binary_stl;
+use crate::mesh::{mesh_primitive, MeshSettings};
+use crate::primitive::{to_json, Primitive};
+use crate::script_engine::{Parameter, ScriptEngine};
+use log::{error, info};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlButtonElement, HtmlInputElement, HtmlTextAreaElement};
+
+pub struct ScriptUI {
+    primitive: Option<Box<dyn Primitive>>,
+    mesh_settings: MeshSettings,
+    /// The parameters the sliders were made for.
+    parameters: Vec<Parameter>,
+}
+
+impl ScriptUI {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<E: ScriptEngine + 'static>(
+        code_area: HtmlTextAreaElement,
+        output_area: HtmlTextAreaElement,
+        parameters_area: Element,
+        run_button: HtmlButtonElement,
+        mesh_button: HtmlButtonElement,
+        download_stl_button: HtmlButtonElement,
+        download_json_button: HtmlButtonElement,
+        mut engine: E,
+        on_new_object_callback: impl Fn(&dyn Primitive) + 'static,
+    ) -> Result<Rc<RefCell<ScriptUI>>, JsValue> {
+        connect_output_to_engine(&output_area, &mut engine);
+        let ui = Rc::new(RefCell::new(ScriptUI {
+            primitive: None,
+            mesh_settings: MeshSettings::default(),
+            parameters: Vec::new(),
+        }));
+        let runner = Rc::new(Runner {
+            ui: ui.clone(),
+            engine: RefCell::new(engine),
+            code_area,
+            parameters_area,
+            on_new_object_callback: Box::new(on_new_object_callback),
+        });
+        register_run_callback(&runner, &run_button)?;
+        register_mesh_callback(&ui, &output_area, &mesh_button)?;
+        register_download_stl_callback(&ui, &output_area, &download_stl_button)?;
+        register_download_json_callback(&ui, &output_area, &download_json_button)?;
+        Ok(ui)
+    }
+}
+
+fn connect_output_to_engine<E: ScriptEngine + 'static>(
+    output_area: &HtmlTextAreaElement,
+    engine: &mut E,
+) {
+    let output_area = output_area.clone();
+    engine.on_print(move |s| {
+        let mut output = output_area.value();
+        output.push_str(s);
+        output.push('\n');
+        output_area.set_value(&output);
+    });
+}
+
+type ObjectCallback = dyn Fn(&dyn Primitive);
+
+/// Evaluates the script, on a click of the run button as well as on moving a slider.
+struct Runner<E> {
+    ui: Rc<RefCell<ScriptUI>>,
+    engine: RefCell<E>,
+    code_area: HtmlTextAreaElement,
+    parameters_area: Element,
+    on_new_object_callback: Box<ObjectCallback>,
+}
+
+impl<E: ScriptEngine + 'static> Runner<E> {
+    fn run(self: &Rc<Self>) -> Result<(), JsValue> {
+        let result = self.engine.borrow().eval(&self.code_area.value());
+        let primitive = match result {
+            Ok(primitive) => primitive,
+            Err(e) => {
+                info!("was err: {:?}", e);
+                return Ok(());
+            }
+        };
+        let parameters = self.engine.borrow().parameters();
+        {
+            let mut ui = self.ui.borrow_mut();
+            ui.primitive = Some(primitive.clone());
+            ui.mesh_settings = self.engine.borrow().mesh_settings();
+        }
+        (self.on_new_object_callback)(&*primitive);
+        // Rebuilding the sliders while one gets dragged would end the drag.
+        if !same_sliders(&self.ui.borrow().parameters, &parameters) {
+            self.make_sliders(&parameters)?;
+        }
+        self.ui.borrow_mut().parameters = parameters;
+        Ok(())
+    }
+
+    fn make_sliders(self: &Rc<Self>, parameters: &[Parameter]) -> Result<(), JsValue> {
+        let document = web_sys::window().unwrap().document().unwrap();
+        self.parameters_area.set_inner_html("");
+        for parameter in parameters {
+            let label = document.create_element("label")?;
+            label.set_text_content(Some(&parameter.name));
+            let slider = document
+                .create_element("input")?
+                .dyn_into::<HtmlInputElement>()?;
+            slider.set_type("range");
+            slider.set_min(&parameter.min.to_string());
+            slider.set_max(&parameter.max.to_string());
+            slider.set_step("any");
+            slider.set_value(&parameter.value.to_string());
+            let closure = {
+                let runner = self.clone();
+                let slider = slider.clone();
+                let name = parameter.name.clone();
+                Closure::<dyn FnMut(_)>::new(move |_event: web_sys::Event| {
+                    let Ok(value) = slider.value().parse::<f32>() else {
+                        return;
+                    };
+                    runner.engine.borrow_mut().set_parameter(&name, value);
+                    if let Err(e) = runner.run() {
+                        error!("{:?}", e);
+                    }
+                })
+            };
+            slider.add_event_listener_with_callback("input", closure.as_ref().unchecked_ref())?;
+            closure.forget();
+            label.append_child(&slider)?;
+            self.parameters_area.append_child(&label)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether sliders made for `a` also fit `b`, which may only differ in the values.
+fn same_sliders(a: &[Parameter], b: &[Parameter]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|(a, b)| {
+            a.name == b.name && a.default == b.default && a.min == b.min && a.max == b.max
+        })
+}
+
+fn register_run_callback<E: ScriptEngine + 'static>(
+    runner: &Rc<Runner<E>>,
+    run_button: &HtmlButtonElement,
+) -> Result<(), JsValue> {
+    // Run once to create the first object.
+    runner.run()?;
+    let runner = runner.clone();
+    let closure = Closure::<dyn FnMut(_)>::new(move |_event: web_sys::MouseEvent| {
+        if let Err(e) = runner.run() {
+            error!("{:?}", e);
+        }
+    });
+    run_button.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+    closure.forget();
+    Ok(())
+}
+
+fn register_mesh_callback(
+    ui: &Rc<RefCell<ScriptUI>>,
+    output_area: &HtmlTextAreaElement,
+    mesh_button: &HtmlButtonElement,
+) -> Result<(), JsValue> {
+    let ui = ui.clone();
+    let output_area = output_area.clone();
+    let closure = move |_event: web_sys::MouseEvent| match &ui.borrow().primitive {
+        Some(p) => {
+            let mut output = output_area.value();
+            match mesh_primitive(&**p, &ui.borrow().mesh_settings) {
+                Ok(mesh) => output.push_str(&format!(
+                    "Got {} vertices and {} triangles.\n",
+                    mesh.vertices.len(),
+                    mesh.triangles.len()
+                )),
+                Err(e) => output.push_str(&format!("Meshing failed: {}\n", e)),
+            }
+            output_area.set_value(&output);
+        }
+        None => {
+            let mut output = output_area.value();
+            output.push_str("No object found. (Click Run first?)\n");
+            output_area.set_value(&output);
+        }
+    };
+    let closure = Closure::<dyn FnMut(_)>::new(closure);
+    mesh_button.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+    closure.forget();
+    Ok(())
+}
+
+fn register_download_stl_callback(
+    ui: &Rc<RefCell<ScriptUI>>,
+    output_area: &HtmlTextAreaElement,
+    download_stl_button: &HtmlButtonElement,
+) -> Result<(), JsValue> {
+    let ui = ui.clone();
+    let output_area = output_area.clone();
+    let closure = move |_event: web_sys::MouseEvent| {
+        let ui = ui.borrow();
+        let message = match &ui.primitive {
+            Some(p) => match download_stl(&**p, &ui.mesh_settings) {
+                Ok(()) => return,
+                Err(e) => format!("STL export failed: {:?}\n", e),
+            },
+            None => "No object found. (Click Run first?)\n".to_string(),
+        };
+        let mut output = output_area.value();
+        output.push_str(&message);
+        output_area.set_value(&output);
+    };
+    let closure = Closure::<dyn FnMut(_)>::new(closure);
+    download_stl_button
+        .add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+    closure.forget();
+    Ok(())
+}
+
+fn download_stl(p: &dyn Primitive, mesh_settings: &MeshSettings) -> Result<(), JsValue> {
+    let mesh = mesh_primitive(p, mesh_settings).map_err(|e| e.to_string())?;
+    let mut stl = Vec::new();
+    write_binary_stl(&mesh, &mut stl).map_err(|e| e.to_string())?;
+    download("sdfer.stl", "model/stl", &stl)
+}
+
+fn register_download_json_callback(
+    ui: &Rc<RefCell<ScriptUI>>,
+    output_area: &HtmlTextAreaElement,
+    download_json_button: &HtmlButtonElement,
+) -> Result<(), JsValue> {
+    let ui = ui.clone();
+    let output_area = output_area.clone();
+    let closure = move |_event: web_sys::MouseEvent| {
+        let message = match &ui.borrow().primitive {
+            Some(p) => match download_json(&**p) {
+                Ok(()) => return,
+                Err(e) => format!("JSON export failed: {:?}\n", e),
+            },
+            None => "No object found. (Click Run first?)\n".to_string(),
+        };
+        let mut output = output_area.value();
+        output.push_str(&message);
+        output_area.set_value(&output);
+    };
+    let closure = Closure::<dyn FnMut(_)>::new(closure);
+    download_json_button
+        .add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+    closure.forget();
+    Ok(())
+}
+
+/// Offers the object for meshing natively, e.g. with `sdfer mesh sdfer.json`.
+fn download_json(p: &dyn Primitive) -> Result<(), JsValue> {
+    let json = to_json(p).map_err(|e| e.to_string())?;
+    download("sdfer.json", "application/json", json.as_bytes())
+}
@@ -0,0 +1,178 @@
+//! Checks that the bounds of every primitive contain the whole object, and that interval
+//! evaluations contain the distances within their regions.
+
+use sdfer::primitive::*;
+
+extern crate nalgebra_glm as glm;
+
+/// Deterministic pseudo random points in [-4, 4]^3.
+struct Points(u32);
+
+impl Iterator for Points {
+    type Item = glm::Vec3;
+    fn next(&mut self) -> Option<glm::Vec3> {
+        let mut coordinate = || {
+            self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+            (self.0 >> 8) as f32 / (1 << 24) as f32 * 8. - 4.
+        };
+        Some(glm::vec3(coordinate(), coordinate(), coordinate()))
+    }
+}
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn cube() -> Box<dyn Primitive> {
+    ExactBox::new(v(1.5, 1., 2.))
+        .unwrap()
+        .translate(v(0.7, 0., 0.))
+}
+
+fn cases() -> Vec<(&'static str, Box<dyn Primitive>)> {
+    let smooth = |mut b: Box<Boolean>| {
+        b.set_min_function(Box::new(MinPolynomial::new(0.5).unwrap()))
+            .unwrap();
+        b as Box<dyn Primitive>
+    };
+    let min_functions: Vec<(&str, Box<dyn MinFunction>)> = vec![
+        (
+            "MinCubicPolynomial",
+            Box::new(MinCubicPolynomial::new(0.5).unwrap()),
+        ),
+        ("MinRoot", Box::new(MinRoot::new(0.5).unwrap())),
+        ("MinChamfer", Box::new(MinChamfer::new(0.5).unwrap())),
+        ("MinStairs", Box::new(MinStairs::new(0.5, 3).unwrap())),
+        ("MinExponential", Box::new(MinExponential::new(4.).unwrap())),
+    ];
+    let min_function_cases = min_functions.into_iter().map(|(name, f)| {
+        let mut union = Boolean::new_union(vec![Sphere::new(1.).unwrap(), cube()]).unwrap();
+        union.set_min_function(f).unwrap();
+        (name, union as Box<dyn Primitive>)
+    });
+    let cases = vec![
+        ("Sphere", Sphere::new(1.).unwrap()),
+        ("Ellipsoid", Ellipsoid::new(v(1.5, 0.4, 0.9)).unwrap()),
+        ("Plane", Plane::new(v(0., 0., -1.), 0.3).unwrap()),
+        ("Tilted plane", Plane::new(v(1., -2., 0.5), 0.3).unwrap()),
+        ("RoundBox", RoundBox::new(v(1.5, 1., 2.), 0.2).unwrap()),
+        ("CappedTorus", Torus::new_capped(0.5, 1.5, 2.).unwrap()),
+        ("Cylinder", Cylinder::new_infinite(0.7).unwrap()),
+        (
+            "CappedCylinder",
+            Cylinder::new(0.7, v(0.1, -1., 0.3), v(1., 1., -0.5)).unwrap(),
+        ),
+        (
+            "RoundedCylinder",
+            RoundedCylinder::new(0.5, 0.1, 1.).unwrap(),
+        ),
+        (
+            "Capsule",
+            Capsule::new(0.4, v(-1., 0., 0.), v(1., 1., 1.)).unwrap(),
+        ),
+        ("Cone", Cone::new(0.8, 1.5).unwrap()),
+        (
+            "CappedCone",
+            CappedCone::new(0.9, 0.3, v(0.1, -1., 0.3), v(1., 1., -0.5)).unwrap(),
+        ),
+        (
+            "RoundCone",
+            RoundCone::new(0.6, 0.2, v(-1., 0., 0.), v(1., 1., 1.)).unwrap(),
+        ),
+        ("Dodecahedron", Dodecahedron::new(0.7).unwrap()),
+        ("HexPrism", HexPrism::new(0.7, 1.5).unwrap()),
+        ("TriPrism", TriPrism::new(0.7, 1.5).unwrap()),
+        ("Rotate", cube().rotate_euler(0.3, 0.6, -1.2)),
+        ("Scale", cube().scale(v(-0.5, 2., 1.5))),
+        ("Twist", Twist::new(cube(), 3.)),
+        ("Bend", Bend::new(cube().translate(v(0., 2., 0.)), 8.)),
+        (
+            "Repeat",
+            Repeat::new(
+                Sphere::new(0.3).unwrap(),
+                v(1., 0.8, 1.3),
+                glm::vec3(-2, 0, -1),
+                glm::vec3(1, 2, 1),
+            )
+            .unwrap(),
+        ),
+        (
+            "Union",
+            smooth(Boolean::new_union(vec![Sphere::new(1.).unwrap(), cube()]).unwrap()),
+        ),
+        (
+            "Intersection",
+            smooth(
+                Boolean::new_intersection(vec![cube(), Cylinder::new_infinite(0.7).unwrap()])
+                    .unwrap(),
+            ),
+        ),
+        (
+            "Difference",
+            Boolean::new_difference(vec![cube(), Sphere::new(1.).unwrap()]).unwrap(),
+        ),
+    ];
+    cases.into_iter().chain(min_function_cases).collect()
+}
+
+#[test]
+fn bounds_are_conservative() {
+    let mut failures = Vec::new();
+    for (name, primitive) in cases() {
+        let bounds = primitive.bounds();
+        for p in Points(1).take(2000) {
+            let d = primitive.eval(p);
+            let outside = bounds.distance(p);
+            // Exact distances even reach the bounds no sooner than the object.
+            let limit = if primitive.is_exact() { outside } else { 0. };
+            if outside > 0. && d < limit - 1e-4 {
+                failures.push(format!(
+                    "{}: eval({:?}) = {} outside of {:?}",
+                    name,
+                    p.as_slice(),
+                    d,
+                    bounds
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn intervals_contain_samples() {
+    let mut failures = Vec::new();
+    for (name, primitive) in cases() {
+        let mut points = Points(2);
+        for _ in 0..200 {
+            // Regions of up to 2 across, sampled at their corners and inside.
+            let center = points.next().unwrap();
+            let half_size = points.next().unwrap().abs() / 4.;
+            let region = BoundingBox::new(center - half_size, center + half_size);
+            let interval = primitive.eval_interval(&region);
+            let corners = (0..8).map(|i| {
+                let corner = glm::vec3(i & 1, (i >> 1) & 1, i >> 2).map(|c| c as f32);
+                region.min + corner.component_mul(&(half_size * 2.))
+            });
+            let inside = points
+                .by_ref()
+                .take(8)
+                .map(|t| center + (t / 4.).component_mul(&half_size))
+                .collect::<Vec<_>>();
+            for p in corners.chain(inside) {
+                let d = primitive.eval(p);
+                let tolerance = 1e-4 * d.abs().max(1.);
+                if d < interval.min - tolerance || d > interval.max + tolerance {
+                    failures.push(format!(
+                        "{}: eval({:?}) = {} not in {:?}",
+                        name,
+                        p.as_slice(),
+                        d,
+                        interval
+                    ));
+                }
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
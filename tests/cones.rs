@@ -0,0 +1,104 @@
+//! Checks the cone distances against the distance to a densely sampled profile.
+
+use sdfer::primitive::*;
+use sdfer::script_engine::{RhaiScriptEngine, ScriptEngine};
+
+extern crate nalgebra_glm as glm;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn points() -> impl Iterator<Item = glm::Vec3> {
+    (0..300).map(|i| {
+        let t = i as f32;
+        v(t.sin() * 2., (t * 1.3).cos() * 2., (t * 0.7).sin() * 2.)
+    })
+}
+
+/// Returns the distance of a point, given by its distance `r` from the z axis and its height
+/// `z`, to the boundary of the polygon `profile` rotated around the z axis.
+fn profile_distance(profile: &[(f32, f32)], r: f32, z: f32) -> f32 {
+    let p = glm::vec2(r, z);
+    let mut distance = f32::INFINITY;
+    let mut inside = false;
+    for (i, a) in profile.iter().enumerate() {
+        let b = profile[(i + 1) % profile.len()];
+        let (a, b) = (glm::vec2(a.0, a.1), glm::vec2(b.0, b.1));
+        let h = ((p - a).dot(&(b - a)) / (b - a).norm_squared()).clamp(0., 1.);
+        distance = distance.min((p - a - (b - a) * h).norm());
+        if (a[1] > z) != (b[1] > z) && r < a[0] + (z - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+            inside = !inside;
+        }
+    }
+    if inside {
+        -distance
+    } else {
+        distance
+    }
+}
+
+#[test]
+fn cones_are_exact() {
+    let cone = Cone::new(0.8, 1.5).unwrap();
+    let capped = CappedCone::new(0.9, 0.3, v(0., 0., -0.5), v(0., 0., 1.)).unwrap();
+    // Mirrors the profile at the axis, so that distances through the axis count.
+    let cone_profile = [(-0.8, 0.), (0.8, 0.), (0., 1.5)];
+    let capped_profile = [(-0.9, -0.5), (0.9, -0.5), (0.3, 1.), (-0.3, 1.)];
+    for p in points() {
+        let r = p.xy().norm();
+        let expected = profile_distance(&cone_profile, r, p[2]);
+        assert!((cone.eval(p) - expected).abs() < 1e-4, "at {:?}", p);
+        let expected = profile_distance(&capped_profile, r, p[2]);
+        assert!((capped.eval(p) - expected).abs() < 1e-4, "at {:?}", p);
+    }
+}
+
+#[test]
+fn round_cones_are_exact() {
+    let (a, b) = (v(-1., 0., 0.), v(1., 1., 1.));
+    let round = RoundCone::new(0.6, 0.2, a, b).unwrap();
+    // The round cone is the union of the spheres swept along the axis.
+    let swept = |p: glm::Vec3| {
+        (0..=10000)
+            .map(|i| {
+                let t = i as f32 / 10000.;
+                (p - a.lerp(&b, t)).norm() - (0.6 + (0.2 - 0.6) * t)
+            })
+            .fold(f32::INFINITY, f32::min)
+    };
+    for p in points() {
+        let expected = swept(p);
+        // The nearest swept sphere only gives the distance outside.
+        if expected > 0. {
+            assert!((round.eval(p) - expected).abs() < 1e-3, "at {:?}", p);
+        } else {
+            assert!(round.eval(p) < 1e-3, "at {:?}", p);
+        }
+    }
+}
+
+#[test]
+fn invalid_cones() {
+    assert!(Cone::new(0., 1.).is_err());
+    assert!(Cone::new(1., -1.).is_err());
+    assert!(CappedCone::new(0., 0., v(0., 0., 0.), v(0., 0., 1.)).is_err());
+    assert!(CappedCone::new(1., 0.5, v(0., 0., 1.), v(0., 0., 1.)).is_err());
+    // One sphere would contain the other.
+    assert!(RoundCone::new(1., 0.2, v(0., 0., 0.), v(0., 0., 0.5)).is_err());
+}
+
+#[test]
+fn cones_in_scripts() {
+    let engine = RhaiScriptEngine::new();
+    let cone = engine.eval("Cone(1., 2.)").unwrap();
+    assert!((cone.eval(v(0., 0., 3.)) - 1.).abs() < 1e-5);
+    let capped = engine
+        .eval("CappedCone(1., 0.5, Vector(0., 0., 0.), Vector(2., 0., 0.))")
+        .unwrap();
+    assert!((capped.eval(v(3., 0., 0.)) - 1.).abs() < 1e-5);
+    let round = engine
+        .eval("RoundCone(1., 0.5, Vector(0., 0., 0.), Vector(0., 3., 0.))")
+        .unwrap();
+    assert!((round.eval(v(0., 4., 0.)) - 0.5).abs() < 1e-5);
+}
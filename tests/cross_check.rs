@@ -0,0 +1,350 @@
+//! Checks that the generated GLSL and WGSL of every primitive, as well as its compiled distance,
+//! compute the same distances as `eval`, and that naga accepts the generated WGSL modules.
+
+mod glsl;
+mod wgsl;
+
+use glsl::{Program, Value};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use sdfer::ir::{Type, Uniforms, Var};
+use sdfer::primitive::*;
+use wgsl::WgslProgram;
+
+extern crate nalgebra_glm as glm;
+
+/// Number of random points every primitive gets evaluated at.
+const SAMPLES: usize = 1000;
+
+/// Deterministic pseudo random points in [-3, 3]^3.
+struct Points(u32);
+
+impl Iterator for Points {
+    type Item = glm::Vec3;
+    fn next(&mut self) -> Option<glm::Vec3> {
+        let mut coordinate = || {
+            self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+            (self.0 >> 8) as f32 / (1 << 24) as f32 * 6. - 3.
+        };
+        Some(glm::vec3(coordinate(), coordinate(), coordinate()))
+    }
+}
+
+/// Returns a description of every point where a shader or the compiled distance and `eval`
+/// disagree.
+fn mismatches(name: &str, primitive: &dyn Primitive) -> Vec<String> {
+    let mut glsl_code = Vec::new();
+    let glsl_expression = primitive.expression("p", &mut glsl_code).unwrap();
+    let glsl_program = Program::new(&glsl_code);
+    let mut wgsl_code = Vec::new();
+    let wgsl_expression = primitive.wgsl_expression("p", &mut wgsl_code).unwrap();
+    let wgsl_program = WgslProgram::new(&wgsl_code);
+    let (uniform_code, uniform_expression, uniforms) = with_uniforms(primitive);
+    let mut uniform_program = Program::new(&uniform_code);
+    uniform_program.set_global(uniforms.name(), uniform_value(&uniforms));
+    let compiled = Compiled::new(primitive);
+    let mut mismatches = Vec::new();
+    for p in Points(1).take(SAMPLES) {
+        let expected = primitive.eval(p);
+        let variables = [("p", Value::Vector(p.as_slice().to_vec()))];
+        let shaders = [
+            ("GLSL", glsl_program.eval(&glsl_expression, &variables)),
+            ("WGSL", wgsl_program.eval(&wgsl_expression, &variables)),
+            (
+                "GLSL with uniforms",
+                uniform_program.eval(&uniform_expression, &variables),
+            ),
+            ("compiled", Value::Float(compiled.eval(p))),
+        ];
+        for (language, value) in shaders {
+            let actual = match value {
+                Value::Float(d) => d,
+                v => panic!("{} evaluated to {:?} in {}", name, v, language),
+            };
+            if (expected - actual).abs() > 1e-4 * expected.abs().max(1.) {
+                mismatches.push(format!(
+                    "{}: eval({:?}) = {}, {} = {}",
+                    name,
+                    p.as_slice(),
+                    expected,
+                    language,
+                    actual
+                ));
+            }
+        }
+    }
+    mismatches
+}
+
+/// Returns the shared code and the expression reading constants from uniforms.
+fn with_uniforms(primitive: &dyn Primitive) -> (Vec<String>, String, Uniforms) {
+    let mut shared_code = Vec::new();
+    let mut uniforms = Uniforms::new("iParameters", 768);
+    let p = Var::new("p", Type::Vec3);
+    let expression = sdfer::ir::glsl::expression_with_uniforms(
+        &primitive.sdf(&p.expr()),
+        &[(p, "p")],
+        &mut shared_code,
+        &mut uniforms,
+    );
+    (shared_code, expression, uniforms)
+}
+
+fn uniform_value(uniforms: &Uniforms) -> Value {
+    Value::Array(
+        uniforms
+            .values()
+            .chunks(16)
+            .map(|m| Value::Matrix(4, m.to_vec()))
+            .collect(),
+    )
+}
+
+/// Panics unless naga parses and validates the WGSL module defining the distance.
+fn validate_wgsl(name: &str, primitive: &dyn Primitive) {
+    let source = sdfer::renderer::generate_wgsl_map(primitive).unwrap();
+    let module = naga::front::wgsl::parse_str(&source)
+        .unwrap_or_else(|e| panic!("{}: {}", name, e.emit_to_string(&source)));
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .unwrap_or_else(|e| panic!("{}: {}", name, e.emit_to_string(&source)));
+}
+
+fn check(cases: Vec<(&str, Box<dyn Primitive>)>) {
+    for (name, primitive) in &cases {
+        validate_wgsl(name, &**primitive);
+    }
+    let mismatches = cases
+        .iter()
+        .flat_map(|(name, primitive)| mismatches(name, &**primitive).into_iter().take(3))
+        .collect::<Vec<_>>();
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn sphere() -> Box<dyn Primitive> {
+    Sphere::new(1.).unwrap()
+}
+
+fn cube() -> Box<dyn Primitive> {
+    ExactBox::new(v(1.5, 1., 2.))
+        .unwrap()
+        .translate(v(0.7, 0., 0.))
+}
+
+#[test]
+fn primitives() {
+    check(vec![
+        ("Sphere", sphere()),
+        ("Ellipsoid", Ellipsoid::new(v(1.5, 0.4, 0.9)).unwrap()),
+        ("Plane", Plane::new(v(1., -2., 0.5), 0.3).unwrap()),
+        ("ExactBox", ExactBox::new(v(1.5, 1., 2.)).unwrap()),
+        ("RoundBox", RoundBox::new(v(1.5, 1., 2.), 0.2).unwrap()),
+        ("Torus", Torus::new(0.5, 1.5).unwrap()),
+        ("CappedTorus", Torus::new_capped(0.5, 1.5, 2.).unwrap()),
+        ("Cylinder", Cylinder::new_infinite(0.7).unwrap()),
+        (
+            "CappedCylinder",
+            Cylinder::new(0.7, v(0.1, -1., 0.3), v(1., 1., -0.5)).unwrap(),
+        ),
+        (
+            "RoundedCylinder",
+            RoundedCylinder::new(0.5, 0.1, 1.).unwrap(),
+        ),
+        (
+            "Capsule",
+            Capsule::new(0.4, v(-1., 0., 0.), v(1., 1., 1.)).unwrap(),
+        ),
+        ("Cone", Cone::new(0.8, 1.5).unwrap()),
+        ("Tetrahedron", Tetrahedron::new(0.7).unwrap()),
+        ("Octahedron", Octahedron::new(0.7).unwrap()),
+        ("Dodecahedron", Dodecahedron::new(0.7).unwrap()),
+        ("Icosahedron", Icosahedron::new(0.7).unwrap()),
+        ("HexPrism", HexPrism::new(0.7, 1.5).unwrap()),
+        ("TriPrism", TriPrism::new(0.7, 1.5).unwrap()),
+        (
+            "ConvexPolyhedron",
+            ConvexPolyhedron::new(vec![
+                v(-1., -0.5, -0.8),
+                v(1.2, -0.7, -0.5),
+                v(0.1, 1.3, -0.6),
+                v(0.2, 0.1, 1.4),
+                v(-0.6, 0.9, 0.7),
+                v(0.9, 0.8, 0.6),
+            ])
+            .unwrap(),
+        ),
+        (
+            "ConvexPolyhedron from planes",
+            ConvexPolyhedron::from_planes(
+                vec![
+                    v(1., 0.2, 0.1),
+                    v(-1., 0.3, 0.),
+                    v(0., 1., -0.2),
+                    v(0.1, -1., 0.),
+                    v(0.3, 0., 1.),
+                    v(0., -0.2, -1.),
+                    v(1., 1., 1.),
+                ],
+                vec![0.8, 0.7, 0.9, 0.6, 1.1, 0.5, 1.],
+            )
+            .unwrap(),
+        ),
+        (
+            "CappedCone",
+            CappedCone::new(0.9, 0.3, v(0.1, -1., 0.3), v(1., 1., -0.5)).unwrap(),
+        ),
+        (
+            "RoundCone",
+            RoundCone::new(0.6, 0.2, v(-1., 0., 0.), v(1., 1., 1.)).unwrap(),
+        ),
+        ("Part", Part::new(sphere(), "ball", None).unwrap()),
+    ]);
+}
+
+#[test]
+fn transformations() {
+    check(vec![
+        ("Translate", sphere().translate(v(0.5, -1., 0.3))),
+        ("Rotate", cube().rotate_euler(0.3, 0.6, -1.2)),
+        ("Scale", cube().scale(v(0.5, 2., 1.5))),
+        ("Scale negative", cube().scale(v(-0.5, 2., 1.5))),
+        (
+            "Transform",
+            cube()
+                .translate(v(0.5, -1., 0.3))
+                .rotate_euler(0.3, 0.6, -1.2)
+                .scale(v(0.5, 2., 1.5))
+                .simplify(),
+        ),
+        (
+            "Transform shear",
+            Transform::new(
+                cube(),
+                glm::mat4(
+                    1., 0.5, 0., 0.3, //
+                    0., 1.2, 0., -0.5, //
+                    0.2, 0., 0.8, 0., //
+                    0., 0., 0., 1.,
+                ),
+            )
+            .unwrap(),
+        ),
+        ("Twist", Twist::new(cube(), 3.)),
+        ("Bend", Bend::new(cube().translate(v(0., 2., 0.)), 8.)),
+        (
+            "Repeat",
+            Repeat::new(
+                Sphere::new(0.3).unwrap(),
+                v(1., 0.8, 1.3),
+                glm::vec3(-2, 0, -1),
+                glm::vec3(1, 2, 1),
+            )
+            .unwrap(),
+        ),
+    ]);
+}
+
+#[test]
+fn booleans() {
+    let many = (0..9)
+        .map(|i| {
+            Sphere::new(0.3)
+                .unwrap()
+                .translate(v(i as f32 * 0.5 - 2., 0., 0.))
+        })
+        .collect::<Vec<_>>();
+    check(vec![
+        ("Union", Boolean::new_union(vec![sphere(), cube()]).unwrap()),
+        (
+            "Intersection",
+            Boolean::new_intersection(vec![sphere(), cube()]).unwrap(),
+        ),
+        (
+            "Difference",
+            Boolean::new_difference(vec![cube(), sphere(), sphere().translate(v(1., 1., 0.))])
+                .unwrap(),
+        ),
+        ("Union of many", Boolean::new_union(many).unwrap()),
+        (
+            "Union with distance bounds",
+            Boolean::new_union(vec![
+                Sphere::new(0.5).unwrap().translate(v(0., 2.5, 0.)),
+                sphere().scale(v(1., 1., 0.25)),
+                sphere().scale(v(1., 1., 4.)).translate(v(2., 0., -5.)),
+            ])
+            .unwrap(),
+        ),
+        (
+            "Union with warps",
+            Boolean::new_union(vec![
+                Twist::new(cube(), 3.),
+                Bend::new(cube().translate(v(0., 2., 0.)), 8.),
+                Plane::new(v(0., 0., 1.), 2.).unwrap(),
+            ])
+            .unwrap(),
+        ),
+    ]);
+}
+
+#[test]
+fn min_functions() {
+    let min_functions: Vec<(&str, Box<dyn MinFunction>)> = vec![
+        ("MinPolynomial", Box::new(MinPolynomial::new(0.5).unwrap())),
+        (
+            "MinCubicPolynomial",
+            Box::new(MinCubicPolynomial::new(0.5).unwrap()),
+        ),
+        ("MinRoot", Box::new(MinRoot::new(0.5).unwrap())),
+        ("MinChamfer", Box::new(MinChamfer::new(0.5).unwrap())),
+        ("MinStairs", Box::new(MinStairs::new(0.5, 3).unwrap())),
+        ("MinExponential", Box::new(MinExponential::new(4.).unwrap())),
+    ];
+    check(
+        min_functions
+            .into_iter()
+            .flat_map(|(name, f)| {
+                let mut union = Boolean::new_union(vec![sphere(), cube()]).unwrap();
+                union.set_min_function(f.clone()).unwrap();
+                let mut intersection = Boolean::new_intersection(vec![sphere(), cube()]).unwrap();
+                intersection.set_min_function(f).unwrap();
+                [
+                    (name, union as Box<dyn Primitive>),
+                    (name, intersection as Box<dyn Primitive>),
+                ]
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn uniforms_keep_code() {
+    let objects = [(1., v(0.5, -1., 0.3), 3.), (2., v(-1., 0., 2.), 0.5)].map(|(r, t, k)| {
+        let mut union =
+            Boolean::new_union(vec![Sphere::new(r).unwrap().translate(t), cube()]).unwrap();
+        union
+            .set_min_function(Box::new(MinPolynomial::new(k).unwrap()))
+            .unwrap();
+        with_uniforms(&*Twist::new(union, k))
+    });
+    let [(code0, expression0, uniforms0), (code1, expression1, uniforms1)] = objects;
+    assert_eq!(code0, code1);
+    assert_eq!(expression0, expression1);
+    assert_ne!(uniforms0.values(), uniforms1.values());
+}
+
+#[test]
+fn bounding_volumes_skip_exact_children_only() {
+    let code = |children: Vec<Box<dyn Primitive>>| {
+        let mut code = Vec::new();
+        let union = Boolean::new_union(children).unwrap();
+        let expression = union.expression("p", &mut code).unwrap();
+        format!("{}{}", code.concat(), expression)
+    };
+    let exact = code(vec![sphere(), cube().rotate_euler(0.3, 0.6, -1.2)]);
+    assert!(exact.contains("BoundsDistance"));
+    let scaled = code(vec![sphere().scale(v(1., 1., 4.)), Twist::new(cube(), 3.)]);
+    assert!(!scaled.contains("BoundsDistance"));
+}
@@ -0,0 +1,126 @@
+//! Checks that primitive trees get described by their constructors and parameters.
+
+use sdfer::primitive::*;
+
+extern crate nalgebra_glm as glm;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn cube() -> Box<dyn Primitive> {
+    ExactBox::new(v(1., 1., 1.)).unwrap()
+}
+
+#[test]
+fn union() {
+    let mut union = Boolean::new_union(vec![
+        Sphere::new(1.5).unwrap(),
+        cube().translate(v(1., 0., 0.)),
+    ])
+    .unwrap();
+    union
+        .set_min_function(Box::new(MinPolynomial::new(0.2).unwrap()))
+        .unwrap();
+    let union: Box<dyn Primitive> = union;
+    let expected = "\
+Union(min_function: MinPolynomial(k: 0.2))
+  Sphere(radius: 1.5)
+  Translate(vector: [1.0, 0.0, 0.0])
+    ExactBox(size: [1.0, 1.0, 1.0])";
+    assert_eq!(describe(&*union), expected);
+    assert_eq!(format!("{:?}", union), expected);
+}
+
+#[test]
+fn intersection_and_difference() {
+    let sphere = || Sphere::new(0.6).unwrap();
+    let intersection = Boolean::new_intersection(vec![cube(), sphere()]).unwrap();
+    assert_eq!(
+        describe(&*intersection),
+        "\
+Intersection
+  ExactBox(size: [1.0, 1.0, 1.0])
+  Sphere(radius: 0.6)"
+    );
+
+    let difference =
+        Boolean::new_difference(vec![cube(), sphere(), Cylinder::new_infinite(0.2).unwrap()])
+            .unwrap();
+    let expected = "\
+Difference
+  ExactBox(size: [1.0, 1.0, 1.0])
+  Sphere(radius: 0.6)
+  Cylinder(radius: 0.2)";
+    assert_eq!(describe(&*difference), expected);
+    // Simplifying removes the double negation of the subtracted children.
+    assert_eq!(describe(&*difference.simplify()), expected);
+
+    // Intersecting a difference keeps the intersected children together.
+    let nested = Boolean::new_intersection(vec![difference, sphere().translate(v(0., 0., 1.))])
+        .unwrap()
+        .simplify();
+    assert_eq!(
+        describe(&*nested),
+        "\
+Difference
+  Intersection
+    ExactBox(size: [1.0, 1.0, 1.0])
+    Translate(vector: [0.0, 0.0, 1.0])
+      Sphere(radius: 0.6)
+  Sphere(radius: 0.6)
+  Cylinder(radius: 0.2)"
+    );
+}
+
+#[test]
+fn parameters() {
+    let cases = vec![
+        (
+            RoundBox::new(v(1., 2., 3.), 0.25).unwrap(),
+            "RoundBox(size: [1.0, 2.0, 3.0], radius: 0.25)",
+        ),
+        (
+            Torus::new_capped(0.5, 1.5, 2.).unwrap(),
+            "Torus(inner: 0.5, outer: 1.5, cap_angle: 2.0)",
+        ),
+        (
+            Cylinder::new(0.7, v(0., 0., -1.), v(0., 0., 1.)).unwrap(),
+            "Cylinder(radius: 0.7, begin: [0.0, 0.0, -1.0], end: [0.0, 0.0, 1.0])",
+        ),
+        (
+            Repeat::new(
+                Sphere::new(0.3).unwrap(),
+                v(1., 1., 1.),
+                glm::vec3(-2, 0, 0),
+                glm::vec3(1, 2, 0),
+            )
+            .unwrap(),
+            "\
+Repeat(bounds: [1.0, 1.0, 1.0], repeats_min: [-2, 0, 0], repeats_max: [1, 2, 0])
+  Sphere(radius: 0.3)",
+        ),
+        (
+            Part::new(cube(), "lid", Some(v(1., 0.5, 0.))).unwrap(),
+            "\
+Part(name: \"lid\", color: [1.0, 0.5, 0.0])
+  ExactBox(size: [1.0, 1.0, 1.0])",
+        ),
+        (
+            Rotate::from_axis_angle(cube(), v(0., 0., 2.), 0.5).unwrap(),
+            "\
+Rotate(axis: [0.0, 0.0, 1.0], angle: 0.5)
+  ExactBox(size: [1.0, 1.0, 1.0])",
+        ),
+        (
+            Transform::new(cube(), glm::translation(&v(1., 2., 3.))).unwrap(),
+            "\
+Transform(pose: [[1.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 2.0], [0.0, 0.0, 1.0, 3.0], \
+[0.0, 0.0, 0.0, 1.0]])
+  ExactBox(size: [1.0, 1.0, 1.0])",
+        ),
+    ];
+    for (primitive, expected) in cases {
+        assert_eq!(describe(&*primitive), expected);
+    }
+}
@@ -0,0 +1,126 @@
+//! Checks that dual contouring closes surfaces and keeps their sharp features.
+
+use sdfer::mesh::{mesh_primitive, MeshSettings, Resolution, TriangleMesh};
+use sdfer::primitive::*;
+use std::collections::HashMap;
+
+extern crate nalgebra_glm as glm;
+
+fn dual_contouring(p: &dyn Primitive) -> TriangleMesh {
+    let settings = MeshSettings {
+        resolution: Resolution::CellSize(0.1),
+        ..MeshSettings::default()
+    };
+    mesh_primitive(p, &settings).unwrap()
+}
+
+/// Panics unless every edge is shared by exactly two triangles, which run along it in opposite
+/// directions.
+fn assert_watertight(mesh: &TriangleMesh) {
+    assert!(!mesh.triangles.is_empty());
+    let mut edges = HashMap::<(u32, u32), usize>::new();
+    for t in &mesh.triangles {
+        for i in 0..3 {
+            *edges.entry((t[i], t[(i + 1) % 3])).or_default() += 1;
+        }
+    }
+    for (&(a, b), &count) in &edges {
+        assert_eq!(count, 1, "edge {} {} is used {} times", a, b, count);
+        assert!(edges.contains_key(&(b, a)), "edge {} {} is open", a, b);
+    }
+}
+
+#[test]
+fn watertight() {
+    let sphere = Sphere::new(1.).unwrap();
+    assert_watertight(&dual_contouring(&*sphere));
+
+    let cube = ExactBox::new(glm::vec3(1.9, 1.7, 1.3)).unwrap();
+    assert_watertight(&dual_contouring(&*cube));
+
+    // Clipped by the meshed region.
+    let plane = Plane::new(glm::vec3(0., 0., 1.), 0.).unwrap();
+    assert_watertight(&dual_contouring(&*plane));
+
+    let torus = Torus::new(0.3, 1.).unwrap();
+    let union = Boolean::new_union(vec![torus, Sphere::new(0.9).unwrap()]).unwrap();
+    assert_watertight(&dual_contouring(&*union));
+
+    // Tiny balls above a large face barely add to the error of merging them into the face,
+    // which would join their surfaces.
+    let balls = Repeat::new(
+        Sphere::new(0.02).unwrap(),
+        glm::vec3(0.37, 0.41, 1.),
+        glm::vec3(-5, -5, 0),
+        glm::vec3(5, 5, 0),
+    )
+    .unwrap()
+    .translate(glm::vec3(0., 0., 0.09));
+    let ground = ExactBox::new(glm::vec3(4., 4., 1.))
+        .unwrap()
+        .translate(glm::vec3(0., 0., -0.5));
+    let union = Boolean::new_union(vec![ground, balls]).unwrap();
+    assert_watertight(&dual_contouring(&*union));
+}
+
+#[test]
+fn sharp_cube_edges() {
+    // The size is no multiple of the cell size, so edges and corners fall inside cells.
+    let half_size = glm::vec3(0.95, 0.85, 0.65);
+    let cube = ExactBox::new(half_size * 2.).unwrap();
+    let mesh = dual_contouring(&*cube);
+    let on_face = |v: &glm::Vec3, i: usize, side: f32| (v[i] - side * half_size[i]).abs() < 1e-3;
+    let on_faces = |v: &glm::Vec3| {
+        (0..3)
+            .filter(|&i| on_face(v, i, 1.) || on_face(v, i, -1.))
+            .count()
+    };
+    // Every corner gets a vertex.
+    assert_eq!(mesh.vertices.iter().filter(|v| on_faces(v) == 3).count(), 8);
+    // No triangle cuts across an edge, so the edges stay sharp.
+    for t in &mesh.triangles {
+        let v = t.map(|i| mesh.vertices[i as usize]);
+        assert!(
+            (0..3).any(|i| [1., -1.]
+                .iter()
+                .any(|&side| v.iter().all(|v| on_face(v, i, side)))),
+            "{:?} is not within a face",
+            v.map(|v| v.as_slice().to_vec())
+        );
+    }
+}
+
+#[test]
+fn adaptive() {
+    // Flat faces get merged into large triangles, while a uniform grid would need two per cell
+    // on the surface.
+    let cube = ExactBox::new(glm::vec3(1.9, 1.7, 1.3)).unwrap();
+    let triangles = dual_contouring(&*cube).triangles.len();
+    let area = 2. * (1.9 * 1.7 + 1.9 * 1.3 + 1.7 * 1.3);
+    let uniform_triangles = (2. * area / (0.1 * 0.1)) as usize;
+    assert!(
+        triangles * 10 < uniform_triangles,
+        "{} triangles",
+        triangles
+    );
+
+    // Curved surfaces keep their detail.
+    let sphere = Sphere::new(1.).unwrap();
+    let mesh = dual_contouring(&*sphere);
+    for v in &mesh.vertices {
+        let d = sphere.eval(*v);
+        assert!(
+            d.abs() < 0.01,
+            "{:?} is {} off the surface",
+            v.as_slice(),
+            d
+        );
+    }
+    for t in &mesh.triangles {
+        for i in 0..3 {
+            let (a, b) = (t[i] as usize, t[(i + 1) % 3] as usize);
+            let length = glm::distance(&mesh.vertices[a], &mesh.vertices[b]);
+            assert!(length < 0.5, "triangle edge of {}", length);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Checks that the ellipsoid distance is a bound on the exact distance, and a close one.
+
+use sdfer::primitive::*;
+use sdfer::script_engine::{RhaiScriptEngine, ScriptEngine};
+
+extern crate nalgebra_glm as glm;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn points() -> impl Iterator<Item = glm::Vec3> {
+    (0..1000).map(|i| {
+        let t = i as f32;
+        v(t.sin() * 3., (t * 1.3).cos() * 3., (t * 0.7).sin() * 3.)
+    })
+}
+
+/// The closest point on the surface is `p_i r_i² / (r_i² + t)` for the largest `t` putting it
+/// on the surface, which gets found by bisection.
+fn exact_distance(p: glm::Vec3, r: glm::Vec3) -> f32 {
+    let (p, r) = (
+        glm::convert::<_, glm::DVec3>(p),
+        glm::convert::<_, glm::DVec3>(r),
+    );
+    let r2 = r.component_mul(&r);
+    let closest = |t: f64| p.component_mul(&r2).component_div(&r2.add_scalar(t));
+    let outside = |t: f64| closest(t).component_div(&r).norm() > 1.;
+    let (mut low, mut high) = (-r2.min(), p.norm() * r.max());
+    for _ in 0..200 {
+        let t = (low + high) / 2.;
+        if outside(t) {
+            low = t;
+        } else {
+            high = t;
+        }
+    }
+    let d = (p - closest(low)).norm() as f32;
+    if p.component_div(&r).norm() > 1. {
+        d
+    } else {
+        -d
+    }
+}
+
+#[test]
+fn distance_bound() {
+    for radii in [v(1., 0.5, 0.3), v(2., 0.2, 1.), v(0.4, 1.5, 1.)] {
+        let ellipsoid = Ellipsoid::new(radii).unwrap();
+        let scaled_sphere = Sphere::new(1.).unwrap().scale(radii);
+        let (mut ellipsoid_total, mut scaled_total) = (0., 0.);
+        for p in points() {
+            let exact = exact_distance(p, radii);
+            let d = ellipsoid.eval(p);
+            assert!(d * exact >= 0., "sign at {:?}", p.as_slice());
+            assert!(
+                d.abs() <= exact.abs() + 1e-5,
+                "{} > {} at {:?}",
+                d,
+                exact,
+                p
+            );
+            ellipsoid_total += d / exact;
+            scaled_total += scaled_sphere.eval(p) / exact;
+            let q = p + v(p[1], p[2], -p[0]) * 1e-2;
+            assert!((ellipsoid.eval(q) - d).abs() <= (q - p).norm() * 1.001);
+        }
+        assert!(ellipsoid_total > scaled_total);
+    }
+    // Spheres are exact.
+    let sphere = Ellipsoid::new(v(0.7, 0.7, 0.7)).unwrap();
+    for p in points() {
+        assert!((sphere.eval(p) - (p.norm() - 0.7)).abs() < 1e-5);
+    }
+}
+
+/// Estimates the gradient of `eval` by central differences with a step of `e`.
+fn central_difference(primitive: &dyn Primitive, p: glm::Vec3, e: f32) -> glm::Vec3 {
+    let d = |o: glm::Vec3| primitive.eval(p + o) - primitive.eval(p - o);
+    v(d(v(e, 0., 0.)), d(v(0., e, 0.)), d(v(0., 0., e))) / (2. * e)
+}
+
+#[test]
+fn gradient() {
+    for radii in [v(1., 0.5, 0.3), v(2., 0.2, 1.), v(0.4, 1.5, 1.)] {
+        let ellipsoid = Ellipsoid::new(radii).unwrap();
+        let mut checked = 0;
+        for p in points() {
+            let estimate = central_difference(&*ellipsoid, p, 1e-3);
+            // Skip points where the largest bound changes, the gradient jumps there.
+            if (estimate - central_difference(&*ellipsoid, p, 1e-2)).norm() > 1e-2 {
+                continue;
+            }
+            let g = ellipsoid.gradient(p, 1e-4);
+            assert!(
+                (g - estimate).norm() < 1e-2,
+                "{:?} != {:?} at {:?}",
+                g.as_slice(),
+                estimate.as_slice(),
+                p.as_slice()
+            );
+            checked += 1;
+        }
+        assert!(checked > 900, "only {} points checked", checked);
+    }
+}
+
+#[test]
+fn ellipsoids_in_scripts() {
+    let engine = RhaiScriptEngine::new();
+    let ellipsoid = engine.eval("Ellipsoid(2., 1., 0.5)").unwrap();
+    assert!((ellipsoid.eval(v(3., 0., 0.)) - 1.).abs() < 1e-5);
+    assert!((ellipsoid.eval(v(0., 0., 1.)) - 0.5).abs() < 1e-5);
+    assert!(ellipsoid.eval(v(1.9, 0., 0.)) < 0.);
+    assert!(engine.eval("Ellipsoid(2., 0., 0.5)").is_err());
+}
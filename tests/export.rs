@@ -0,0 +1,225 @@
+//! Checks what the mesh writers put into their files.
+
+use sdfer::export::*;
+use sdfer::mesh::{mesh_parts, MeshSettings, Resolution, TriangleMesh};
+use sdfer::primitive::*;
+use std::io::{Cursor, Read};
+
+extern crate nalgebra_glm as glm;
+
+/// A tetrahedron with its triangles facing outwards.
+fn tetrahedron() -> TriangleMesh {
+    let vertices = vec![
+        glm::vec3(0., 0., 0.),
+        glm::vec3(1., 0., 0.),
+        glm::vec3(0., 1., 0.),
+        glm::vec3(0., 0., 1.),
+    ];
+    let normals = vertices
+        .iter()
+        .map(|v| (v - glm::vec3(0.25, 0.25, 0.25)).normalize())
+        .collect();
+    TriangleMesh {
+        vertices,
+        normals,
+        triangles: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+    }
+}
+
+fn read_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks(4)
+        .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn binary_stl() {
+    assert_eq!(Format::from_extension("STL").unwrap(), Format::BinaryStl);
+    let mesh = tetrahedron();
+    let mut bytes = Vec::new();
+    write_mesh(&mesh, Format::BinaryStl, &mut bytes).unwrap();
+    assert!(bytes[..80].starts_with(b"sdfer"));
+    assert_eq!(u32::from_le_bytes(bytes[80..84].try_into().unwrap()), 4);
+    assert_eq!(bytes.len(), 84 + 4 * 50);
+    for (triangle, record) in mesh.triangles.iter().zip(bytes[84..].chunks(50)) {
+        let values = read_f32s(&record[..48]);
+        assert_eq!(values[..3], *mesh.face_normal(triangle).as_slice());
+        for (i, v) in triangle.iter().zip(values[3..].chunks(3)) {
+            assert_eq!(v, mesh.vertices[*i as usize].as_slice());
+        }
+        assert_eq!(record[48..], [0, 0]);
+    }
+}
+
+#[test]
+fn ascii_stl() {
+    let mesh = tetrahedron();
+    let mut bytes = Vec::new();
+    write_mesh(&mesh, Format::AsciiStl, &mut bytes).unwrap();
+    let text = String::from_utf8(bytes).unwrap();
+    let lines = text.lines().map(str::trim).collect::<Vec<_>>();
+    assert_eq!(lines.first(), Some(&"solid sdfer"));
+    assert_eq!(lines.last(), Some(&"endsolid sdfer"));
+    let count = |prefix: &str| lines.iter().filter(|l| l.starts_with(prefix)).count();
+    assert_eq!(count("facet normal"), 4);
+    assert_eq!(count("endfacet"), 4);
+    let vertices = lines
+        .iter()
+        .filter_map(|l| l.strip_prefix("vertex "))
+        .map(|v| {
+            v.split(' ')
+                .map(|c| c.parse::<f32>().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let expected = mesh
+        .triangles
+        .iter()
+        .flatten()
+        .map(|i| mesh.vertices[*i as usize].as_slice().to_vec())
+        .collect::<Vec<_>>();
+    assert_eq!(vertices, expected);
+}
+
+#[test]
+fn obj() {
+    let mesh = tetrahedron();
+    let mut bytes = Vec::new();
+    write_mesh(&mesh, Format::Obj, &mut bytes).unwrap();
+    let text = String::from_utf8(bytes).unwrap();
+    let rows = |prefix: &str| {
+        text.lines()
+            .filter_map(|l| l.strip_prefix(prefix))
+            .map(|l| l.split(' ').collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    let vectors = |prefix: &str| {
+        rows(prefix)
+            .iter()
+            .map(|r| glm::Vec3::from_iterator(r.iter().map(|c| c.parse().unwrap())))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(vectors("v "), mesh.vertices);
+    assert_eq!(vectors("vn "), mesh.normals);
+    // Shared vertices keep their 1-based index, together with their normal.
+    for (triangle, face) in mesh.triangles.iter().zip(rows("f ")) {
+        let indices = face
+            .iter()
+            .map(|c| {
+                let (v, n) = c.split_once("//").unwrap();
+                assert_eq!(v, n);
+                v.parse::<u32>().unwrap() - 1
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices, triangle);
+    }
+    assert_eq!(rows("f ").len(), 4);
+}
+
+#[test]
+fn ply() {
+    let mesh = tetrahedron();
+    let mut bytes = Vec::new();
+    write_mesh(&mesh, Format::Ply, &mut bytes).unwrap();
+    let end = b"end_header\n";
+    let header_length = bytes.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+    let header = std::str::from_utf8(&bytes[..header_length]).unwrap();
+    assert!(header.starts_with("ply\nformat binary_little_endian 1.0\n"));
+    assert!(header.contains("element vertex 4\n"));
+    assert!(header.contains("element face 4\n"));
+    assert!(header.contains("property float nz\n"));
+
+    let (vertices, faces) = bytes[header_length..].split_at(4 * 6 * 4);
+    for (i, values) in read_f32s(vertices).chunks(6).enumerate() {
+        assert_eq!(values[..3], *mesh.vertices[i].as_slice());
+        assert_eq!(values[3..], *mesh.normals[i].as_slice());
+    }
+    assert_eq!(faces.len(), 4 * 13);
+    for (triangle, face) in mesh.triangles.iter().zip(faces.chunks(13)) {
+        assert_eq!(face[0], 3);
+        let indices = face[1..]
+            .chunks(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(indices, triangle);
+    }
+}
+
+fn sphere(x: f32) -> Box<dyn Primitive> {
+    Sphere::new(0.5).unwrap().translate(glm::vec3(x, 0., 0.))
+}
+
+#[test]
+fn parts() {
+    let red = glm::vec3(1., 0., 0.);
+    let ball = Part::new(sphere(0.), "ball", Some(red)).unwrap();
+    let knob = Part::new(sphere(2.), "knob", None).unwrap();
+    let union = Boolean::new_union(vec![ball.clone(), sphere(-2.), knob, sphere(4.)]).unwrap();
+    let parts = split_parts(&*union).unwrap();
+    let names = parts.iter().map(|p| p.name()).collect::<Vec<_>>();
+    assert_eq!(names, ["ball", "knob", "sdfer"]);
+    assert_eq!(parts[0].color(), Some(red));
+    // The unnamed children are collected into the last part.
+    assert!(parts[2].eval(glm::vec3(-2., 0., 0.)) < 0.);
+    assert!(parts[2].eval(glm::vec3(4., 0., 0.)) < 0.);
+    assert!(parts[2].eval(glm::vec3(0., 0., 0.)) > 0.);
+
+    assert_eq!(split_parts(&*ball).unwrap()[0].name(), "ball");
+    let unnamed = Boolean::new_union(vec![sphere(0.), sphere(2.)]).unwrap();
+    for p in [&*unnamed, &*sphere(0.)] {
+        let parts = split_parts(p).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].name(), "sdfer");
+    }
+}
+
+/// Returns the model of a 3MF package, after checking the files it consists of.
+fn read_model(bytes: Vec<u8>) -> String {
+    let mut package = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let mut files = package.file_names().collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(
+        files,
+        ["3D/3dmodel.model", "[Content_Types].xml", "_rels/.rels"]
+    );
+    let mut model = String::new();
+    package
+        .by_name("3D/3dmodel.model")
+        .unwrap()
+        .read_to_string(&mut model)
+        .unwrap();
+    model
+}
+
+#[test]
+fn three_mf() {
+    let ball = Part::new(sphere(0.), "ball & <chain>", Some(glm::vec3(1., 0.5, 0.))).unwrap();
+    let knob = Part::new(sphere(2.), "knob", None).unwrap();
+    let union = Boolean::new_union(vec![ball, knob]).unwrap();
+    let settings = MeshSettings {
+        resolution: Resolution::Depth(4),
+        ..MeshSettings::default()
+    };
+    let parts = mesh_parts(&*union, &settings).unwrap();
+    let mut bytes = Vec::new();
+    write_3mf(&parts, &mut bytes).unwrap();
+    let model = read_model(bytes);
+    let count = |tag: &str| model.matches(tag).count();
+    assert_eq!(count("<object "), 2);
+    assert_eq!(count("<item "), 2);
+    assert_eq!(count("<base "), 1);
+    assert!(model.contains(r##"name="ball &amp; &lt;chain&gt;" displaycolor="#FF8000FF""##));
+    let vertices: usize = parts.iter().map(|p| p.mesh.vertices.len()).sum();
+    let triangles: usize = parts.iter().map(|p| p.mesh.triangles.len()).sum();
+    assert_eq!(count("<vertex "), vertices);
+    assert_eq!(count("<triangle "), triangles);
+    assert!(triangles > 0);
+
+    // A single mesh becomes a single object.
+    let mut bytes = Vec::new();
+    write_mesh(&tetrahedron(), Format::ThreeMf, &mut bytes).unwrap();
+    let model = read_model(bytes);
+    assert_eq!(model.matches("<object ").count(), 1);
+    assert_eq!(model.matches("<triangle ").count(), 4);
+}
@@ -0,0 +1,737 @@
+//! A tree walking interpreter for the subset of GLSL that primitives generate.
+//!
+//! It knows just enough of the language to run the shared functions and distance expressions:
+//! float, vector, matrix and array values, declarations, (compound) assignments, `if`/`else`,
+//! counting `for` loops, `return`, the ternary operator, swizzles and the builtin functions used
+//! by the shaders.
+//! The syntax tree is shared with the WGSL front end in `wgsl`.
+
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Float(f32),
+    Bool(bool),
+    /// A vec2, vec3 or vec4.
+    Vector(Vec<f32>),
+    /// A square matrix, stored column by column.
+    Matrix(usize, Vec<f32>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn float(&self) -> f32 {
+        match self {
+            Value::Float(f) => *f,
+            _ => panic!("expected float, got {:?}", self),
+        }
+    }
+    fn bool(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            _ => panic!("expected bool, got {:?}", self),
+        }
+    }
+    fn components(&self) -> Vec<f32> {
+        match self {
+            Value::Float(f) => vec![*f],
+            Value::Vector(v) | Value::Matrix(_, v) => v.clone(),
+            Value::Bool(_) | Value::Array(_) => panic!("expected number, got {:?}", self),
+        }
+    }
+    fn index(&self, i: usize) -> Value {
+        match self {
+            Value::Array(values) => values[i].clone(),
+            Value::Matrix(n, m) => Value::Vector(m[i * n..(i + 1) * n].to_vec()),
+            Value::Vector(v) => Value::Float(v[i]),
+            _ => panic!("cannot index {:?}", self),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 31] = [
+    "++", "->", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "(", ")", "{", "}",
+    "[", "]", ",", ";", ".", "+", "-", "*", "/", "<", ">", "=", "?", ":", "!",
+];
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest = chars[i..].iter().collect::<String>();
+        if c.is_whitespace() {
+            i += 1;
+        } else if rest.starts_with("//") || rest.starts_with('#') {
+            // Comments and preprocessor lines.
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if chars[i] == '-' || chars[i] == '+' {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+            }
+            let number = chars[start..i].iter().collect::<String>();
+            tokens.push(Token::Number(number.parse().unwrap()));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(*s))
+                .unwrap_or_else(|| panic!("unexpected character {:?}", c));
+            tokens.push(Token::Symbol(symbol));
+            i += symbol.len();
+        }
+    }
+    tokens
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Number(f32),
+    Variable(String),
+    Call(String, Vec<Expr>),
+    Swizzle(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug)]
+pub enum Statement {
+    Declare(Vec<(String, Option<Expr>)>),
+    Assign(String, Option<String>, &'static str, Expr),
+    If(Expr, Box<Statement>, Option<Box<Statement>>),
+    /// Initialization, condition, step and body.
+    For(Box<Statement>, Expr, Box<Statement>, Box<Statement>),
+    Block(Vec<Statement>),
+    Return(Expr),
+}
+
+#[derive(Clone, Debug)]
+pub struct Function {
+    pub parameters: Vec<String>,
+    pub body: Vec<Statement>,
+}
+
+const TYPES: [&str; 10] = [
+    "float", "int", "vec2", "vec3", "vec4", "mat2", "mat3", "mat4", "bool", "void",
+];
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    pub fn new(source: &str) -> Parser {
+        Parser {
+            tokens: tokenize(source),
+            position: 0,
+        }
+    }
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    pub fn next(&mut self) -> Token {
+        self.position += 1;
+        self.tokens[self.position - 1].clone()
+    }
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+    fn is_type(&self) -> bool {
+        matches!(self.peek(), Some(Token::Ident(i)) if TYPES.contains(&i.as_str()))
+    }
+    pub fn expect(&mut self, symbol: &str) {
+        let token = self.next();
+        assert_eq!(
+            token,
+            Token::Symbol(SYMBOLS.iter().find(|s| **s == symbol).unwrap())
+        );
+    }
+    pub fn ident(&mut self) -> String {
+        match self.next() {
+            Token::Ident(i) => i,
+            t => panic!("expected identifier, got {:?}", t),
+        }
+    }
+
+    fn function(&mut self) -> (String, Function) {
+        self.ident();
+        let name = self.ident();
+        self.expect("(");
+        let mut parameters = Vec::new();
+        while !self.is_symbol(")") {
+            if matches!(self.peek(), Some(Token::Ident(i)) if i == "in") {
+                self.next();
+            }
+            self.ident();
+            parameters.push(self.ident());
+            if self.is_symbol(",") {
+                self.next();
+            }
+        }
+        self.expect(")");
+        let body = match self.statement() {
+            Statement::Block(body) => body,
+            s => panic!("expected function body, got {:?}", s),
+        };
+        (name, Function { parameters, body })
+    }
+
+    fn statement(&mut self) -> Statement {
+        if self.is_symbol("{") {
+            self.next();
+            let mut statements = Vec::new();
+            while !self.is_symbol("}") {
+                statements.push(self.statement());
+            }
+            self.next();
+            return Statement::Block(statements);
+        }
+        if self.is_type() {
+            self.next();
+            let mut declarations = Vec::new();
+            loop {
+                let name = self.ident();
+                if self.is_symbol("[") {
+                    // The size of an array.
+                    self.next();
+                    self.next();
+                    self.expect("]");
+                }
+                let value = if self.is_symbol("=") {
+                    self.next();
+                    Some(self.expression())
+                } else {
+                    None
+                };
+                declarations.push((name, value));
+                if self.is_symbol(";") {
+                    break;
+                }
+                self.expect(",");
+            }
+            self.expect(";");
+            return Statement::Declare(declarations);
+        }
+        let keyword = self.ident();
+        match keyword.as_str() {
+            "return" => {
+                let value = self.expression();
+                self.expect(";");
+                Statement::Return(value)
+            }
+            "if" => {
+                self.expect("(");
+                let condition = self.expression();
+                self.expect(")");
+                let then = Box::new(self.statement());
+                let otherwise = if matches!(self.peek(), Some(Token::Ident(i)) if i == "else") {
+                    self.next();
+                    Some(Box::new(self.statement()))
+                } else {
+                    None
+                };
+                Statement::If(condition, then, otherwise)
+            }
+            "for" => {
+                self.expect("(");
+                let initialization = Box::new(self.statement());
+                let condition = self.expression();
+                self.expect(";");
+                let step = Box::new(self.step());
+                self.expect(")");
+                Statement::For(initialization, condition, step, Box::new(self.statement()))
+            }
+            _ => {
+                let swizzle = if self.is_symbol(".") {
+                    self.next();
+                    Some(self.ident())
+                } else {
+                    None
+                };
+                let operator = match self.next() {
+                    Token::Symbol(s) if ["=", "+=", "-=", "*=", "/="].contains(&s) => s,
+                    t => panic!("expected assignment, got {:?}", t),
+                };
+                let value = self.expression();
+                self.expect(";");
+                Statement::Assign(keyword, swizzle, operator, value)
+            }
+        }
+    }
+
+    /// Parses the `i++` closing the head of a loop.
+    pub fn step(&mut self) -> Statement {
+        let name = self.ident();
+        self.expect("++");
+        Statement::Assign(name, None, "+=", Expr::Number(1.))
+    }
+
+    /// Parses the arguments of a call after the opening parenthesis.
+    fn arguments(&mut self) -> Vec<Expr> {
+        let mut arguments = Vec::new();
+        while !self.is_symbol(")") {
+            arguments.push(self.expression());
+            if self.is_symbol(",") {
+                self.next();
+            }
+        }
+        self.next();
+        arguments
+    }
+
+    pub fn expression(&mut self) -> Expr {
+        let condition = self.binary(0);
+        if self.is_symbol("?") {
+            self.next();
+            let then = self.expression();
+            self.expect(":");
+            let otherwise = self.expression();
+            return Expr::Ternary(Box::new(condition), Box::new(then), Box::new(otherwise));
+        }
+        condition
+    }
+
+    fn binary(&mut self, min_precedence: usize) -> Expr {
+        const PRECEDENCE: [&[&str]; 5] = [
+            &["||"],
+            &["&&"],
+            &["==", "!=", "<", ">", "<=", ">="],
+            &["+", "-"],
+            &["*", "/"],
+        ];
+        let mut lhs = self.unary();
+        while let Some(Token::Symbol(operator)) = self.peek() {
+            let operator = *operator;
+            let Some(precedence) = PRECEDENCE.iter().position(|ops| ops.contains(&operator)) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.next();
+            let rhs = self.binary(precedence + 1);
+            lhs = Expr::Binary(operator, Box::new(lhs), Box::new(rhs));
+        }
+        lhs
+    }
+
+    fn unary(&mut self) -> Expr {
+        if self.is_symbol("-") || self.is_symbol("!") {
+            let operator = match self.next() {
+                Token::Symbol(s) => s,
+                _ => unreachable!(),
+            };
+            return Expr::Unary(operator, Box::new(self.unary()));
+        }
+        if self.is_symbol("+") {
+            self.next();
+            return self.unary();
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Expr {
+        let mut expr = match self.next() {
+            Token::Number(n) => Expr::Number(n),
+            Token::Symbol("(") => {
+                let expr = self.expression();
+                self.expect(")");
+                expr
+            }
+            Token::Ident(name)
+                if (TYPES.contains(&name.as_str()) && self.is_symbol("["))
+                    || (name == "array" && self.is_symbol("<")) =>
+            {
+                // The array constructors `vec3[2](a, b)` of GLSL and `array<vec3f, 2>(a, b)` of
+                // WGSL.
+                while !self.is_symbol("(") {
+                    self.next();
+                }
+                self.next();
+                Expr::Call("array".to_string(), self.arguments())
+            }
+            Token::Ident(name) if self.is_symbol("(") => {
+                self.next();
+                Expr::Call(name, self.arguments())
+            }
+            Token::Ident(name) => Expr::Variable(name),
+            t => panic!("unexpected token {:?}", t),
+        };
+        loop {
+            if self.is_symbol(".") {
+                self.next();
+                expr = Expr::Swizzle(Box::new(expr), self.ident());
+            } else if self.is_symbol("[") {
+                self.next();
+                let index = self.expression();
+                self.expect("]");
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return expr;
+            }
+        }
+    }
+}
+
+/// A compiled set of shared functions, ready to evaluate expressions.
+pub struct Program {
+    functions: HashMap<String, Function>,
+    /// Variables in scope everywhere, like uniforms.
+    globals: Vec<(String, Value)>,
+}
+
+impl Program {
+    /// Parses the function definitions in `shared_code`.
+    pub fn new(shared_code: &[String]) -> Program {
+        let mut functions = HashMap::new();
+        for code in shared_code {
+            let mut parser = Parser::new(code);
+            while parser.peek().is_some() {
+                let (name, function) = parser.function();
+                functions.insert(name, function);
+            }
+        }
+        Program::with_functions(functions)
+    }
+
+    pub fn with_functions(functions: HashMap<String, Function>) -> Program {
+        Program {
+            functions,
+            globals: Vec::new(),
+        }
+    }
+
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.push((name.to_string(), value));
+    }
+
+    /// Evaluates `expression` with the given variables in scope.
+    pub fn eval(&self, expression: &str, variables: &[(&str, Value)]) -> Value {
+        let mut parser = Parser::new(expression);
+        let expr = parser.expression();
+        assert!(parser.peek().is_none(), "trailing tokens in {}", expression);
+        self.eval_expr(&expr, variables)
+    }
+
+    /// Evaluates a parsed expression with the given variables in scope.
+    pub fn eval_expr(&self, expr: &Expr, variables: &[(&str, Value)]) -> Value {
+        let mut scope = self
+            .globals
+            .iter()
+            .cloned()
+            .chain(
+                variables
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone())),
+            )
+            .collect();
+        self.expr(expr, &mut scope)
+    }
+
+    fn call(&self, name: &str, arguments: Vec<Value>) -> Value {
+        if let Some(function) = self.functions.get(name) {
+            let mut scope = self
+                .globals
+                .iter()
+                .cloned()
+                .chain(function.parameters.iter().cloned().zip(arguments))
+                .collect::<HashMap<_, _>>();
+            return self
+                .block(&function.body, &mut scope)
+                .unwrap_or_else(|| panic!("{} did not return", name));
+        }
+        builtin(name, arguments)
+    }
+
+    fn block(&self, statements: &[Statement], scope: &mut HashMap<String, Value>) -> Option<Value> {
+        statements.iter().find_map(|s| self.statement(s, scope))
+    }
+
+    fn statement(
+        &self,
+        statement: &Statement,
+        scope: &mut HashMap<String, Value>,
+    ) -> Option<Value> {
+        match statement {
+            Statement::Declare(declarations) => {
+                for (name, value) in declarations {
+                    let value = value
+                        .as_ref()
+                        .map_or(Value::Float(0.), |v| self.expr(v, scope));
+                    scope.insert(name.clone(), value);
+                }
+                None
+            }
+            Statement::Assign(name, swizzle, operator, value) => {
+                let value = self.expr(value, scope);
+                let old = scope[name].clone();
+                let target = match swizzle {
+                    Some(s) => swizzle_value(&old, s),
+                    None => old.clone(),
+                };
+                let value = match *operator {
+                    "=" => value,
+                    op => arithmetic(&op[..1], target, value),
+                };
+                let new = match swizzle {
+                    Some(s) => {
+                        let mut components = old.components();
+                        for (c, v) in s.chars().zip(value.components()) {
+                            components[component_index(c)] = v;
+                        }
+                        Value::Vector(components)
+                    }
+                    None => value,
+                };
+                scope.insert(name.clone(), new);
+                None
+            }
+            Statement::If(condition, then, otherwise) => {
+                if self.expr(condition, scope).bool() {
+                    self.statement(then, scope)
+                } else {
+                    otherwise.as_ref().and_then(|s| self.statement(s, scope))
+                }
+            }
+            Statement::For(initialization, condition, step, body) => {
+                self.statement(initialization, scope);
+                while self.expr(condition, scope).bool() {
+                    if let Some(value) = self.statement(body, scope) {
+                        return Some(value);
+                    }
+                    self.statement(step, scope);
+                }
+                None
+            }
+            Statement::Block(statements) => self.block(statements, scope),
+            Statement::Return(value) => Some(self.expr(value, scope)),
+        }
+    }
+
+    fn expr(&self, expr: &Expr, scope: &mut HashMap<String, Value>) -> Value {
+        match expr {
+            Expr::Number(n) => Value::Float(*n),
+            Expr::Variable(name) => scope
+                .get(name)
+                .unwrap_or_else(|| panic!("unknown variable {}", name))
+                .clone(),
+            Expr::Call(name, arguments) => {
+                let arguments = arguments.iter().map(|a| self.expr(a, scope)).collect();
+                self.call(name, arguments)
+            }
+            Expr::Swizzle(value, swizzle) => swizzle_value(&self.expr(value, scope), swizzle),
+            Expr::Index(value, index) => {
+                let index = self.expr(index, scope).float() as usize;
+                self.expr(value, scope).index(index)
+            }
+            Expr::Unary(operator, value) => {
+                let value = self.expr(value, scope);
+                match *operator {
+                    "-" => map(&value, |x| -x),
+                    _ => Value::Bool(!value.bool()),
+                }
+            }
+            Expr::Binary(operator, lhs, rhs) => {
+                let lhs = self.expr(lhs, scope);
+                let rhs = self.expr(rhs, scope);
+                match *operator {
+                    "&&" => Value::Bool(lhs.bool() && rhs.bool()),
+                    "||" => Value::Bool(lhs.bool() || rhs.bool()),
+                    "==" => Value::Bool(lhs == rhs),
+                    "!=" => Value::Bool(lhs != rhs),
+                    "<" => Value::Bool(lhs.float() < rhs.float()),
+                    ">" => Value::Bool(lhs.float() > rhs.float()),
+                    "<=" => Value::Bool(lhs.float() <= rhs.float()),
+                    ">=" => Value::Bool(lhs.float() >= rhs.float()),
+                    op => arithmetic(op, lhs, rhs),
+                }
+            }
+            Expr::Ternary(condition, then, otherwise) => {
+                if self.expr(condition, scope).bool() {
+                    self.expr(then, scope)
+                } else {
+                    self.expr(otherwise, scope)
+                }
+            }
+        }
+    }
+}
+
+fn component_index(c: char) -> usize {
+    match c {
+        'x' | 'r' => 0,
+        'y' | 'g' => 1,
+        'z' | 'b' => 2,
+        'w' | 'a' => 3,
+        _ => panic!("invalid swizzle component {}", c),
+    }
+}
+
+fn swizzle_value(value: &Value, swizzle: &str) -> Value {
+    let components = value.components();
+    let result = swizzle
+        .chars()
+        .map(|c| components[component_index(c)])
+        .collect::<Vec<_>>();
+    if result.len() == 1 {
+        Value::Float(result[0])
+    } else {
+        Value::Vector(result)
+    }
+}
+
+fn map(value: &Value, f: impl Fn(f32) -> f32) -> Value {
+    match value {
+        Value::Float(x) => Value::Float(f(*x)),
+        Value::Vector(v) => Value::Vector(v.iter().map(|x| f(*x)).collect()),
+        Value::Matrix(n, v) => Value::Matrix(*n, v.iter().map(|x| f(*x)).collect()),
+        Value::Bool(_) | Value::Array(_) => panic!("expected number, got {:?}", value),
+    }
+}
+
+/// Applies `f` component wise, repeating scalars as needed.
+fn zip(a: &Value, b: &Value, f: impl Fn(f32, f32) -> f32) -> Value {
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => Value::Float(f(*a, *b)),
+        (Value::Float(a), v) => map(v, |b| f(*a, b)),
+        (v, Value::Float(b)) => map(v, |a| f(a, *b)),
+        (Value::Vector(a), Value::Vector(b)) => {
+            assert_eq!(a.len(), b.len());
+            Value::Vector(a.iter().zip(b).map(|(a, b)| f(*a, *b)).collect())
+        }
+        _ => panic!("cannot combine {:?} and {:?}", a, b),
+    }
+}
+
+fn arithmetic(operator: &str, lhs: Value, rhs: Value) -> Value {
+    match (operator, &lhs, &rhs) {
+        ("*", Value::Matrix(n, m), Value::Vector(v)) => {
+            assert_eq!(*n, v.len());
+            Value::Vector(
+                (0..*n)
+                    .map(|row| (0..*n).map(|col| m[col * n + row] * v[col]).sum())
+                    .collect(),
+            )
+        }
+        ("*", Value::Vector(v), Value::Matrix(n, m)) => {
+            assert_eq!(*n, v.len());
+            Value::Vector(
+                (0..*n)
+                    .map(|col| (0..*n).map(|row| v[row] * m[col * n + row]).sum())
+                    .collect(),
+            )
+        }
+        ("+", ..) => zip(&lhs, &rhs, |a, b| a + b),
+        ("-", ..) => zip(&lhs, &rhs, |a, b| a - b),
+        ("*", ..) => zip(&lhs, &rhs, |a, b| a * b),
+        ("/", ..) => zip(&lhs, &rhs, |a, b| a / b),
+        _ => panic!("unsupported operator {}", operator),
+    }
+}
+
+fn construct(size: usize, arguments: &[Value]) -> Vec<f32> {
+    let components = arguments
+        .iter()
+        .flat_map(|a| a.components())
+        .collect::<Vec<_>>();
+    if components.len() == 1 {
+        vec![components[0]; size]
+    } else {
+        assert_eq!(components.len(), size, "wrong number of components");
+        components
+    }
+}
+
+fn builtin(name: &str, a: Vec<Value>) -> Value {
+    let glsl_mod = |x: f32, y: f32| x - y * (x / y).floor();
+    match (name, a.len()) {
+        ("array", _) => Value::Array(a),
+        ("float", 1) => Value::Float(a[0].components()[0]),
+        ("vec2", _) => Value::Vector(construct(2, &a)),
+        ("vec3", _) => Value::Vector(construct(3, &a)),
+        ("vec4", _) => Value::Vector(construct(4, &a)),
+        ("mat2", _) => Value::Matrix(2, construct(4, &a)),
+        ("mat3", _) => Value::Matrix(3, construct(9, &a)),
+        ("abs", 1) => map(&a[0], f32::abs),
+        ("sign", 1) => map(&a[0], |x| if x == 0. { 0. } else { x.signum() }),
+        ("sqrt", 1) => map(&a[0], f32::sqrt),
+        ("floor", 1) => map(&a[0], f32::floor),
+        ("round", 1) => map(&a[0], f32::round),
+        ("fract", 1) => map(&a[0], |x| x - x.floor()),
+        ("sin", 1) => map(&a[0], f32::sin),
+        ("cos", 1) => map(&a[0], f32::cos),
+        ("tan", 1) => map(&a[0], f32::tan),
+        ("asin", 1) => map(&a[0], f32::asin),
+        ("acos", 1) => map(&a[0], f32::acos),
+        ("atan", 1) => map(&a[0], f32::atan),
+        ("atan", 2) => zip(&a[0], &a[1], f32::atan2),
+        ("exp", 1) => map(&a[0], f32::exp),
+        ("exp2", 1) => map(&a[0], f32::exp2),
+        ("log", 1) => map(&a[0], f32::ln),
+        ("log2", 1) => map(&a[0], f32::log2),
+        ("pow", 2) => zip(&a[0], &a[1], f32::powf),
+        ("min", 2) => zip(&a[0], &a[1], f32::min),
+        ("max", 2) => zip(&a[0], &a[1], f32::max),
+        ("mod", 2) => zip(&a[0], &a[1], glsl_mod),
+        ("clamp", 3) => zip(&zip(&a[0], &a[1], f32::max), &a[2], f32::min),
+        ("mix", 3) => {
+            let t = &a[2];
+            arithmetic(
+                "+",
+                arithmetic("*", a[0].clone(), map(t, |t| 1. - t)),
+                arithmetic("*", a[1].clone(), t.clone()),
+            )
+        }
+        ("dot", 2) => Value::Float(
+            a[0].components()
+                .iter()
+                .zip(a[1].components())
+                .map(|(a, b)| a * b)
+                .sum(),
+        ),
+        ("length", 1) => Value::Float(a[0].components().iter().map(|x| x * x).sum::<f32>().sqrt()),
+        ("normalize", 1) => {
+            let length = a[0].components().iter().map(|x| x * x).sum::<f32>().sqrt();
+            map(&a[0], |x| x / length)
+        }
+        ("cross", 2) => {
+            let (u, v) = (a[0].components(), a[1].components());
+            Value::Vector(vec![
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ])
+        }
+        _ => panic!("unknown function {}/{}", name, a.len()),
+    }
+}
@@ -0,0 +1,121 @@
+//! Checks how the mesh settings pick the meshed region and resolution.
+
+use sdfer::mesh::{mesh_primitive, MeshSettings, Resolution};
+use sdfer::primitive::*;
+use sdfer::script_engine::{RhaiScriptEngine, ScriptEngine};
+
+extern crate nalgebra_glm as glm;
+
+fn settings(resolution: Resolution) -> MeshSettings {
+    MeshSettings {
+        resolution,
+        ..MeshSettings::default()
+    }
+}
+
+#[test]
+fn resolution_out_of_range() {
+    let sphere = Sphere::new(1.).unwrap();
+    assert!(mesh_primitive(&*sphere, &settings(Resolution::Depth(0))).is_err());
+    assert!(mesh_primitive(&*sphere, &settings(Resolution::Depth(11))).is_err());
+    assert!(mesh_primitive(&*sphere, &settings(Resolution::CellSize(0.))).is_err());
+    assert!(mesh_primitive(&*sphere, &settings(Resolution::CellSize(1e-4))).is_err());
+    let mesh = mesh_primitive(&*sphere, &settings(Resolution::Depth(3))).unwrap();
+    assert!(!mesh.triangles.is_empty());
+
+    let engine = RhaiScriptEngine::new();
+    assert!(engine
+        .eval("mesh_settings(#{depth: -1}); Sphere(1.)")
+        .is_err());
+    engine
+        .eval("mesh_settings(#{depth: 0}); Sphere(1.)")
+        .unwrap();
+    assert!(mesh_primitive(&*sphere, &engine.mesh_settings()).is_err());
+}
+
+#[test]
+fn normal_epsilon() {
+    let engine = RhaiScriptEngine::new();
+    engine
+        .eval("mesh_settings(#{depth: 4, normal_epsilon: 0.001}); Sphere(1.)")
+        .unwrap();
+    let settings = engine.mesh_settings();
+    assert_eq!(settings.normal_epsilon, 0.001);
+    // Round cones estimate their normals by central differences.
+    let cone = RoundCone::new(0.5, 0.2, glm::vec3(0., 0., -1.), glm::vec3(0., 0., 1.)).unwrap();
+    let mesh = mesh_primitive(&*cone, &settings).unwrap();
+    for (v, n) in mesh.vertices.iter().zip(&mesh.normals) {
+        let expected = cone.gradient(*v, 1e-4).normalize();
+        assert!(n.dot(&expected) > 0.99, "{:?}", v.as_slice());
+    }
+    let settings = MeshSettings {
+        normal_epsilon: 0.,
+        ..settings
+    };
+    assert!(mesh_primitive(&*cone, &settings).is_err());
+}
+
+/// Returns the box spanned by the vertices of `p` meshed with `settings`.
+fn mesh_extent(p: &dyn Primitive, settings: &MeshSettings) -> BoundingBox {
+    let mesh = mesh_primitive(p, settings).unwrap();
+    let min = mesh
+        .vertices
+        .iter()
+        .fold(glm::Vec3::repeat(f32::INFINITY), |m, v| m.inf(v));
+    let max = mesh
+        .vertices
+        .iter()
+        .fold(glm::Vec3::repeat(-f32::INFINITY), |m, v| m.sup(v));
+    BoundingBox::new(min, max)
+}
+
+fn assert_close(actual: glm::Vec3, expected: glm::Vec3) {
+    assert!(
+        glm::distance(&actual, &expected) < 1e-3,
+        "{:?} != {:?}",
+        actual.as_slice(),
+        expected.as_slice()
+    );
+}
+
+#[test]
+fn region() {
+    let settings = settings(Resolution::Depth(4));
+    // Unbounded axes get meshed within 2 of the origin.
+    let plane = Plane::new(glm::vec3(0., 0., 1.), -0.5).unwrap();
+    let extent = mesh_extent(&*plane, &settings);
+    assert_close(extent.min, glm::vec3(-2., -2., -2.));
+    assert_close(extent.max, glm::vec3(2., 2., 0.5));
+    let cylinder = Cylinder::new_infinite(1.).unwrap();
+    let extent = mesh_extent(&*cylinder, &settings);
+    assert_eq!(extent.min[2], -2.);
+    assert_eq!(extent.max[2], 2.);
+    assert!((extent.max[0] - 1.).abs() < 0.05);
+
+    // Explicit bounds clip the object.
+    let sphere = Sphere::new(1.).unwrap();
+    let clipped = MeshSettings {
+        bounds: Some(BoundingBox::new(
+            glm::vec3(0., -2., -2.),
+            glm::vec3(2., 2., 0.5),
+        )),
+        ..settings.clone()
+    };
+    let extent = mesh_extent(&*sphere, &clipped);
+    assert!(extent.min[0].abs() < 1e-3);
+    assert!((extent.max[0] - 1.).abs() < 0.05);
+    assert_eq!(extent.max[2], 0.5);
+
+    let empty = MeshSettings {
+        bounds: Some(BoundingBox::new(
+            glm::vec3(1., 0., 0.),
+            glm::vec3(0., 1., 1.),
+        )),
+        ..settings.clone()
+    };
+    assert!(mesh_primitive(&*sphere, &empty).is_err());
+    let engine = RhaiScriptEngine::new();
+    assert!(engine
+        .eval("mesh_settings(#{min: Vector(0., 0., 0.)}); Sphere(1.)")
+        .is_err());
+}
@@ -0,0 +1,63 @@
+//! Checks how scripts declare parameters and how they get overridden.
+
+use sdfer::script_engine::{Parameter, RhaiScriptEngine, ScriptEngine};
+
+const SCRIPT: &str = r#"
+let wall = param("wall", 2.0, 0.5, 5.0);
+Sphere(wall)
+"#;
+
+#[test]
+fn defaults_and_overrides() {
+    let mut engine = RhaiScriptEngine::new();
+    engine.eval(SCRIPT).unwrap();
+    let wall = Parameter {
+        name: "wall".to_string(),
+        value: 2.,
+        default: 2.,
+        min: 0.5,
+        max: 5.,
+    };
+    assert_eq!(engine.parameters(), vec![wall.clone()]);
+
+    engine.set_parameter("wall", 3.);
+    engine.eval(SCRIPT).unwrap();
+    assert_eq!(engine.parameters(), vec![Parameter { value: 3., ..wall }]);
+}
+
+#[test]
+fn invalid_parameters() {
+    let mut engine = RhaiScriptEngine::new();
+    engine.set_parameter("wall", 7.);
+    assert!(engine.eval(SCRIPT).is_err());
+    assert!(engine
+        .eval(r#"param("wall", 6.0, 0.5, 5.0); Sphere(1.)"#)
+        .is_err());
+    assert!(engine
+        .eval(r#"param("a", 1.0, 0.0, 2.0); param("a", 1.0, 0.0, 2.0); Sphere(1.)"#)
+        .is_err());
+}
+
+#[test]
+fn edited_declarations() {
+    let mut engine = RhaiScriptEngine::new();
+    engine.eval(SCRIPT).unwrap();
+    engine.set_parameter("wall", 4.);
+    engine.eval(SCRIPT).unwrap();
+    assert_eq!(engine.parameters()[0].value, 4.);
+
+    // The slider value no longer fits the edited range, so the default is used.
+    engine
+        .eval(r#"let wall = param("wall", 1.0, 0.5, 3.0); Sphere(wall)"#)
+        .unwrap();
+    assert_eq!(engine.parameters()[0].value, 1.);
+    engine.eval(SCRIPT).unwrap();
+    assert_eq!(engine.parameters()[0].value, 2.);
+
+    // A new default takes effect too, even though the old value would fit.
+    engine.set_parameter("wall", 4.);
+    engine
+        .eval(r#"let wall = param("wall", 3.0, 0.5, 5.0); Sphere(wall)"#)
+        .unwrap();
+    assert_eq!(engine.parameters()[0].value, 3.);
+}
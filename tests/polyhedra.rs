@@ -0,0 +1,263 @@
+//! Checks the platonic solids against their vertices and the prisms against known distances.
+
+use sdfer::primitive::*;
+use sdfer::script_engine::{RhaiScriptEngine, ScriptEngine};
+
+extern crate nalgebra_glm as glm;
+
+const PHI: f32 = 1.618034;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+/// Returns the points with all sign combinations of their coordinates.
+fn mirrored(points: &[glm::Vec3]) -> Vec<glm::Vec3> {
+    let mut result = Vec::new();
+    for p in points {
+        for signs in 0..8 {
+            let s = |i: usize| if signs & (1 << i) == 0 { 1. } else { -1. };
+            let m = v(p[0] * s(0), p[1] * s(1), p[2] * s(2));
+            if !result.contains(&m) {
+                result.push(m);
+            }
+        }
+    }
+    result
+}
+
+/// Checks that `vertices`, scaled to faces at `radius`, are the corners of `solid`.
+fn check_vertices(solid: &dyn Primitive, vertices: &[glm::Vec3], inradius: f32, radius: f32) {
+    let vertices = vertices
+        .iter()
+        .map(|p| p * (radius / inradius))
+        .collect::<Vec<_>>();
+    assert!((solid.eval(v(0., 0., 0.)) + radius).abs() < 1e-5);
+    let mut extent = 0_f32;
+    for p in &vertices {
+        assert!(solid.eval(*p).abs() < 1e-5, "{:?}", p.as_slice());
+        assert!(solid.eval(p * 1.1) > 0.);
+        assert!(solid.eval(p * 0.9) < 0.);
+        extent = extent.max(p.amax());
+    }
+    let bounds = solid.bounds();
+    assert!((bounds.max - v(extent, extent, extent)).amax() < 1e-5);
+    assert!((bounds.min + v(extent, extent, extent)).amax() < 1e-5);
+}
+
+#[test]
+fn platonic_solids() {
+    let tetrahedron = [
+        v(-1., -1., -1.),
+        v(-1., 1., 1.),
+        v(1., -1., 1.),
+        v(1., 1., -1.),
+    ];
+    let inradius = 1. / 3_f32.sqrt();
+    check_vertices(
+        &*Tetrahedron::new(0.7).unwrap(),
+        &tetrahedron,
+        inradius,
+        0.7,
+    );
+
+    let octahedron = mirrored(&[v(1., 0., 0.), v(0., 1., 0.), v(0., 0., 1.)]);
+    check_vertices(&*Octahedron::new(0.7).unwrap(), &octahedron, inradius, 0.7);
+
+    let dodecahedron = mirrored(&[
+        v(1., 1., 1.),
+        v(0., 1. / PHI, PHI),
+        v(1. / PHI, PHI, 0.),
+        v(PHI, 0., 1. / PHI),
+    ]);
+    assert_eq!(dodecahedron.len(), 20);
+    let inradius = PHI * PHI / (1. + PHI * PHI).sqrt();
+    check_vertices(
+        &*Dodecahedron::new(0.7).unwrap(),
+        &dodecahedron,
+        inradius,
+        0.7,
+    );
+
+    let icosahedron = mirrored(&[v(0., 1., PHI), v(1., PHI, 0.), v(PHI, 0., 1.)]);
+    assert_eq!(icosahedron.len(), 12);
+    let inradius = PHI * PHI / 3_f32.sqrt();
+    check_vertices(
+        &*Icosahedron::new(0.7).unwrap(),
+        &icosahedron,
+        inradius,
+        0.7,
+    );
+}
+
+#[test]
+fn exact_distances() {
+    let octahedron = Octahedron::new(1. / 3_f32.sqrt()).unwrap();
+    // Beyond a vertex, an edge and a face.
+    assert!((octahedron.eval(v(0., -3., 0.)) - 2.).abs() < 1e-5);
+    let beyond_edge = v(0.5, 0., -0.5) + v(1., 0., -1.).normalize() * 2.;
+    assert!((octahedron.eval(beyond_edge) - 2.).abs() < 1e-5);
+    let beyond_face = v(1., -1., 1.) / 3. + v(1., -1., 1.).normalize() * 2.;
+    assert!((octahedron.eval(beyond_face) - 2.).abs() < 1e-5);
+
+    let hex_prism = HexPrism::new(1., 2.).unwrap();
+    assert!((hex_prism.eval(v(0., -3., 0.)) - 2.).abs() < 1e-5);
+    let corner = v(2. / 3_f32.sqrt(), 0., 1.);
+    assert!((hex_prism.eval(corner + v(1., 0., 1.)) - 2_f32.sqrt()).abs() < 1e-5);
+    assert!((hex_prism.eval(v(0., 0.5, 0.2)) + 0.5).abs() < 1e-5);
+
+    let tri_prism = TriPrism::new(1., 6.).unwrap();
+    assert!((tri_prism.eval(v(0., 3., 0.)) - 1.).abs() < 1e-5);
+    assert!((tri_prism.eval(v(0., -2., 0.)) - 1.).abs() < 1e-5);
+    assert!((tri_prism.eval(v(0., 0., 5.)) - 2.).abs() < 1e-5);
+    assert!((tri_prism.eval(v(0., 0., 0.)) + 1.).abs() < 1e-5);
+    let side = v(3_f32.sqrt() / 2., 0.5, 0.);
+    assert!((tri_prism.eval(side * 3.) - 2.).abs() < 1e-5);
+}
+
+#[test]
+fn invalid_solids() {
+    assert!(Tetrahedron::new(0.).is_err());
+    assert!(Icosahedron::new(-1.).is_err());
+    assert!(HexPrism::new(1., 0.).is_err());
+    assert!(TriPrism::new(0., 1.).is_err());
+}
+
+#[test]
+fn solids_in_scripts() {
+    let engine = RhaiScriptEngine::new();
+    for (script, d) in [
+        ("Tetrahedron(1.)", -1.),
+        ("Octahedron(1.)", -1.),
+        ("Dodecahedron(1.)", -1.),
+        ("Icosahedron(1.)", -1.),
+        ("HexPrism(1., 4.)", -1.),
+        ("TriPrism(1., 4.)", -1.),
+    ] {
+        let solid = engine.eval(script).unwrap();
+        assert!((solid.eval(v(0., 0., 0.)) - d).abs() < 1e-5, "{}", script);
+    }
+}
+
+#[test]
+fn convex_hulls() {
+    let icosahedron = mirrored(&[v(0., 1., PHI), v(1., PHI, 0.), v(PHI, 0., 1.)]);
+    let radius = PHI * PHI / 3_f32.sqrt();
+    // Points inside the hull do not change it.
+    let mut points = icosahedron.clone();
+    points.extend([v(0., 0., 0.), v(0.5, -0.3, 0.2)]);
+    let hull = ConvexPolyhedron::new(points).unwrap();
+    let solid = Icosahedron::new(radius).unwrap();
+    for p in [
+        v(0., 0., 0.),
+        v(0.3, -0.2, 0.5),
+        v(2., 1., -3.),
+        v(0., 0., 2.),
+        v(-1.5, 1.5, 0.),
+    ] {
+        assert!((hull.eval(p) - solid.eval(p)).abs() < 1e-5, "{:?}", p);
+    }
+    check_vertices(&*hull, &icosahedron, radius, radius);
+    // The faces are looped over instead of unrolled.
+    let mut code = Vec::new();
+    hull.expression("p", &mut code).unwrap();
+    assert!(code.concat().contains("for ("));
+
+    let cube = ConvexPolyhedron::new(mirrored(&[v(1., 1., 1.)])).unwrap();
+    assert!((cube.eval(v(0., 0., 3.)) - 2.).abs() < 1e-5);
+    assert!((cube.eval(v(0.5, 0., 0.)) + 0.5).abs() < 1e-5);
+    let bounds = cube.bounds();
+    assert_eq!(bounds.min, v(-1., -1., -1.));
+    assert_eq!(bounds.max, v(1., 1., 1.));
+}
+
+#[test]
+fn convex_polyhedron_from_planes() {
+    let normals = [v(0., PHI, 1.), v(1., 0., PHI), v(PHI, 1., 0.)];
+    let normals = normals
+        .iter()
+        .flat_map(|n| [*n, -n])
+        .flat_map(|n| {
+            [
+                n,
+                v(-n[0], n[1], n[2]),
+                v(n[0], -n[1], n[2]),
+                v(n[0], n[1], -n[2]),
+            ]
+        })
+        .fold(Vec::new(), |mut normals, n| {
+            if !normals.contains(&n) {
+                normals.push(n);
+            }
+            normals
+        });
+    assert_eq!(normals.len(), 12);
+    let hull = ConvexPolyhedron::from_planes(normals, vec![0.7; 12]).unwrap();
+    let solid = Dodecahedron::new(0.7).unwrap();
+    for p in [
+        v(0., 0., 0.),
+        v(0.3, -0.2, 0.5),
+        v(2., 1., -3.),
+        v(0., 0., 2.),
+    ] {
+        assert!((hull.eval(p) - solid.eval(p)).abs() < 1e-5, "{:?}", p);
+    }
+
+    // A redundant plane is ignored.
+    let mut normals = vec![
+        v(1., 0., 0.),
+        v(-1., 0., 0.),
+        v(0., 1., 0.),
+        v(0., -1., 0.),
+        v(0., 0., 1.),
+        v(0., 0., -1.),
+    ];
+    let mut offsets = vec![1.; 6];
+    normals.push(v(1., 1., 1.));
+    offsets.push(10.);
+    let cube = ConvexPolyhedron::from_planes(normals, offsets).unwrap();
+    assert!((cube.eval(v(0., 3., 0.)) - 2.).abs() < 1e-5);
+    assert_eq!(cube.bounds().max, v(1., 1., 1.));
+}
+
+#[test]
+fn invalid_convex_polyhedra() {
+    let square = vec![v(0., 0., 0.), v(1., 0., 0.), v(0., 1., 0.), v(1., 1., 0.)];
+    assert!(ConvexPolyhedron::new(square).is_err());
+    assert!(ConvexPolyhedron::new(vec![v(0., 0., 0.), v(1., 0., 0.)]).is_err());
+    let slab = vec![v(0., 0., 1.), v(0., 0., -1.)];
+    assert!(ConvexPolyhedron::from_planes(slab, vec![1., 1.]).is_err());
+    let normals = mirrored(&[v(1., 1., 1.)]);
+    assert!(ConvexPolyhedron::from_planes(normals.clone(), vec![1.; 7]).is_err());
+    let mut offsets = vec![1.; 8];
+    offsets[0] = -2.;
+    offsets[7] = -2.;
+    assert!(ConvexPolyhedron::from_planes(normals, offsets).is_err());
+}
+
+#[test]
+fn convex_polyhedra_in_scripts() {
+    let engine = RhaiScriptEngine::new();
+    let cube = engine
+        .eval(
+            "ConvexPolyhedron([
+                Vector(-1., -1., -1.), Vector(1., -1., -1.), Vector(-1., 1., -1.),
+                Vector(1., 1., -1.), Vector(-1., -1., 1.), Vector(1., -1., 1.),
+                Vector(-1., 1., 1.), Vector(1., 1., 1.)])",
+        )
+        .unwrap();
+    assert!((cube.eval(v(0., 0., 0.)) + 1.).abs() < 1e-5);
+    let tetrahedron = engine
+        .eval(
+            "ConvexPolyhedron::from_planes(
+                [Vector(1., 1., 1.), Vector(-1., -1., 1.), Vector(-1., 1., -1.),
+                 Vector(1., -1., -1.)],
+                [1, 1., 1., 1.])",
+        )
+        .unwrap();
+    assert!((tetrahedron.eval(v(0., 0., 0.)) + 1.).abs() < 1e-5);
+    assert!(engine.eval("ConvexPolyhedron([1., 2.])").is_err());
+    assert!(engine
+        .eval("ConvexPolyhedron::from_planes([Vector(1., 0., 0.)], [\"a\"])")
+        .is_err());
+}
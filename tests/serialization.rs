@@ -0,0 +1,173 @@
+//! Checks that primitive trees evaluate the same after a round trip through JSON and RON.
+
+use sdfer::primitive::*;
+
+extern crate nalgebra_glm as glm;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+/// A tree containing every primitive and min function.
+fn tree() -> Box<dyn Primitive> {
+    let cube = || ExactBox::new(v(1.5, 1., 2.)).unwrap();
+    let mut smooth = Boolean::new_union(vec![
+        Sphere::new(1.).unwrap(),
+        RoundBox::new(v(1., 1., 1.), 0.2).unwrap(),
+    ])
+    .unwrap();
+    smooth
+        .set_min_function(Box::new(MinPolynomial::new(0.3).unwrap()))
+        .unwrap();
+    let min_functions: Vec<Box<dyn MinFunction>> = vec![
+        Box::new(MinCubicPolynomial::new(0.3).unwrap()),
+        Box::new(MinRoot::new(0.3).unwrap()),
+        Box::new(MinChamfer::new(0.3).unwrap()),
+        Box::new(MinStairs::new(0.3, 3).unwrap()),
+        Box::new(MinExponential::new(0.3).unwrap()),
+    ];
+    let blends = min_functions.into_iter().map(|f| {
+        let mut b = Boolean::new_intersection(vec![cube(), Torus::new(0.5, 1.5).unwrap()]).unwrap();
+        b.set_min_function(f).unwrap();
+        b as Box<dyn Primitive>
+    });
+    let children = vec![
+        smooth as Box<dyn Primitive>,
+        Plane::new(v(1., -2., 0.5), 5.).unwrap(),
+        Torus::new_capped(0.5, 1.5, 2.).unwrap(),
+        Cylinder::new_infinite(0.7)
+            .unwrap()
+            .translate(v(9., 0., 0.)),
+        Cylinder::new(0.7, v(0.1, -1., 0.3), v(1., 1., -0.5)).unwrap(),
+        RoundedCylinder::new(0.5, 0.1, 1.).unwrap(),
+        Capsule::new(0.4, v(-1., 0., 0.), v(1., 1., 1.)).unwrap(),
+        Ellipsoid::new(v(1.5, 0.4, 0.9)).unwrap(),
+        Cone::new(0.8, 1.5).unwrap(),
+        Tetrahedron::new(0.7).unwrap(),
+        Octahedron::new(0.7).unwrap(),
+        Dodecahedron::new(0.7).unwrap(),
+        Icosahedron::new(0.7).unwrap(),
+        HexPrism::new(0.7, 1.5).unwrap(),
+        TriPrism::new(0.7, 1.5).unwrap(),
+        ConvexPolyhedron::new(vec![
+            v(-1., -0.5, -0.8),
+            v(1.2, -0.7, -0.5),
+            v(0.1, 1.3, -0.6),
+            v(0.2, 0.1, 1.4),
+        ])
+        .unwrap(),
+        CappedCone::new(0.9, 0.3, v(0.1, -1., 0.3), v(1., 1., -0.5)).unwrap(),
+        RoundCone::new(0.6, 0.2, v(-1., 0., 0.), v(1., 1., 1.)).unwrap(),
+        Part::new(Sphere::new(0.5).unwrap(), "ball", Some(v(1., 0., 0.))).unwrap(),
+        cube().rotate_euler(0.3, 0.6, -1.2).scale(v(0.5, 2., 1.5)),
+        Rotate::from_axis_angle(cube(), v(1., 2., 3.), 0.7).unwrap(),
+        Rotate::align(cube(), v(0., 0., 1.), v(1., 1., 0.)).unwrap(),
+        Transform::new(
+            cube(),
+            glm::mat4(
+                1., 0.5, 0., 0.3, //
+                0., 1.2, 0., -0.5, //
+                0.2, 0., 0.8, 0., //
+                0., 0., 0., 1.,
+            ),
+        )
+        .unwrap(),
+        Twist::new(cube(), 3.),
+        Bend::new(cube().translate(v(0., 2., 0.)), 8.),
+        Repeat::new(
+            Sphere::new(0.3).unwrap(),
+            v(1., 0.8, 1.3),
+            glm::vec3(-2, 0, -1),
+            glm::vec3(1, 2, 1),
+        )
+        .unwrap(),
+        Boolean::new_difference(vec![cube(), Sphere::new(1.).unwrap()]).unwrap(),
+    ];
+    Boolean::new_union(children.into_iter().chain(blends).collect()).unwrap()
+}
+
+fn assert_same(expected: &dyn Primitive, actual: &dyn Primitive) {
+    for i in 0..100 {
+        let t = i as f32;
+        let p = v(t.sin() * 3., (t * 1.3).cos() * 3., (t * 0.7).sin() * 3.);
+        assert_eq!(expected.eval(p), actual.eval(p), "at {:?}", p.as_slice());
+    }
+}
+
+#[test]
+fn json_round_trip() {
+    let tree = tree();
+    let json = to_json(&*tree).unwrap();
+    let loaded = from_json(&json).unwrap();
+    assert_same(&*tree, &*loaded);
+    assert_eq!(to_json(&*loaded).unwrap(), json);
+}
+
+#[test]
+fn ron_round_trip() {
+    let tree = tree();
+    let ron = to_ron(&*tree).unwrap();
+    let loaded = from_ron(&ron).unwrap();
+    assert_same(&*tree, &*loaded);
+    assert_eq!(to_ron(&*loaded).unwrap(), ron);
+}
+
+#[test]
+fn malformed_json() {
+    let sphere = r#"{"Sphere": {"radius": 1.0}}"#;
+    let union = |children: &str, min_function: &str| {
+        format!(
+            r#"{{"Boolean": {{"children": [{}], "min_function": {}, "negate": false}}}}"#,
+            children, min_function
+        )
+    };
+    let default = r#"{"MinDefault": null}"#;
+    let polynomial = r#"{"MinPolynomial": {"k": 0.3}}"#;
+    assert!(from_json(&union(&[sphere, sphere].join(","), polynomial)).is_ok());
+    for json in [
+        union("", default),
+        union(sphere, default),
+        union(&[sphere, sphere, sphere].join(","), polynomial),
+        union(
+            &[sphere, sphere].join(","),
+            r#"{"MinPolynomial": {"k": -0.3}}"#,
+        ),
+        r#"{"Sphere": {"radius": -1.0}}"#.to_string(),
+        r#"{"Translate": {"primitive": {"Sphere": {"radius": 0.0}}, "vector": [0, 0, 0]}}"#
+            .to_string(),
+        r#"{"Part": {"primitive": {"Sphere": {"radius": 1.0}}, "name": "", "color": null}}"#
+            .to_string(),
+        r#"{"Plane": {"normal": [0.0, 0.0, 2.0], "d": 1.0}}"#.to_string(),
+    ] {
+        assert!(from_json(&json).is_err(), "{}", json);
+    }
+}
+
+#[test]
+fn malformed_ron() {
+    for ron in [
+        r#"{"Boolean": (children: [], min_function: {"MinDefault": ()}, negate: false)}"#,
+        r#"{"Sphere": (radius: -1.0)}"#,
+        r#"{"Scale": (primitive: {"ExactBox": (size: (0.5, -1.0, 0.5))}, scale: (1, 1, 1))}"#,
+        r#"{"Repeat": (primitive: {"Sphere": (radius: 1.0)}, bounds: (1, 1, 1),
+            repeats_min: (0, 0, 2), repeats_max: (0, 0, 1))}"#,
+    ] {
+        assert!(from_ron(ron).is_err(), "{}", ron);
+    }
+    assert!(from_ron(r#"{"Sphere": (radius: 1.0)}"#).is_ok());
+}
+
+#[test]
+fn readable_json() {
+    let json = to_json(&*Sphere::new(1.).unwrap().translate(v(1., 2., 3.))).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "Translate": {
+                "primitive": {"Sphere": {"radius": 1.0}},
+                "vector": [1.0, 2.0, 3.0],
+            }
+        })
+    );
+}
@@ -0,0 +1,153 @@
+//! Checks that simplified trees are smaller but evaluate the same.
+
+use sdfer::primitive::*;
+
+extern crate nalgebra_glm as glm;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn sphere() -> Box<dyn Primitive> {
+    Sphere::new(1.).unwrap()
+}
+
+fn cube() -> Box<dyn Primitive> {
+    ExactBox::new(v(1.5, 1., 2.)).unwrap()
+}
+
+fn union(children: Vec<Box<dyn Primitive>>) -> Box<dyn Primitive> {
+    Boolean::new_union(children).unwrap()
+}
+
+/// Asserts that `primitive` simplifies to a tree described as `expected` with the same distances.
+fn check(primitive: Box<dyn Primitive>, expected: &str) {
+    let simplified = primitive.simplify();
+    assert_eq!(describe(&*simplified), expected);
+    for i in 0..200 {
+        let t = i as f32;
+        let p = v(t.sin() * 3., (t * 1.3).cos() * 3., (t * 0.7).sin() * 3.);
+        let (a, b) = (primitive.eval(p), simplified.eval(p));
+        assert!(
+            (a - b).abs() <= 1e-5 * a.abs().max(1.),
+            "{} != {} at {:?} for\n{}",
+            a,
+            b,
+            p.as_slice(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn transformations() {
+    check(
+        sphere().translate(v(1., 0., 0.)).translate(v(0., 2., 0.)),
+        "\
+Translate(vector: [1.0, 2.0, 0.0])
+  Sphere(radius: 1.0)",
+    );
+    check(
+        sphere()
+            .translate(v(0., 0., 0.))
+            .rotate_euler(0., 0., 0.)
+            .scale(v(1., 1., 1.)),
+        "Sphere(radius: 1.0)",
+    );
+    check(
+        cube().scale(v(2., 2., 2.)),
+        "\
+Scale(scale: [2.0, 2.0, 2.0])
+  ExactBox(size: [1.5, 1.0, 2.0])",
+    );
+    let chain = || {
+        cube()
+            .translate(v(0.5, -1., 0.3))
+            .rotate_euler(0.3, 0.6, -1.2)
+            .scale(v(0.5, 2., 1.5))
+            .translate(v(1., 1., 1.))
+    };
+    let simplified = describe(&*chain().simplify());
+    assert!(simplified.starts_with("Transform("), "{}", simplified);
+    check(chain(), &simplified);
+    check(
+        Twist::new(
+            sphere().translate(v(1., 0., 0.)).translate(v(1., 0., 0.)),
+            3.,
+        ),
+        "\
+Twist(height_per_rotation: 3.0)
+  Translate(vector: [2.0, 0.0, 0.0])
+    Sphere(radius: 1.0)",
+    );
+}
+
+#[test]
+fn rotations_compose() {
+    let rotated = cube()
+        .rotate_euler(0.3, 0., 0.)
+        .rotate_euler(0., 0.6, 0.)
+        .rotate_euler(0., 0., -1.2);
+    let simplified = rotated.simplify();
+    assert!(describe(&*simplified).starts_with("Rotate("));
+    check(rotated, &describe(&*simplified));
+}
+
+#[test]
+fn booleans() {
+    check(
+        union(vec![
+            union(vec![sphere(), cube()]),
+            sphere().translate(v(1., 0., 0.)),
+        ]),
+        "\
+Union
+  Sphere(radius: 1.0)
+  ExactBox(size: [1.5, 1.0, 2.0])
+  Translate(vector: [1.0, 0.0, 0.0])
+    Sphere(radius: 1.0)",
+    );
+    check(
+        Boolean::new_difference(vec![
+            cube(),
+            union(vec![sphere(), sphere().translate(v(1., 0., 0.))]),
+        ])
+        .unwrap(),
+        "\
+Difference
+  ExactBox(size: [1.5, 1.0, 2.0])
+  Sphere(radius: 1.0)
+  Translate(vector: [1.0, 0.0, 0.0])
+    Sphere(radius: 1.0)",
+    );
+    check(
+        Boolean::new_intersection(vec![
+            cube(),
+            Boolean::new_intersection(vec![sphere(), sphere().translate(v(0.5, 0., 0.))]).unwrap(),
+        ])
+        .unwrap(),
+        "\
+Intersection
+  ExactBox(size: [1.5, 1.0, 2.0])
+  Sphere(radius: 1.0)
+  Translate(vector: [0.5, 0.0, 0.0])
+    Sphere(radius: 1.0)",
+    );
+}
+
+#[test]
+fn blends_stay_nested() {
+    let mut smooth = Boolean::new_union(vec![sphere(), cube()]).unwrap();
+    smooth
+        .set_min_function(Box::new(MinPolynomial::new(0.3).unwrap()))
+        .unwrap();
+    let nested = union(vec![smooth.clone(), cube().translate(v(2., 0., 0.))]);
+    check(nested.clone(), &describe(&*nested));
+
+    let mut outer = Boolean::new_union(vec![union(vec![sphere(), cube()]), sphere()]).unwrap();
+    outer
+        .set_min_function(Box::new(MinPolynomial::new(0.3).unwrap()))
+        .unwrap();
+    let outer: Box<dyn Primitive> = outer;
+    check(outer.clone(), &describe(&*outer));
+}
@@ -0,0 +1,160 @@
+//! Checks placing primitives by matrices, from Rust and from scripts.
+
+use sdfer::primitive::*;
+use sdfer::script_engine::{RhaiScriptEngine, ScriptEngine};
+
+extern crate nalgebra_glm as glm;
+
+fn v(x: f32, y: f32, z: f32) -> glm::Vec3 {
+    glm::vec3(x, y, z)
+}
+
+fn cube() -> Box<dyn Primitive> {
+    ExactBox::new(v(1.5, 1., 2.)).unwrap()
+}
+
+fn points() -> impl Iterator<Item = glm::Vec3> {
+    (0..500).map(|i| {
+        let t = i as f32;
+        v(t.sin() * 3., (t * 1.3).cos() * 3., (t * 0.7).sin() * 3.)
+    })
+}
+
+#[test]
+fn rigid_poses_keep_distances() {
+    let pose = glm::translation(&v(1., -2., 0.5)) * glm::rotation(0.7, &v(1., 2., 3.));
+    let placed = Transform::new(cube(), pose).unwrap();
+    let inverse = pose.try_inverse().unwrap();
+    for p in points() {
+        let local = (inverse * p.push(1.)).xyz();
+        assert!((placed.eval(p) - cube().eval(local)).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn distorting_poses_give_distance_bounds() {
+    let shear = glm::mat4(
+        1., 0.8, 0., 0.3, //
+        0., 1.2, 0., -0.5, //
+        0.2, 0., 0.4, 0., //
+        0., 0., 0., 1.,
+    );
+    let placed = Transform::new(cube(), shear).unwrap();
+    // Distances must not change faster than the points they are evaluated at.
+    let points = points().collect::<Vec<_>>();
+    for (a, b) in points.iter().zip(&points[1..]) {
+        let change = (placed.eval(*a) - placed.eval(*b)).abs();
+        assert!(change <= (a - b).norm() * 1.0001, "{} at {:?}", change, a);
+    }
+}
+
+#[test]
+fn invalid_poses() {
+    let mut projective = glm::Mat4::identity();
+    projective[(3, 0)] = 1.;
+    assert!(Transform::new(cube(), projective).is_err());
+    assert!(Transform::new(cube(), glm::scaling(&v(1., 0., 1.))).is_err());
+}
+
+#[test]
+fn matrices_in_scripts() {
+    let engine = RhaiScriptEngine::new();
+    let placed = engine
+        .eval(
+            r#"
+            let pose = Matrix::translation(Vector(1., 0., 0.))
+                * Matrix::rotation_axis_angle(Vector(0., 0., 1.), 90.);
+            Sphere(1.).translate(1., 0., 0.).transform(pose * inverse(pose) * pose)
+            "#,
+        )
+        .unwrap();
+    // The sphere ends up centered at (1, 1, 0).
+    assert!((placed.eval(v(1., 1., 0.)) + 1.).abs() < 1e-5);
+
+    let rows = engine
+        .eval("Sphere(1.).transform(Matrix([[1, 0, 0, 2], [0, 1, 0, 0], [0, 0, 1, 0]]))")
+        .unwrap();
+    assert!((rows.eval(v(2., 0., 0.)) + 1.).abs() < 1e-5);
+
+    let aimed = engine
+        .eval(
+            "Cylinder(0.1, Vector(0., 0., 0.), Vector(0., 0., 5.)).transform(\
+             Matrix::look_at(Vector(1., 1., 1.), Vector(2., 2., 2.), Vector(0., 0., 1.)))",
+        )
+        .unwrap();
+    assert!(aimed.eval(v(3., 3., 3.)) < 0.);
+    assert!(aimed.eval(v(0., 0., 0.)) > 0.);
+
+    assert!(engine
+        .eval("Sphere(1.).transform(Matrix::scaling(Vector(0., 1., 1.)))")
+        .is_err());
+    assert!(engine.eval("Matrix([[1, 0, 0]])").is_err());
+}
+
+#[test]
+fn rotations() {
+    let quarter =
+        Rotate::from_axis_angle(cube(), v(0., 0., 2.), std::f32::consts::FRAC_PI_2).unwrap();
+    // The extent along x ends up along y.
+    assert!(quarter.eval(v(0., 0.6, 0.)) < 0.);
+    assert!(quarter.eval(v(0.6, 0., 0.)) > 0.);
+
+    let q = glm::quat_angle_axis(0.7, &v(1., 2., 3.).normalize());
+    let by_quaternion = Rotate::from_quaternion(cube(), q * 3.).unwrap();
+    let by_axis = Rotate::from_axis_angle(cube(), v(1., 2., 3.), 0.7).unwrap();
+    for p in points() {
+        assert!((by_quaternion.eval(p) - by_axis.eval(p)).abs() < 1e-5);
+    }
+
+    let cylinder = || Cylinder::new(0.1, v(0., 0., 0.), v(0., 0., 5.)).unwrap();
+    for to in [v(1., 1., 0.), v(0., 0., -1.), v(0., 0., 2.)] {
+        let aligned = Rotate::align(cylinder(), v(0., 0., 1.), to).unwrap();
+        assert!(aligned.eval(to.normalize() * 4.) < 0.);
+        assert!(aligned.eval(to.normalize() * -4.) > 0.);
+    }
+
+    assert!(Rotate::from_axis_angle(cube(), v(0., 0., 0.), 1.).is_err());
+    assert!(Rotate::from_quaternion(cube(), glm::quat(0., 0., 0., 0.)).is_err());
+    assert!(Rotate::align(cube(), v(0., 0., 0.), v(1., 0., 0.)).is_err());
+}
+
+#[test]
+fn rotations_in_scripts() {
+    let engine = RhaiScriptEngine::new();
+    let turned = engine
+        .eval("Sphere(1.).translate(2., 0., 0.).rotate_axis(Vector(0., 0., 1.), 90.)")
+        .unwrap();
+    assert!((turned.eval(v(0., 2., 0.)) + 1.).abs() < 1e-5);
+
+    let aligned = engine
+        .eval("align(Sphere(1.).translate(0., 0., 2.), Vector(0., 0., 1.), Vector(-1., 0., 0.))")
+        .unwrap();
+    assert!((aligned.eval(v(-2., 0., 0.)) + 1.).abs() < 1e-5);
+
+    assert!(engine
+        .eval("Sphere(1.).rotate_axis(Vector(0., 0., 0.), 90.)")
+        .is_err());
+}
+
+#[test]
+fn exactness() {
+    let sphere = || Sphere::new(1.).unwrap();
+    let rigid = glm::translation(&v(1., -2., 0.5)) * glm::rotation(0.7, &v(1., 2., 3.));
+    assert!(Transform::new(sphere(), rigid).unwrap().is_exact());
+    let even = glm::translation(&v(1., -2., 0.5)) * glm::scaling(&v(2., 2., 2.));
+    let even = Transform::new(sphere(), even).unwrap();
+    assert!(even.is_exact());
+    assert!((even.eval(v(5., -4., 1.)) - (glm::length(&v(4., -2., 0.5)) - 2.)).abs() < 1e-5);
+    assert!(!Transform::new(sphere(), glm::scaling(&v(1., 2., 1.)))
+        .unwrap()
+        .is_exact());
+
+    // Scaling back evenly composes to the identity matrix, but keeps the factor of the uneven
+    // scales, so the result is only a bound.
+    let rescaled = sphere()
+        .scale(v(1., 2., 1.))
+        .scale(v(1., 0.5, 1.))
+        .simplify();
+    assert!(rescaled.eval(v(3., 0., 0.)) < 2.);
+    assert!(!rescaled.is_exact());
+}
@@ -0,0 +1,261 @@
+//! A front end for the subset of WGSL that primitives generate.
+//!
+//! WGSL expressions read like GLSL ones, so only function definitions and declarations get
+//! parsed here. Builtins get renamed to their GLSL counterparts and the GLSL interpreter runs
+//! the result. This only computes values; naga checks that the generated modules are valid.
+
+use super::glsl::{Expr, Function, Parser, Program, Statement, Token, Value};
+use std::collections::HashMap;
+
+/// WGSL builtins used by the shaders and their GLSL names.
+const BUILTINS: [(&str, &str); 23] = [
+    ("array", "array"),
+    ("f32", "float"),
+    ("vec2f", "vec2"),
+    ("vec3f", "vec3"),
+    ("vec4f", "vec4"),
+    ("mat2x2f", "mat2"),
+    ("mat3x3f", "mat3"),
+    ("abs", "abs"),
+    ("sign", "sign"),
+    ("sqrt", "sqrt"),
+    ("floor", "floor"),
+    ("round", "round"),
+    ("sin", "sin"),
+    ("cos", "cos"),
+    ("atan2", "atan"),
+    ("exp2", "exp2"),
+    ("log2", "log2"),
+    ("min", "min"),
+    ("max", "max"),
+    ("clamp", "clamp"),
+    ("dot", "dot"),
+    ("length", "length"),
+    ("normalize", "normalize"),
+];
+
+/// Parses the function definitions in `shared_code` and evaluates WGSL expressions with them.
+pub struct WgslProgram {
+    program: Program,
+    functions: Vec<String>,
+}
+
+impl WgslProgram {
+    pub fn new(shared_code: &[String]) -> WgslProgram {
+        let mut parsed = Vec::new();
+        for code in shared_code {
+            let mut parser = Parser::new(code);
+            while parser.peek().is_some() {
+                parsed.push(function(&mut parser));
+            }
+        }
+        let names = parsed
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        let functions = parsed
+            .into_iter()
+            .map(|(name, f)| {
+                let body = f
+                    .body
+                    .iter()
+                    .map(|s| translate_statement(s, &names))
+                    .collect();
+                let f = Function {
+                    parameters: f.parameters,
+                    body,
+                };
+                (name, f)
+            })
+            .collect::<HashMap<_, _>>();
+        WgslProgram {
+            program: Program::with_functions(functions),
+            functions: names,
+        }
+    }
+
+    /// Evaluates `expression` with the given variables in scope.
+    pub fn eval(&self, expression: &str, variables: &[(&str, Value)]) -> Value {
+        let mut parser = Parser::new(expression);
+        let expr = parser.expression();
+        assert!(parser.peek().is_none(), "trailing tokens in {}", expression);
+        self.program
+            .eval_expr(&translate(&expr, &self.functions), variables)
+    }
+}
+
+fn is_ident(parser: &Parser, ident: &str) -> bool {
+    matches!(parser.peek(), Some(Token::Ident(i)) if i == ident)
+}
+
+/// Parses `fn name(a: T, ...) -> T { ... }`.
+fn function(parser: &mut Parser) -> (String, Function) {
+    assert_eq!(parser.ident(), "fn");
+    let name = parser.ident();
+    parser.expect("(");
+    let mut parameters = Vec::new();
+    while !parser.is_symbol(")") {
+        parameters.push(parser.ident());
+        parser.expect(":");
+        parser.ident();
+        if parser.is_symbol(",") {
+            parser.next();
+        }
+    }
+    parser.expect(")");
+    parser.expect("->");
+    parser.ident();
+    let body = match statement(parser) {
+        Statement::Block(body) => body,
+        s => panic!("expected function body, got {:?}", s),
+    };
+    (name, Function { parameters, body })
+}
+
+/// Skips a type like `f32` or `array<vec3f, 4>`.
+fn skip_type(parser: &mut Parser) {
+    parser.ident();
+    if parser.is_symbol("<") {
+        while !parser.is_symbol(">") {
+            parser.next();
+        }
+        parser.next();
+    }
+}
+
+fn statement(parser: &mut Parser) -> Statement {
+    if parser.is_symbol("{") {
+        parser.next();
+        let mut statements = Vec::new();
+        while !parser.is_symbol("}") {
+            statements.push(statement(parser));
+        }
+        parser.next();
+        return Statement::Block(statements);
+    }
+    let keyword = parser.ident();
+    match keyword.as_str() {
+        "let" | "var" => {
+            let name = parser.ident();
+            parser.expect(":");
+            skip_type(parser);
+            let value = if parser.is_symbol("=") {
+                parser.next();
+                Some(parser.expression())
+            } else {
+                assert_eq!(keyword, "var", "let {} needs a value", name);
+                None
+            };
+            parser.expect(";");
+            Statement::Declare(vec![(name, value)])
+        }
+        "return" => {
+            let value = parser.expression();
+            parser.expect(";");
+            Statement::Return(value)
+        }
+        "if" => {
+            let condition = parser.expression();
+            let then = Box::new(statement(parser));
+            let otherwise = if is_ident(parser, "else") {
+                parser.next();
+                Some(Box::new(statement(parser)))
+            } else {
+                None
+            };
+            Statement::If(condition, then, otherwise)
+        }
+        "for" => {
+            parser.expect("(");
+            let initialization = Box::new(statement(parser));
+            let condition = parser.expression();
+            parser.expect(";");
+            let step = Box::new(parser.step());
+            parser.expect(")");
+            Statement::For(initialization, condition, step, Box::new(statement(parser)))
+        }
+        _ => {
+            let swizzle = if parser.is_symbol(".") {
+                parser.next();
+                Some(parser.ident())
+            } else {
+                None
+            };
+            let operator = match parser.next() {
+                Token::Symbol(s) if ["=", "+=", "-=", "*=", "/="].contains(&s) => s,
+                t => panic!("expected assignment, got {:?}", t),
+            };
+            let value = parser.expression();
+            parser.expect(";");
+            Statement::Assign(keyword, swizzle, operator, value)
+        }
+    }
+}
+
+/// Renames the builtins in `statement` to GLSL.
+fn translate_statement(statement: &Statement, functions: &[String]) -> Statement {
+    let expr = |e: &Expr| translate(e, functions);
+    match statement {
+        Statement::Declare(declarations) => Statement::Declare(
+            declarations
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_ref().map(expr)))
+                .collect(),
+        ),
+        Statement::Assign(name, swizzle, operator, value) => {
+            Statement::Assign(name.clone(), swizzle.clone(), operator, expr(value))
+        }
+        Statement::If(condition, then, otherwise) => Statement::If(
+            expr(condition),
+            Box::new(translate_statement(then, functions)),
+            otherwise
+                .as_ref()
+                .map(|s| Box::new(translate_statement(s, functions))),
+        ),
+        Statement::For(initialization, condition, step, body) => Statement::For(
+            Box::new(translate_statement(initialization, functions)),
+            expr(condition),
+            step.clone(),
+            Box::new(translate_statement(body, functions)),
+        ),
+        Statement::Block(statements) => Statement::Block(
+            statements
+                .iter()
+                .map(|s| translate_statement(s, functions))
+                .collect(),
+        ),
+        Statement::Return(value) => Statement::Return(expr(value)),
+    }
+}
+
+/// Renames the builtins in `expr` to GLSL.
+fn translate(expr: &Expr, functions: &[String]) -> Expr {
+    let t = |e: &Expr| Box::new(translate(e, functions));
+    match expr {
+        Expr::Number(_) | Expr::Variable(_) => expr.clone(),
+        Expr::Call(name, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|a| translate(a, functions))
+                .collect::<Vec<_>>();
+            if functions.contains(name) {
+                return Expr::Call(name.clone(), arguments);
+            }
+            if name == "select" {
+                let [otherwise, then, condition] = <[Expr; 3]>::try_from(arguments).unwrap();
+                return Expr::Ternary(Box::new(condition), Box::new(then), Box::new(otherwise));
+            }
+            let glsl = BUILTINS
+                .iter()
+                .find(|(wgsl, _)| wgsl == name)
+                .unwrap_or_else(|| panic!("{} is no WGSL builtin", name))
+                .1;
+            Expr::Call(glsl.to_string(), arguments)
+        }
+        Expr::Swizzle(value, swizzle) => Expr::Swizzle(t(value), swizzle.clone()),
+        Expr::Index(value, index) => Expr::Index(t(value), t(index)),
+        Expr::Unary(operator, value) => Expr::Unary(operator, t(value)),
+        Expr::Binary(operator, lhs, rhs) => Expr::Binary(operator, t(lhs), t(rhs)),
+        Expr::Ternary(..) => panic!("WGSL has no ternary operator"),
+    }
+}